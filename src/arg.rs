@@ -1,5 +1,6 @@
 //! arg module define the application entry arguments [Arg]
 
+use std::fmt;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -11,28 +12,111 @@ use clap::{
     },
 };
 use clap_complete::Shell;
+use serde::{Deserialize, Deserializer};
+
+use crate::assertion::{Assertion, parse_assertion};
+use crate::curl;
+use crate::json_path::JsonPath;
+use crate::openapi;
+use crate::threshold::{Threshold, parse_threshold};
 
 fn is_number(s: &str) -> bool {
     s.parse::<u64>().is_ok()
 }
 
-fn parse_duration(arg: &str) -> Result<Duration, std::num::ParseIntError> {
+fn parse_duration(arg: &str) -> anyhow::Result<Duration> {
     if is_number(arg) {
         return Ok(Duration::from_secs(arg.parse()?));
     }
 
     let mut input = arg;
+    let mut unit = 1;
     if input.ends_with("s") {
         input = &arg[..arg.len() - 1]
+    } else if input.ends_with("m") {
+        input = &arg[..arg.len() - 1];
+        unit = 60;
+    } else if input.ends_with("h") {
+        input = &arg[..arg.len() - 1];
+        unit = 3600;
     }
 
-    let seconds = input.parse()?;
+    let seconds: u64 = input.parse()?;
+    let seconds = seconds
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow::anyhow!("duration `{arg}` is too large"))?;
     Ok(Duration::from_secs(seconds))
 }
 
-fn parse_percentiles(arg: &str) -> anyhow::Result<f32> {
-    let value = arg.parse::<f32>()?;
-    if value <= 0f32 || value >= 1f32 {
+/// parse a latency like `200ms`, `1.5s` or `500us`, a number without unit is
+/// in milliseconds
+pub(crate) fn parse_latency(arg: &str) -> anyhow::Result<Duration> {
+    let input = arg.trim();
+    let (value, unit) =
+        match input.find(|c: char| !(c.is_ascii_digit() || c == '.')) {
+            Some(pos) => input.split_at(pos),
+            None => (input, "ms"),
+        };
+    let nanos_per_unit = match unit {
+        "ns" => 1f64,
+        "us" | "µs" => 1e3,
+        "ms" => 1e6,
+        "s" => 1e9,
+        "m" => 60e9,
+        _ => anyhow::bail!(
+            "invalid latency `{arg}`, the unit must be one of ns, us, ms, s, m"
+        ),
+    };
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| anyhow::anyhow!("invalid latency `{arg}`"))?;
+    Ok(Duration::from_nanos((value * nanos_per_unit).round() as u64))
+}
+
+fn parse_stage(arg: &str) -> anyhow::Result<Stage> {
+    let parts: Vec<&str> = arg.trim().split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        anyhow::bail!(
+            "invalid stage `{arg}`, expected duration:rate[:connections], \
+            example: 30s:100rps or 2m:500rps:50c"
+        );
+    }
+
+    let duration = parse_duration(parts[0])?;
+    if duration.is_zero() {
+        anyhow::bail!("the duration of stage `{arg}` must be greater than 0");
+    }
+    let rate = parts[1].trim_end_matches("rps").parse::<u16>()?;
+    let connections = match parts.get(2) {
+        Some(connections) => {
+            let connections = connections.trim_end_matches("c").parse()?;
+            if connections == 0 {
+                anyhow::bail!(
+                    "the connections of stage `{arg}` must be greater than 0"
+                );
+            }
+            Some(connections)
+        },
+        None => None,
+    };
+
+    Ok(Stage {
+        duration,
+        rate,
+        connections,
+    })
+}
+
+pub(crate) fn parse_percentiles(arg: &str) -> anyhow::Result<f64> {
+    // p99.9 and 99.9% are the same as 0.999
+    let input = arg.trim();
+    let value =
+        match input.strip_prefix("p").or_else(|| input.strip_suffix("%")) {
+            // rounded to get rid of the error introduced by the division
+            Some(percent) => (percent.parse::<f64>()? * 1e10).round() / 1e12,
+            None => input.parse::<f64>()?,
+        };
+    if value <= 0f64 || value >= 1f64 {
         anyhow::bail!("{} must be limited to the range (0 to 1)", value);
     }
     Ok(value)
@@ -76,10 +160,132 @@ impl ValueEnum for OutputFormat {
     }
 }
 
-/// define supported http methods
-#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+/// define how the signature of `--hmac-secret` is encoded
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SignatureEncoding {
+    /// lowercase hex digits
+    #[default]
+    Hex,
+    /// standard base64 with padding
+    Base64,
+}
+
+impl IntoResettable<OsStr> for SignatureEncoding {
+    fn into_resettable(self) -> Resettable<OsStr> {
+        match self {
+            SignatureEncoding::Hex => Value(OsStr::from("HEX")),
+            SignatureEncoding::Base64 => Value(OsStr::from("BASE64")),
+        }
+    }
+}
+
+impl ValueEnum for SignatureEncoding {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[SignatureEncoding::Hex, SignatureEncoding::Base64]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            SignatureEncoding::Hex => PossibleValue::new("HEX"),
+            SignatureEncoding::Base64 => PossibleValue::new("BASE64"),
+        })
+    }
+}
+
+/// define how the rows of `--data-file` are used
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DataMode {
+    /// the rows are used in order by all connections
+    #[default]
+    Sequential,
+    /// a row is picked at random for each request
+    Random,
+    /// each connection keeps using one row
+    PerWorker,
+}
+
+impl IntoResettable<OsStr> for DataMode {
+    fn into_resettable(self) -> Resettable<OsStr> {
+        match self {
+            DataMode::Sequential => Value(OsStr::from("SEQUENTIAL")),
+            DataMode::Random => Value(OsStr::from("RANDOM")),
+            DataMode::PerWorker => Value(OsStr::from("PER_WORKER")),
+        }
+    }
+}
+
+impl ValueEnum for DataMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[DataMode::Sequential, DataMode::Random, DataMode::PerWorker]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            DataMode::Sequential => PossibleValue::new("SEQUENTIAL"),
+            DataMode::Random => PossibleValue::new("RANDOM"),
+            DataMode::PerWorker => PossibleValue::new("PER_WORKER"),
+        })
+    }
+}
+
+/// define how the requests imported from a HAR file are replayed
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ReplayMode {
+    /// each connection sends the requests in the original order
+    #[default]
+    Ordered,
+    /// like [ReplayMode::Ordered], and keeps the intervals between requests
+    Timed,
+    /// a request is picked at random for each iteration
+    Weighted,
+}
+
+impl IntoResettable<OsStr> for ReplayMode {
+    fn into_resettable(self) -> Resettable<OsStr> {
+        match self {
+            ReplayMode::Ordered => Value(OsStr::from("ORDERED")),
+            ReplayMode::Timed => Value(OsStr::from("TIMED")),
+            ReplayMode::Weighted => Value(OsStr::from("WEIGHTED")),
+        }
+    }
+}
+
+impl ValueEnum for ReplayMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[ReplayMode::Ordered, ReplayMode::Timed, ReplayMode::Weighted]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            ReplayMode::Ordered => PossibleValue::new("ORDERED"),
+            ReplayMode::Timed => PossibleValue::new("TIMED"),
+            ReplayMode::Weighted => PossibleValue::new("WEIGHTED"),
+        })
+    }
+}
+
+/// [Stage] is one step of a load profile, the rate changes linearly from the
+/// target rate of the previous stage (0 for the first one) to the target rate
+/// of this stage, so that ramp-up, hold and ramp-down can be described
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Stage {
+    /// how long the stage lasts
+    pub duration: Duration,
+
+    /// the target rate at the end of the stage, unit: reqs/s
+    pub rate: u16,
+
+    /// the number of active connections during the stage, all connections
+    /// are active when it's not given
+    pub connections: Option<u16>,
+}
+
+/// define supported http methods, the common ones are shortcuts, any other
+/// valid token is an extension method, e.g. `PROPFIND` of WebDAV
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq)]
 pub enum Method {
     /// Get request
+    #[default]
     Get,
     /// Post request
     Post,
@@ -91,11 +297,17 @@ pub enum Method {
     Head,
     /// patch request
     Patch,
+    /// options request, e.g. CORS preflight
+    Options,
+    /// trace request
+    Trace,
+    /// extension method, which is case-sensitive
+    Other(reqwest::Method),
 }
 
 impl Method {
     /// convert to [reqwest::Method]
-    pub(crate) fn to_reqwest_method(self) -> reqwest::Method {
+    pub(crate) fn to_reqwest_method(&self) -> reqwest::Method {
         match self {
             Method::Get => reqwest::Method::GET,
             Method::Post => reqwest::Method::POST,
@@ -103,10 +315,34 @@ impl Method {
             Method::Patch => reqwest::Method::PATCH,
             Method::Delete => reqwest::Method::DELETE,
             Method::Head => reqwest::Method::HEAD,
+            Method::Options => reqwest::Method::OPTIONS,
+            Method::Trace => reqwest::Method::TRACE,
+            Method::Other(method) => method.clone(),
         }
     }
 }
 
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_reqwest_method().as_str())
+    }
+}
+
+/// parse the shortcuts case-insensitively, and any other token as an
+/// extension method
+fn parse_method(method: &str) -> Result<Method, String> {
+    Method::from_str(method, true)
+}
+
+impl<'de> Deserialize<'de> for Method {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        Method::from_str(&method, true).map_err(serde::de::Error::custom)
+    }
+}
+
 impl IntoResettable<OsStr> for Method {
     fn into_resettable(self) -> Resettable<OsStr> {
         match self {
@@ -116,6 +352,9 @@ impl IntoResettable<OsStr> for Method {
             Method::Delete => Value(OsStr::from("DELETE")),
             Method::Head => Value(OsStr::from("HEAD")),
             Method::Patch => Value(OsStr::from("PATCH")),
+            Method::Options => Value(OsStr::from("OPTIONS")),
+            Method::Trace => Value(OsStr::from("TRACE")),
+            Method::Other(method) => Value(OsStr::from(method.to_string())),
         }
     }
 }
@@ -129,9 +368,28 @@ impl ValueEnum for Method {
             Method::Delete,
             Method::Head,
             Method::Patch,
+            Method::Options,
+            Method::Trace,
         ]
     }
 
+    /// the tokens other than the shortcuts are extension methods
+    fn from_str(input: &str, ignore_case: bool) -> Result<Self, String> {
+        let shortcut = Self::value_variants().iter().find(|method| {
+            let name = method.to_string();
+            match ignore_case {
+                true => name.eq_ignore_ascii_case(input),
+                false => name == input,
+            }
+        });
+        match shortcut {
+            Some(method) => Ok(method.clone()),
+            None => reqwest::Method::from_bytes(input.as_bytes())
+                .map(Method::Other)
+                .map_err(|_| format!("invalid method `{input}`")),
+        }
+    }
+
     fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
         Some(match self {
             Method::Get => PossibleValue::new("GET"),
@@ -140,6 +398,9 @@ impl ValueEnum for Method {
             Method::Delete => PossibleValue::new("DELETE"),
             Method::Head => PossibleValue::new("HEAD"),
             Method::Patch => PossibleValue::new("PATCH"),
+            Method::Options => PossibleValue::new("OPTIONS"),
+            Method::Trace => PossibleValue::new("TRACE"),
+            Method::Other(method) => PossibleValue::new(method.to_string()),
         })
     }
 }
@@ -151,7 +412,10 @@ impl ValueEnum for Method {
 #[command(group(ArgGroup::new("json").args(["json_body", "json_file"])))]
 #[command(group(ArgGroup::new("text").args(["text_body", "text_file"])))]
 #[command(group(ArgGroup::new("multipart").args(["mp", "mp_file"]).multiple(true)))]
-#[command(group(ArgGroup::new("mode").args(["duration", "requests"])))]
+#[command(group(ArgGroup::new("mode").args(["duration", "requests", "stages"])))]
+#[command(group(ArgGroup::new("http2_mode").args(["http2", "http2_prior_knowledge"])))]
+#[command(group(ArgGroup::new("http2_streams").args(["http2", "http2_prior_knowledge", "grpc_method"]).multiple(true)))]
+#[command(group(ArgGroup::new("grpc_schema").args(["proto", "protoset"])))]
 #[command(help_template(
     "\
 {before-help}{name}({version}){tab}{about-with-newline}
@@ -191,17 +455,29 @@ pub struct Arg {
         value_parser = parse_percentiles,
         value_delimiter = ',',
         default_value = "0.5,0.75,0.9,0.99",
-        help = "Custom latency percentiles"
+        help = "Custom latency percentiles, example: \
+        --percentiles=0.5,0.99,p99.9,99.99%"
     )]
-    pub(crate) percentiles: Vec<f32>,
+    pub(crate) percentiles: Vec<f64>,
+
+    /// significant value digits of the latency histogram
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=5),
+        default_value_t = 3,
+        help = "Number of significant value digits kept by the latency \
+        histogram, from 1 to 5"
+    )]
+    pub(crate) precision: u8,
 
     /// Request method
     #[arg(
         long,
         short,
         default_value = Method::Get,
-        value_enum,
-        help = "Request method"
+        value_parser = parse_method,
+        help = "Request method, one of GET, POST, PUT, DELETE, HEAD, PATCH, \
+        OPTIONS and TRACE, or an extension method like PROPFIND"
     )]
     pub method: Method,
 
@@ -209,6 +485,37 @@ pub struct Arg {
     #[arg(long, short = 'a', help = "Disable HTTP keep-alive")]
     pub(crate) disable_keep_alive: bool,
 
+    /// Don't read response bodies
+    #[arg(
+        long,
+        help = "Don't read response bodies, the latency only covers the \
+        response headers, and the received bytes don't include the bodies"
+    )]
+    pub(crate) skip_body: bool,
+
+    /// Rules which every response should satisfy
+    #[arg(
+        long = "assert",
+        value_parser = parse_assertion,
+        help = "Rule which every response should satisfy, the responses \
+        which fail any rule are counted per rule, example: --assert status=2xx \
+        --assert 'header:content-type~json' --assert 'body~ok' \
+        --assert 'json:$.data.id exists' --assert 'latency<200ms'"
+    )]
+    pub(crate) assertions: Vec<Assertion>,
+
+    /// Pass/fail criteria of the final result
+    #[arg(
+        long = "threshold",
+        value_parser = parse_threshold,
+        help = "Pass/fail criterion of the final result, rsb exits with code \
+        99 if any criterion is not met, supports avg, min, max, stdev, \
+        percentiles like p99, error_rate, rps and throughput, example: \
+        --threshold 'p99<250ms' --threshold 'error_rate<1%' \
+        --threshold 'rps>1000'"
+    )]
+    pub(crate) thresholds: Vec<Threshold>,
+
     #[arg(
         long,
         short = 'H',
@@ -223,7 +530,7 @@ pub struct Arg {
         long,
         short = 'n',
         help = "Number of requests",
-        required_unless_present_any(["duration", "completions"])
+        required_unless_present_any(["duration", "stages", "completions", "openapi_list"])
     )]
     pub requests: Option<u64>,
 
@@ -233,7 +540,7 @@ pub struct Arg {
         short = 'd',
         value_parser = parse_duration,
         help = "Duration of test",
-        required_unless_present_any(["requests", "completions"])
+        required_unless_present_any(["requests", "stages", "completions", "openapi_list"])
     )]
     pub duration: Option<Duration>,
 
@@ -241,6 +548,26 @@ pub struct Arg {
     #[arg(long, short = 'r', help = "Rate limit in requests per second")]
     pub(crate) rate: Option<u16>,
 
+    /// Send requests at a constant arrival rate (open model)
+    #[arg(
+        long,
+        requires("rate"),
+        help = "Send requests at the constant arrival rate given by --rate \
+        (open model) and correct latencies for coordinated omission"
+    )]
+    pub(crate) open_model: bool,
+
+    /// Stages of the load profile
+    #[arg(
+        long = "stage",
+        value_parser = parse_stage,
+        conflicts_with_all(["rate"]),
+        help = "Stage of the load profile, the rate changes linearly to the \
+        target of each stage, example: --stage 30s:100rps --stage \
+        2m:500rps:100c --stage 30s:0rps"
+    )]
+    pub stages: Vec<Stage>,
+
     /// Path to the client's TLS Certificate
     #[arg(
         long,
@@ -268,6 +595,134 @@ pub struct Arg {
     )]
     pub(crate) insecure: bool,
 
+    /// Only use HTTP/1.1
+    #[arg(
+        long,
+        conflicts_with = "http2_mode",
+        help = "Only use HTTP/1.1, without negotiating HTTP/2 by ALPN"
+    )]
+    pub(crate) http1_only: bool,
+
+    /// Only use HTTP/2, negotiated by ALPN
+    #[arg(
+        long,
+        help = "Only use HTTP/2, the TLS handshake only offers h2 by ALPN, so \
+        it fails instead of downgrading to HTTP/1.1"
+    )]
+    pub(crate) http2: bool,
+
+    /// Use HTTP/2 without negotiation
+    #[arg(
+        long,
+        help = "Use HTTP/2 without negotiation, which is h2c for http urls"
+    )]
+    pub(crate) http2_prior_knowledge: bool,
+
+    /// Maximum number of concurrent streams over each HTTP/2 connection
+    #[arg(
+        long,
+        requires = "http2_streams",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Maximum number of concurrent streams over each HTTP/2 \
+        connection, the connections are opened for every N of them, all \
+        streams share one connection by default"
+    )]
+    pub(crate) http2_max_streams: Option<u16>,
+
+    /// Send the requests over HTTP/3
+    #[arg(
+        long,
+        conflicts_with_all(["http1_only", "http2_mode", "cert", "mp", "mp_file"]),
+        help = "Send the requests over HTTP/3, which needs rsb built with the \
+        `http3` feature"
+    )]
+    pub(crate) http3: bool,
+
+    /// Json path which matches the WebSocket messages with their replies
+    #[arg(
+        long,
+        value_parser = JsonPath::parse,
+        help = "Json path of the value which matches a WebSocket message with \
+        its reply, e.g. $.id, the other messages received meanwhile are \
+        counted as unmatched, the first message received is the reply by \
+        default"
+    )]
+    pub(crate) ws_match: Option<JsonPath>,
+
+    /// Unary gRPC method to call
+    #[arg(
+        long,
+        requires = "grpc_schema",
+        conflicts_with_all(["scenario", "har", "openapi", "from_curl", "form", "mp", "mp_file", "text_body", "text_file", "json_command", "http1_only", "http3", "assertions", "cookie_jar", "aws_sigv4", "hmac_secret"]),
+        help = "Call the unary gRPC method, e.g. helloworld.Greeter/SayHello, \
+        with the request message given by --json-body or --json-file, `{}` by \
+        default, the url is the address of the server, e.g. \
+        http://127.0.0.1:50051"
+    )]
+    pub(crate) grpc_method: Option<String>,
+
+    /// The .proto file of the gRPC method
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        requires = "grpc_method",
+        help = "The .proto file which defines the service of --grpc-method"
+    )]
+    pub(crate) proto: Option<PathBuf>,
+
+    /// Directories to search the imports of the .proto file
+    #[arg(
+        long = "import-path",
+        value_hint = ValueHint::DirPath,
+        requires = "proto",
+        help = "Directory to search the imports of --proto, the directory of \
+        --proto is searched as well"
+    )]
+    pub(crate) import_paths: Vec<PathBuf>,
+
+    /// The descriptor set of the gRPC method
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        requires = "grpc_method",
+        help = "The descriptor set which defines the service of \
+        --grpc-method, such as the one written by \
+        `protoc --include_imports -o`"
+    )]
+    pub(crate) protoset: Option<PathBuf>,
+
+    /// Measure the responses as streams of events
+    #[arg(
+        long,
+        conflicts_with_all(["skip_body", "grpc_method"]),
+        help = "Measure the responses as streams of events, which are the \
+        Server-Sent Events of `text/event-stream` responses, or the lines of \
+        the others, such as the chunks of a token stream, the time to the \
+        first byte and the first event, the gaps between events, the number \
+        of events and the duration of each stream are reported"
+    )]
+    pub(crate) stream: bool,
+
+    /// Stop reading each stream after the number of events
+    #[arg(
+        long,
+        requires = "stream",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Stop reading each stream once the number of events are \
+        received"
+    )]
+    pub(crate) stream_events: Option<u64>,
+
+    /// Stop reading each stream after the duration
+    #[arg(
+        long,
+        requires = "stream",
+        value_parser = parse_duration,
+        help = "Stop reading each stream once the duration has passed since \
+        its request was sent, it must be shorter than --timeout"
+    )]
+    pub(crate) stream_duration: Option<Duration>,
+
     /// File to use as json request body
     #[arg(
         long,
@@ -288,9 +743,33 @@ pub struct Arg {
     #[arg(
     long,
     conflicts_with_all(["mp_file", "mp", "form", "text_body", "text_file", "json_file", "json_body"]),
-    help = "Build request body from external command for ContentType: application/json")]
+    help = "Build request body from external command for ContentType: application/json, \
+    the command line is split like a shell, and it's run once before the test, or for each request with \
+    --rebuild-body")]
     pub(crate) json_command: Option<String>,
 
+    /// run the json command as a long-lived generator
+    #[arg(
+        long,
+        requires = "json_command",
+        conflicts_with = "rebuild_body",
+        help = "Run --json-command as a long-lived generator, which writes one \
+        body per line on stdout, a new line is read for each request, and the \
+        test stops once the generator exits"
+    )]
+    pub(crate) json_generator: bool,
+
+    /// timeout of the json command
+    #[arg(
+        long,
+        value_parser = parse_duration,
+        default_value = "10s",
+        requires = "json_command",
+        help = "Timeout of each run of --json-command, or of reading each \
+        line of --json-generator"
+    )]
+    pub(crate) json_command_timeout: Duration,
+
     /// File to use as text request Body
     #[arg(
         long,
@@ -329,6 +808,15 @@ pub struct Arg {
     )]
     pub(crate) mp_file: Vec<(String, PathBuf)>,
 
+    /// build the body for each request
+    #[arg(
+        long,
+        help = "Build the body for each request, --text-file, --json-file and \
+        --mp-file are read and --json-command is run again for each request, \
+        instead of once before the test"
+    )]
+    pub(crate) rebuild_body: bool,
+
     /// form request parameters
     #[arg(
         long,
@@ -339,6 +827,242 @@ pub struct Arg {
     )]
     pub(crate) form: Vec<String>,
 
+    /// Scenario file which defines a weighted mix of named requests
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all(["method", "mp_file", "mp", "form", "text_body", "text_file", "json_file", "json_body", "json_command"]),
+        help = "TOML, YAML or JSON file which defines a weighted mix of named \
+        requests, or a sequence of steps, each with its own method, url, \
+        headers and body, relative urls are resolved against <URL>"
+    )]
+    pub scenario: Option<PathBuf>,
+
+    /// curl command line which describes the request
+    #[arg(
+        long,
+        conflicts_with_all(["url", "scenario", "har", "method", "mp_file", "mp", "form", "text_body", "text_file", "json_file", "json_body", "json_command"]),
+        help = "Curl command line, such as the one copied by \"Copy as cURL\" \
+        of browsers, whose url, method, headers and body are used, supports: \
+        -X, -H, -b, -u, -d, --data-raw, --data-binary, -F, -k, -I, --cert and \
+        --key"
+    )]
+    pub from_curl: Option<String>,
+
+    /// HAR file whose entries are replayed
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all(["scenario", "method", "mp_file", "mp", "form", "text_body", "text_file", "json_file", "json_body", "json_command", "data_file"]),
+        help = "HAR file captured by browsers, each entry is replayed with its \
+        method, url, headers and body as they were captured, the scheme, host \
+        and port are replaced by <URL> when it's given"
+    )]
+    pub har: Option<PathBuf>,
+
+    /// how the entries of the HAR file are replayed
+    #[arg(
+        long,
+        default_value = ReplayMode::Ordered,
+        value_enum,
+        requires = "har",
+        help = "How the entries of --har are replayed, ORDERED: in the original \
+        order by each connection, TIMED: in order with the original intervals, \
+        WEIGHTED: picked at random"
+    )]
+    pub(crate) har_mode: ReplayMode,
+
+    /// OpenAPI document whose operations are sent
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all(["scenario", "har", "from_curl", "method", "mp_file", "mp", "form", "text_body", "text_file", "json_file", "json_body", "json_command"]),
+        help = "OpenAPI 3 document in JSON or YAML, each operation is sent in \
+        order with the examples of its parameters and body, the parameters \
+        named after the columns of --data-file are filled with the rows, the \
+        servers of the document are replaced by <URL> when it's given"
+    )]
+    pub openapi: Option<PathBuf>,
+
+    /// List the operations of --openapi
+    #[arg(
+        long,
+        requires = "openapi",
+        help = "Print the method, path and operationId of each operation of \
+        --openapi, and exit without sending any request"
+    )]
+    pub(crate) openapi_list: bool,
+
+    /// CSV or JSONL file whose rows are used as template variables
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        help = "CSV or JSONL file, the columns of each row are used as \
+        template variables like {{user_id}} in the url, headers and body"
+    )]
+    pub(crate) data_file: Option<PathBuf>,
+
+    /// how the rows of the data file are used
+    #[arg(
+        long,
+        default_value = DataMode::Sequential,
+        value_enum,
+        requires = "data_file",
+        help = "How the rows of --data-file are used, SEQUENTIAL: in order by \
+        all connections, RANDOM: picked at random, PER_WORKER: one row per \
+        connection"
+    )]
+    pub(crate) data_mode: DataMode,
+
+    /// stop once all rows of the data file are used
+    #[arg(
+        long,
+        requires = "data_file",
+        help = "Use each row of --data-file only once, and stop the test once \
+        all rows are used"
+    )]
+    pub(crate) data_once: bool,
+
+    /// keep the cookies of each connection
+    #[arg(
+        long,
+        help = "Keep a cookie jar for each connection like a virtual user, the \
+        cookies set by the responses are sent by its later requests"
+    )]
+    pub(crate) cookie_jar: bool,
+
+    /// Netscape cookie file which seeds the cookie jars
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        requires = "cookie_jar",
+        help = "Netscape cookie file, such as the one written by curl -c, whose \
+        cookies are put into the jar of each connection at the start"
+    )]
+    pub(crate) cookie_file: Option<PathBuf>,
+
+    /// reset the cookie jars on each iteration
+    #[arg(
+        long,
+        requires = "cookie_jar",
+        help = "Reset the cookie jar of each connection to the cookies of \
+        --cookie-file at the start of each iteration, which is one request, or \
+        the sequence of steps of --scenario"
+    )]
+    pub(crate) cookie_reset: bool,
+
+    /// credentials of basic auth
+    #[arg(
+        long,
+        value_name = "USER:PASS",
+        conflicts_with_all(["bearer", "oauth2_token_url"]),
+        help = "Basic auth credentials, sent as Authorization: Basic <base64>"
+    )]
+    pub(crate) basic: Option<String>,
+
+    /// token of bearer auth
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        conflicts_with = "oauth2_token_url",
+        help = "Bearer token, sent as Authorization: Bearer <TOKEN>"
+    )]
+    pub(crate) bearer: Option<String>,
+
+    /// token endpoint of the OAuth2 client credentials flow
+    #[arg(
+        long,
+        value_hint = ValueHint::Url,
+        requires_all(["oauth2_client_id", "oauth2_client_secret"]),
+        help = "Token endpoint of the OAuth2 client credentials flow, the \
+        access token is fetched before the test and refreshed before it \
+        expires, and sent as Authorization: Bearer <TOKEN>"
+    )]
+    pub(crate) oauth2_token_url: Option<String>,
+
+    /// client id of the OAuth2 client credentials flow
+    #[arg(
+        long,
+        requires = "oauth2_token_url",
+        help = "Client id of --oauth2-token-url"
+    )]
+    pub(crate) oauth2_client_id: Option<String>,
+
+    /// client secret of the OAuth2 client credentials flow
+    #[arg(
+        long,
+        requires = "oauth2_token_url",
+        help = "Client secret of --oauth2-token-url"
+    )]
+    pub(crate) oauth2_client_secret: Option<String>,
+
+    /// scope of the OAuth2 client credentials flow
+    #[arg(
+        long,
+        requires = "oauth2_token_url",
+        help = "Scope of the access token of --oauth2-token-url"
+    )]
+    pub(crate) oauth2_scope: Option<String>,
+
+    /// region and service of AWS Signature Version 4
+    #[arg(
+        long,
+        value_name = "REGION:SERVICE",
+        conflicts_with_all(["hmac_secret", "basic", "bearer", "oauth2_token_url", "mp", "mp_file"]),
+        help = "Sign each request by AWS Signature Version 4 for the region \
+        and service, e.g. us-east-1:execute-api, with the credentials of \
+        AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN"
+    )]
+    pub(crate) aws_sigv4: Option<String>,
+
+    /// secret of the HMAC-SHA256 signature
+    #[arg(
+        long,
+        conflicts_with_all(["mp", "mp_file"]),
+        help = "Sign each request by HMAC-SHA256 of the secret, over the \
+        method, path, timestamp, --hmac-signed-headers and the SHA-256 of the \
+        body"
+    )]
+    pub(crate) hmac_secret: Option<String>,
+
+    /// header of the HMAC-SHA256 signature
+    #[arg(
+        long,
+        default_value = "X-Signature",
+        requires = "hmac_secret",
+        help = "Header of the signature of --hmac-secret"
+    )]
+    pub(crate) hmac_header: String,
+
+    /// header of the timestamp of the HMAC-SHA256 signature
+    #[arg(
+        long,
+        default_value = "X-Timestamp",
+        requires = "hmac_secret",
+        help = "Header of the unix timestamp in seconds of --hmac-secret"
+    )]
+    pub(crate) hmac_timestamp_header: String,
+
+    /// headers covered by the HMAC-SHA256 signature
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "hmac_secret",
+        help = "Headers covered by the signature of --hmac-secret, in order, \
+        e.g. content-type,x-api-key"
+    )]
+    pub(crate) hmac_signed_headers: Vec<String>,
+
+    /// encoding of the HMAC-SHA256 signature
+    #[arg(
+        long,
+        default_value = SignatureEncoding::Hex,
+        value_enum,
+        requires = "hmac_secret",
+        help = "Encoding of the signature of --hmac-secret"
+    )]
+    pub(crate) hmac_encoding: SignatureEncoding,
+
     /// Output Format
     #[arg(
         long,
@@ -355,16 +1079,59 @@ pub struct Arg {
 
     /// Target Url
     #[arg(
-        required_unless_present("completions"), 
+        required_unless_present_any(["completions", "scenario", "har", "openapi", "from_curl"]),
         value_hint = ValueHint::Url,
         help = "Target Url"
     )]
     pub url: Option<String>,
 }
 
+impl Arg {
+    /// fill the url, method, headers and body with the command given by
+    /// `--from-curl`, it's done only once
+    pub fn apply_from_curl(&mut self) -> anyhow::Result<()> {
+        if let Some(command) = self.from_curl.take() {
+            curl::apply(&command, self)?;
+        }
+        Ok(())
+    }
+
+    /// the operations of the document given by `--openapi`, one line for each,
+    /// None without `--openapi-list`
+    pub fn openapi_operations(&self) -> anyhow::Result<Option<String>> {
+        match (&self.openapi, self.openapi_list) {
+            (Some(path), true) => openapi::list(path).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// the duration of the test, which is given by `--duration`, or the sum of
+    /// the duration of all stages
+    pub fn test_duration(&self) -> Option<Duration> {
+        if !self.stages.is_empty() {
+            return Some(self.stages.iter().map(|stage| stage.duration).sum());
+        }
+        self.duration
+    }
+
+    /// whether the messages are sent over WebSocket, which is given by a
+    /// `ws://` or `wss://` url
+    pub fn is_websocket(&self) -> bool {
+        self.url.as_deref().is_some_and(|url| {
+            let url = url.trim_start().to_ascii_lowercase();
+            url.starts_with("ws://") || url.starts_with("wss://")
+        })
+    }
+
+    /// the gRPC method called instead of sending HTTP requests
+    pub fn grpc_method(&self) -> Option<&str> {
+        self.grpc_method.as_deref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use clap::{Command, CommandFactory};
+    use clap::{Command, CommandFactory, FromArgMatches};
 
     use super::*;
     const URI: &str = "https://localhost/test";
@@ -381,28 +1148,101 @@ mod tests {
         assert!(parse_duration("123").is_ok());
         assert!(parse_duration("123s").is_ok());
         assert!(parse_duration("123x").is_err());
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        let max = u64::MAX.to_string();
+        assert!(parse_duration(&max).is_ok());
+        assert!(parse_duration(&format!("{max}s")).is_ok());
+        assert!(parse_duration(&format!("{}m", u64::MAX / 60 + 1)).is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 3600 + 1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_latency() {
+        assert_eq!(parse_latency("200ms").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_latency("200").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_latency("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_latency("500us").unwrap(), Duration::from_micros(500));
+        assert_eq!(parse_latency("1m").unwrap(), Duration::from_secs(60));
+        assert!(parse_latency("200x").is_err());
+        assert!(parse_latency("ms").is_err());
+        assert!(parse_latency("1.2.3ms").is_err());
+    }
+
+    #[test]
+    fn test_parse_stage() {
+        let stage = parse_stage("30s:100rps").unwrap();
+        assert_eq!(stage.duration, Duration::from_secs(30));
+        assert_eq!(stage.rate, 100);
+        assert_eq!(stage.connections, None);
+
+        let stage = parse_stage("2m:500:20c").unwrap();
+        assert_eq!(stage.duration, Duration::from_secs(120));
+        assert_eq!(stage.rate, 500);
+        assert_eq!(stage.connections, Some(20));
+
+        assert!(parse_stage("30s:0rps").is_ok());
+        assert!(parse_stage("30s").is_err());
+        assert!(parse_stage("0s:100rps").is_err());
+        assert!(parse_stage("30s:100rps:0c").is_err());
+        assert!(parse_stage("30s:100rps:1c:1").is_err());
+        assert!(parse_stage("30x:100rps").is_err());
+    }
+
+    #[test]
+    fn test_stages_params() {
+        let mut cmd = Arg::command();
+        let args =
+            vec![BINARY, "--stage", "30s:100rps", "--stage", "1m:100rps", URI];
+        let mut result = cmd.try_get_matches_from_mut(args);
+        assert!(result.as_ref().is_ok());
+        let arg = Arg::from_arg_matches_mut(result.as_mut().unwrap()).unwrap();
+        assert_eq!(arg.stages.len(), 2);
+        assert_eq!(arg.test_duration(), Some(Duration::from_secs(90)));
+
+        // stages cannot be used with other modes or rate
+        for conflict in [["-n", "20"], ["-d", "20"], ["-r", "20"]] {
+            let args = vec![
+                BINARY,
+                "--stage",
+                "30s:100rps",
+                conflict[0],
+                conflict[1],
+                URI,
+            ];
+            let result = cmd.try_get_matches_from_mut(args);
+            assert!(result.as_ref().is_err());
+        }
     }
 
     #[test]
     fn test_method_choices() {
         let mut cmd = Arg::command();
-        let methods = vec!["GET", "PUT", "POST", "DELETE", "HEAD", "PATCH"];
+        let methods = vec![
+            "GET", "PUT", "POST", "DELETE", "HEAD", "PATCH", "OPTIONS",
+            "TRACE", "PROPFIND",
+        ];
         for method in methods {
             let args = vec![BINARY, "-n", "20", "-m", method, URI];
             let result = cmd.try_get_matches_from_mut(args);
             assert!(result.is_ok());
         }
 
+        // the shortcuts are case-insensitive, the extension methods are kept
+        let arg =
+            Arg::try_parse_from([BINARY, "-n", "20", "-m", "options", URI])
+                .unwrap();
+        assert_eq!(arg.method, Method::Options);
+        let arg = Arg::try_parse_from([BINARY, "-n", "20", "-m", "MkCol", URI])
+            .unwrap();
+        assert_eq!(arg.method.to_reqwest_method().as_str(), "MkCol");
+
         let result = cmd.try_get_matches_from_mut(vec![
-            BINARY, "-n", "20", "-m", "get", URI,
+            BINARY, "-n", "20", "-m", "GET /", URI,
         ]);
         assert!(result.as_ref().is_err());
         let err_msg = result.err().unwrap().to_string();
-        assert!(
-            err_msg.contains(
-                "possible values: GET, PUT, POST, DELETE, HEAD, PATCH"
-            )
-        );
+        assert!(err_msg.contains("invalid method `GET /`"));
     }
 
     #[test]
@@ -651,6 +1491,429 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scenario_conflicts_with_other_body() {
+        let cmd = Arg::command();
+        let args =
+            vec![BINARY, "-n", "20", "--scenario", "s.toml", "xx", "xx", URI];
+        let conflicts_params = vec![
+            "--mp-file",
+            "--mp",
+            "--form",
+            "--text-body",
+            "--text-file",
+            "--json-body",
+            "--json-file",
+            "--json-command",
+        ];
+        validate_args_conflict(conflicts_params, args, cmd);
+
+        let result = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--scenario",
+            "s.toml",
+            "--method",
+            "POST",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_scenario_without_url() {
+        let arg =
+            Arg::try_parse_from([BINARY, "-n", "20", "--scenario", "s.toml"]);
+        assert!(arg.is_ok());
+        assert_eq!(arg.unwrap().url, None);
+    }
+
+    #[test]
+    fn test_from_curl_options() {
+        let mut arg = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "-H",
+            "x-id:1",
+            "--from-curl",
+            "curl -X PUT https://a.com -H 'x-token: a'",
+        ])
+        .unwrap();
+        assert_eq!(arg.url, None);
+        arg.apply_from_curl().unwrap();
+        assert_eq!(arg.url.as_deref(), Some("https://a.com"));
+        assert_eq!(arg.method, Method::Put);
+        assert_eq!(arg.headers, vec!["x-id:1", "x-token:a"]);
+        assert!(arg.from_curl.is_none());
+
+        let result = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--from-curl",
+            "curl",
+            URI,
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_har_options() {
+        let arg = Arg::try_parse_from([BINARY, "-n", "20", "--har", "s.har"])
+            .unwrap();
+        assert_eq!(arg.url, None);
+        assert_eq!(arg.har_mode, ReplayMode::Ordered);
+
+        for (args, ok) in [
+            (vec!["--har", "s.har", "--har-mode", "TIMED"], true),
+            (vec!["--har", "s.har", "--scenario", "s.toml"], false),
+            (vec!["--har", "s.har", "--json-body", "{}"], false),
+            // the captured entries have no placeholders to fill
+            (vec!["--har", "s.har", "--data-file", "d.csv"], false),
+            (vec!["--har-mode", "WEIGHTED", URI], false),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"].into_iter().chain(args.clone()),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_openapi_options() {
+        for (args, ok) in [
+            (vec!["--openapi", "api.yaml"], true),
+            (vec!["--openapi", "api.yaml", URI], true),
+            (
+                vec!["--openapi", "api.yaml", "--data-file", "ids.csv"],
+                true,
+            ),
+            (vec!["--openapi", "api.yaml", "--har", "s.har"], false),
+            (vec!["--openapi", "api.yaml", "--scenario", "s.toml"], false),
+            (vec!["--openapi", "api.yaml", "-m", "POST"], false),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"].into_iter().chain(args.clone()),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+
+        // no requests are sent when the operations are listed
+        let arg = Arg::try_parse_from([
+            BINARY,
+            "--openapi",
+            "api.yaml",
+            "--openapi-list",
+        ])
+        .unwrap();
+        assert!(arg.openapi_list);
+        assert!(
+            Arg::try_parse_from([BINARY, "-n", "20", "--openapi-list", URI])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cookie_options() {
+        for (args, ok) in [
+            (vec!["--cookie-jar"], true),
+            (
+                vec![
+                    "--cookie-jar",
+                    "--cookie-file",
+                    "c.txt",
+                    "--cookie-reset",
+                ],
+                true,
+            ),
+            (vec!["--cookie-file", "c.txt"], false),
+            (vec!["--cookie-reset"], false),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_auth_options() {
+        let oauth2 = [
+            "--oauth2-token-url",
+            "http://a/token",
+            "--oauth2-client-id",
+            "id",
+            "--oauth2-client-secret",
+            "secret",
+        ];
+        for (args, ok) in [
+            (vec!["--basic", "u:p"], true),
+            (vec!["--bearer", "t"], true),
+            (oauth2.to_vec(), true),
+            ([&oauth2[..], &["--oauth2-scope", "read"]].concat(), true),
+            (vec!["--basic", "u:p", "--bearer", "t"], false),
+            ([&oauth2[..], &["--bearer", "t"]].concat(), false),
+            (oauth2[..4].to_vec(), false),
+            (vec!["--oauth2-scope", "read"], false),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_http_version_options() {
+        for (args, ok) in [
+            (vec!["--http1-only"], true),
+            (vec!["--http2"], true),
+            (
+                vec!["--http2-prior-knowledge", "--http2-max-streams", "8"],
+                true,
+            ),
+            (vec!["--http2", "--http2-prior-knowledge"], false),
+            (vec!["--http1-only", "--http2"], false),
+            (vec!["--http2-max-streams", "8"], false),
+            (vec!["--http2", "--http2-max-streams", "0"], false),
+            (vec!["--http3"], true),
+            (vec!["--http3", "--http2"], false),
+            (vec!["--http3", "--http1-only"], false),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_grpc_options() {
+        for (args, ok) in [
+            (vec!["--grpc-method", "a.B/C", "--proto", "a.proto"], true),
+            (vec!["--grpc-method", "a.B/C", "--protoset", "a.pb"], true),
+            (
+                vec![
+                    "--grpc-method",
+                    "a.B/C",
+                    "--proto",
+                    "a.proto",
+                    "--import-path",
+                    "protos",
+                    "--http2-max-streams",
+                    "8",
+                ],
+                true,
+            ),
+            (vec!["--grpc-method", "a.B/C"], false),
+            (vec!["--proto", "a.proto"], false),
+            (
+                vec![
+                    "--grpc-method",
+                    "a.B/C",
+                    "--proto",
+                    "a.proto",
+                    "--protoset",
+                    "a.pb",
+                ],
+                false,
+            ),
+            (
+                vec![
+                    "--grpc-method",
+                    "a.B/C",
+                    "--protoset",
+                    "a.pb",
+                    "--text-body",
+                    "a",
+                ],
+                false,
+            ),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert_eq!(arg.is_ok(), ok, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_stream_options() {
+        let arg = Arg::parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--stream",
+            "--stream-events",
+            "10",
+            "--stream-duration",
+            "5s",
+            URI,
+        ]);
+        assert!(arg.stream);
+        assert_eq!(arg.stream_events, Some(10));
+        assert_eq!(arg.stream_duration, Some(Duration::from_secs(5)));
+        for args in [
+            vec!["--stream-events", "10"],
+            vec!["--stream", "--stream-events", "0"],
+            vec!["--stream", "--skip-body"],
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert!(arg.is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_websocket_options() {
+        let arg = Arg::parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--ws-match",
+            "$.id",
+            "WSS://localhost/ws",
+        ]);
+        assert!(arg.is_websocket());
+        assert_eq!(arg.ws_match.unwrap().to_string(), "$.id");
+        assert!(!Arg::parse_from([BINARY, "-n", "20", URI]).is_websocket());
+        assert!(
+            Arg::try_parse_from([BINARY, "-n", "20", "--ws-match", "id", URI])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_sign_options() {
+        let arg = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--hmac-secret",
+            "s",
+            "--hmac-signed-headers",
+            "content-type,x-api-key",
+            "--hmac-encoding",
+            "BASE64",
+            URI,
+        ])
+        .unwrap();
+        assert_eq!(arg.hmac_header, "X-Signature");
+        assert_eq!(arg.hmac_signed_headers, ["content-type", "x-api-key"]);
+        assert_eq!(arg.hmac_encoding, SignatureEncoding::Base64);
+
+        for args in [
+            vec!["--aws-sigv4", "us-east-1:s3", "--hmac-secret", "s"],
+            vec!["--hmac-header", "x-sig"],
+            vec!["--hmac-secret", "s", "--mp", "k:v"],
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert!(arg.is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_json_command_options() {
+        let arg = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--json-command",
+            "gen --count 1",
+            "--json-generator",
+            "--json-command-timeout",
+            "2s",
+            URI,
+        ])
+        .unwrap();
+        assert!(arg.json_generator);
+        assert_eq!(arg.json_command_timeout, Duration::from_secs(2));
+
+        for args in [
+            vec!["--json-generator"],
+            vec!["--json-command-timeout", "1s"],
+            vec![
+                "--json-command",
+                "gen",
+                "--json-generator",
+                "--rebuild-body",
+            ],
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            );
+            assert!(arg.is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_data_file_options() {
+        let arg = Arg::try_parse_from([
+            BINARY,
+            "-n",
+            "20",
+            "--data-file",
+            "users.csv",
+            "--data-mode",
+            "PER_WORKER",
+            URI,
+        ])
+        .unwrap();
+        assert_eq!(arg.data_mode, DataMode::PerWorker);
+        assert!(!arg.data_once);
+
+        let arg = Arg::try_parse_from([BINARY, "-n", "20", URI]).unwrap();
+        assert_eq!(arg.data_mode, DataMode::Sequential);
+
+        for args in [["--data-mode", "RANDOM"], ["--data-once", "--"]] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20"].into_iter().chain(args).chain([URI]),
+            );
+            assert!(arg.is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_method_deserialize() {
+        let method: Method = serde_json::from_str("\"post\"").unwrap();
+        assert_eq!(method, Method::Post);
+        let method: Method = serde_json::from_str("\"DELETE\"").unwrap();
+        assert_eq!(method, Method::Delete);
+        let method: Method = serde_json::from_str("\"options\"").unwrap();
+        assert_eq!(method, Method::Options);
+        let method: Method = serde_json::from_str("\"PROPFIND\"").unwrap();
+        assert_eq!(method.to_string(), "PROPFIND");
+        assert!(matches!(method, Method::Other(_)));
+        assert!(serde_json::from_str::<Method>("\"GET /\"").is_err());
+        assert!(serde_json::from_str::<Method>("\"\"").is_err());
+        assert_eq!(Method::default(), Method::Get);
+    }
+
     #[test]
     fn test_text_file_conflicts_with_other_body() {
         let cmd = Arg::command();
@@ -810,7 +2073,7 @@ mod tests {
         let percentiles = result
             .as_ref()
             .unwrap()
-            .get_many::<f32>("percentiles")
+            .get_many::<f64>("percentiles")
             .unwrap()
             .collect::<Vec<_>>();
         assert_eq!(format!("{percentiles:?}"), "[0.5, 0.75, 0.9, 0.99]");
@@ -823,7 +2086,7 @@ mod tests {
         let percentiles = result
             .as_ref()
             .unwrap()
-            .get_many::<f32>("percentiles")
+            .get_many::<f64>("percentiles")
             .unwrap()
             .collect::<Vec<_>>();
         assert_eq!(format!("{percentiles:?}"), "[0.6, 0.8, 0.9, 0.95]");
@@ -851,6 +2114,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_open_model_requires_rate() {
+        let mut cmd = Arg::command();
+
+        // without rate
+        let args = vec![BINARY, "-n", "20", "--open-model", URI];
+        let result = cmd.try_get_matches_from_mut(args);
+        assert!(result.as_ref().is_err());
+        let err_msg = result.err().unwrap().to_string();
+        assert!(err_msg.contains("--rate <RATE>"));
+
+        // with rate
+        let args = vec![BINARY, "-n", "20", "-r", "100", "--open-model", URI];
+        let result = cmd.try_get_matches_from_mut(args);
+        assert!(result.as_ref().is_ok());
+    }
+
+    #[test]
+    fn test_parse_precision_params() {
+        let mut cmd = Arg::command();
+
+        // default precision
+        let args = vec![BINARY, "-n", "20", URI];
+        let result = cmd.try_get_matches_from_mut(args);
+        assert_eq!(result.unwrap().get_one::<u8>("precision"), Some(&3));
+
+        // custom precision
+        let args = vec![BINARY, "-n", "20", "--precision", "5", URI];
+        let result = cmd.try_get_matches_from_mut(args);
+        assert_eq!(result.unwrap().get_one::<u8>("precision"), Some(&5));
+
+        // out of range
+        for precision in ["0", "6"] {
+            let args = vec![BINARY, "-n", "20", "--precision", precision, URI];
+            let result = cmd.try_get_matches_from_mut(args);
+            assert!(result.is_err());
+        }
+    }
+
     #[test]
     fn test_parse_output_format() {
         let mut cmd = Arg::command();
@@ -878,7 +2180,14 @@ mod tests {
         assert!(parse_percentiles("0.99").is_ok());
         assert!(parse_percentiles("0.01").is_ok());
 
+        // tail percentiles
+        assert_eq!(parse_percentiles("0.9999").unwrap(), 0.9999);
+        assert_eq!(parse_percentiles("p99.9").unwrap(), 0.999);
+        assert_eq!(parse_percentiles("99.99%").unwrap(), 0.9999);
+
         // Invalid percentiles
+        assert!(parse_percentiles("p100").is_err());
+        assert!(parse_percentiles("0%").is_err());
         assert!(parse_percentiles("0").is_err());
         assert!(parse_percentiles("1").is_err());
         assert!(parse_percentiles("-0.1").is_err());
@@ -904,6 +2213,16 @@ mod tests {
         assert_eq!(Method::Delete.to_reqwest_method(), reqwest::Method::DELETE);
         assert_eq!(Method::Head.to_reqwest_method(), reqwest::Method::HEAD);
         assert_eq!(Method::Patch.to_reqwest_method(), reqwest::Method::PATCH);
+        assert_eq!(
+            Method::Options.to_reqwest_method(),
+            reqwest::Method::OPTIONS
+        );
+        assert_eq!(Method::Trace.to_reqwest_method(), reqwest::Method::TRACE);
+        let propfind = reqwest::Method::from_bytes(b"PROPFIND").unwrap();
+        assert_eq!(
+            Method::Other(propfind.clone()).to_reqwest_method(),
+            propfind
+        );
     }
 
     #[test]
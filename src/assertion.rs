@@ -0,0 +1,400 @@
+//! mod assertion checks every response against the rules given by `--assert`,
+//! the supported rules are:
+//!
+//! - `status=200`, `status!=500`, `status=2xx`
+//! - `header:content-type~json`, `header:x-id=1`, `header:x-id exists`
+//! - `body~"ok"`, `body!~error`
+//! - `json:$.data.id exists`, `json:$.code=0`, `json:$.msg~ok`
+//! - `latency<200ms`, `latency<=1s`
+//!
+//! `=` and `!=` compare the whole value, `~` and `!~` look for a substring,
+//! `exists` and `!exists` check whether the header or json value is present.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::{Response, header::HeaderName};
+use serde_json::Value;
+
+use crate::arg::parse_latency;
+use crate::json_path::{JsonPath, value_to_string};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Matcher {
+    Exists,
+    NotExists,
+    Equal(String),
+    NotEqual(String),
+    Contains(String),
+    NotContains(String),
+}
+
+impl Matcher {
+    fn parse(input: &str) -> Option<Self> {
+        let input = input.trim_start();
+        let matcher = match input.trim_end() {
+            "exists" => Matcher::Exists,
+            "!exists" => Matcher::NotExists,
+            _ => {
+                if let Some(value) = input.strip_prefix("!=") {
+                    Matcher::NotEqual(unquote(value))
+                } else if let Some(value) = input.strip_prefix("!~") {
+                    Matcher::NotContains(unquote(value))
+                } else if let Some(value) = input.strip_prefix('=') {
+                    Matcher::Equal(unquote(value))
+                } else if let Some(value) = input.strip_prefix('~') {
+                    Matcher::Contains(unquote(value))
+                } else {
+                    return None;
+                }
+            },
+        };
+        Some(matcher)
+    }
+
+    fn matches(&self, actual: Option<&str>) -> bool {
+        match (self, actual) {
+            (Matcher::Exists, actual) => actual.is_some(),
+            (Matcher::NotExists, actual) => actual.is_none(),
+            (Matcher::Equal(expected), Some(actual)) => actual == expected,
+            (Matcher::NotEqual(expected), actual) => {
+                actual != Some(expected.as_str())
+            },
+            (Matcher::Contains(expected), Some(actual)) => {
+                actual.contains(expected.as_str())
+            },
+            (Matcher::NotContains(expected), actual) => {
+                !actual.is_some_and(|actual| actual.contains(expected.as_str()))
+            },
+            (_, None) => false,
+        }
+    }
+}
+
+/// remove the whitespaces and the quotes around the value
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// split `name<op>value` into name and `<op>value`, the brackets and quotes
+/// of a json path, such as `$['a b']`, belong to the name
+fn split_operand(input: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut depth = 0usize;
+    for (pos, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            (None, '=' | '~' | '!') if depth == 0 => {
+                return input.split_at(pos);
+            },
+            (None, _) if depth == 0 && c.is_whitespace() => {
+                return input.split_at(pos);
+            },
+            _ => {},
+        }
+    }
+    (input, "")
+}
+
+/// `x` in the pattern matches any digit, e.g. `2xx` matches `204`
+fn status_matches(pattern: &str, status: &str) -> bool {
+    pattern.len() == status.len()
+        && pattern
+            .chars()
+            .zip(status.chars())
+            .all(|(expected, actual)| {
+                expected.eq_ignore_ascii_case(&'x') || expected == actual
+            })
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Status { pattern: String, negate: bool },
+    Header(HeaderName, Matcher),
+    Body(Matcher),
+    Json(JsonPath, Matcher),
+    Latency { max: Duration, inclusive: bool },
+}
+
+/// [Assertion] is a rule which every response should satisfy
+#[derive(Debug, Clone)]
+pub(crate) struct Assertion {
+    expr: String,
+    rule: Rule,
+}
+
+impl Assertion {
+    /// whether the response body is required to check the assertion
+    pub(crate) fn needs_body(&self) -> bool {
+        matches!(self.rule, Rule::Body(_) | Rule::Json(..))
+    }
+
+    /// check whether the response satisfies the assertion
+    pub(crate) fn check(&self, subject: &Subject) -> bool {
+        match &self.rule {
+            Rule::Status { pattern, negate } => {
+                let status = subject.response.status().as_u16().to_string();
+                status_matches(pattern, &status) != *negate
+            },
+            Rule::Header(name, matcher) => {
+                let value = subject
+                    .response
+                    .headers()
+                    .get(name)
+                    .map(|value| String::from_utf8_lossy(value.as_bytes()));
+                matcher.matches(value.as_deref())
+            },
+            Rule::Body(matcher) => matcher.matches(Some(subject.text())),
+            Rule::Json(path, matcher) => {
+                let value = subject
+                    .json()
+                    .and_then(|json| path.select(json))
+                    .map(value_to_string);
+                matcher.matches(value.as_deref())
+            },
+            Rule::Latency { max, inclusive } => {
+                subject.latency < *max
+                    || (*inclusive && subject.latency == *max)
+            },
+        }
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+/// parse the rule given by `--assert`
+pub(crate) fn parse_assertion(arg: &str) -> anyhow::Result<Assertion> {
+    let expr = arg.trim();
+    let invalid = || {
+        anyhow::anyhow!(
+            "invalid assertion `{expr}`, examples: status=200, \
+            header:content-type~json, body~ok, json:$.data.id exists, \
+            latency<200ms"
+        )
+    };
+
+    let rule = if let Some(rest) = expr.strip_prefix("latency") {
+        let rest = rest.trim_start();
+        let (value, inclusive) = match rest.strip_prefix("<=") {
+            Some(value) => (value, true),
+            None => (rest.strip_prefix('<').ok_or_else(invalid)?, false),
+        };
+        Rule::Latency {
+            max: parse_latency(value)?,
+            inclusive,
+        }
+    } else if let Some(rest) = expr.strip_prefix("status") {
+        let (pattern, negate) = match Matcher::parse(rest) {
+            Some(Matcher::Equal(pattern)) => (pattern, false),
+            Some(Matcher::NotEqual(pattern)) => (pattern, true),
+            _ => return Err(invalid()),
+        };
+        if pattern.len() != 3
+            || !pattern
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.eq_ignore_ascii_case(&'x'))
+        {
+            anyhow::bail!(
+                "invalid status `{pattern}` in assertion `{expr}`, \
+                examples: 200, 2xx"
+            );
+        }
+        Rule::Status { pattern, negate }
+    } else if let Some(rest) = expr.strip_prefix("body") {
+        Rule::Body(Matcher::parse(rest).ok_or_else(invalid)?)
+    } else if let Some(rest) = expr.strip_prefix("header:") {
+        let (name, rest) = split_operand(rest);
+        Rule::Header(
+            HeaderName::from_str(name.trim())?,
+            Matcher::parse(rest).ok_or_else(invalid)?,
+        )
+    } else if let Some(rest) = expr.strip_prefix("json:") {
+        let (path, rest) = split_operand(rest);
+        Rule::Json(
+            JsonPath::parse(path)?,
+            Matcher::parse(rest).ok_or_else(invalid)?,
+        )
+    } else {
+        return Err(invalid());
+    };
+
+    Ok(Assertion {
+        expr: expr.to_string(),
+        rule,
+    })
+}
+
+/// [Subject] is a response to be checked, the body is decoded lazily and
+/// only once no matter how many assertions use it
+pub(crate) struct Subject<'a> {
+    response: &'a Response,
+    body: &'a [u8],
+    latency: Duration,
+    text: OnceCell<Cow<'a, str>>,
+    json: OnceCell<Option<Value>>,
+}
+
+impl<'a> Subject<'a> {
+    pub(crate) fn new(
+        response: &'a Response,
+        body: &'a [u8],
+        latency: Duration,
+    ) -> Self {
+        Self {
+            response,
+            body,
+            latency,
+            text: OnceCell::new(),
+            json: OnceCell::new(),
+        }
+    }
+
+    fn text(&self) -> &str {
+        self.text.get_or_init(|| String::from_utf8_lossy(self.body))
+    }
+
+    fn json(&self) -> Option<&Value> {
+        self.json
+            .get_or_init(|| serde_json::from_slice(self.body).ok())
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Response {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Response::from(builder.body("").unwrap())
+    }
+
+    fn check(expr: &str, response: &Response, body: &str, ms: u64) -> bool {
+        let subject =
+            Subject::new(response, body.as_bytes(), Duration::from_millis(ms));
+        parse_assertion(expr).unwrap().check(&subject)
+    }
+
+    #[test]
+    fn test_parse_assertion() {
+        for expr in [
+            "status=200",
+            "status != 5xx",
+            "header:content-type~json",
+            "header:x-id exists",
+            "header:x-id !exists",
+            "body~\"ok\"",
+            "body!~error",
+            "json:$.data.id exists",
+            "json:$.code=0",
+            "json:$['a b']=1",
+            "json:$[\"x=y\"] exists",
+            "latency<200ms",
+            "latency <= 1s",
+        ] {
+            let assertion = parse_assertion(expr);
+            assert!(assertion.is_ok(), "{expr}");
+            assert_eq!(assertion.unwrap().to_string(), expr);
+        }
+
+        for expr in [
+            "",
+            "status~200",
+            "status=20",
+            "status=abc",
+            "header:content type=json",
+            "body",
+            "json:data.id exists",
+            "latency>200ms",
+            "latency<200x",
+            "unknown=1",
+        ] {
+            assert!(parse_assertion(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_assertion_needs_body() {
+        assert!(parse_assertion("body~ok").unwrap().needs_body());
+        assert!(parse_assertion("json:$.id exists").unwrap().needs_body());
+        assert!(!parse_assertion("status=200").unwrap().needs_body());
+        assert!(!parse_assertion("latency<1s").unwrap().needs_body());
+    }
+
+    #[test]
+    fn test_assertion_check_status() {
+        let rsp = response(204, &[]);
+        assert!(check("status=204", &rsp, "", 0));
+        assert!(check("status=2xx", &rsp, "", 0));
+        assert!(check("status!=5xx", &rsp, "", 0));
+        assert!(!check("status=200", &rsp, "", 0));
+        assert!(!check("status!=2XX", &rsp, "", 0));
+    }
+
+    #[test]
+    fn test_assertion_check_header() {
+        let rsp = response(200, &[("content-type", "application/json")]);
+        assert!(check("header:content-type~json", &rsp, "", 0));
+        assert!(check("header:Content-Type=application/json", &rsp, "", 0));
+        assert!(check("header:content-type exists", &rsp, "", 0));
+        assert!(check("header:x-id !exists", &rsp, "", 0));
+        assert!(check("header:x-id!=1", &rsp, "", 0));
+        assert!(check("header:x-id!~1", &rsp, "", 0));
+        assert!(!check("header:content-type~html", &rsp, "", 0));
+        assert!(!check("header:x-id exists", &rsp, "", 0));
+        assert!(!check("header:x-id=1", &rsp, "", 0));
+    }
+
+    #[test]
+    fn test_assertion_check_body() {
+        let rsp = response(200, &[]);
+        let body = r#"{"code": 0, "data": {"id": "a1", "tags": ["x"]}}"#;
+        assert!(check("body~\"code\"", &rsp, body, 0));
+        assert!(check("body!~error", &rsp, body, 0));
+        assert!(check("json:$.code=0", &rsp, body, 0));
+        assert!(check("json:$.data.id exists", &rsp, body, 0));
+        assert!(check("json:$.data.id='a1'", &rsp, body, 0));
+        assert!(check("json:$.data.tags[0]=x", &rsp, body, 0));
+        assert!(check("json:$.data.name !exists", &rsp, body, 0));
+        let keys = r#"{"a b": 1, "x=y": "a!b"}"#;
+        assert!(check("json:$['a b'] = 1", &rsp, keys, 0));
+        assert!(check("json:$['x=y']~a!b", &rsp, keys, 0));
+        assert!(!check("json:$['a b']!=1", &rsp, keys, 0));
+        assert!(!check("json:$.data.name exists", &rsp, body, 0));
+        assert!(!check("json:$.code!=0", &rsp, body, 0));
+        assert!(!check("json:$ exists", &rsp, "not json", 0));
+    }
+
+    #[test]
+    fn test_assertion_check_latency() {
+        let rsp = response(200, &[]);
+        assert!(check("latency<200ms", &rsp, "", 199));
+        assert!(!check("latency<200ms", &rsp, "", 200));
+        assert!(check("latency<=200ms", &rsp, "", 200));
+        assert!(!check("latency<=200ms", &rsp, "", 201));
+    }
+}
@@ -0,0 +1,193 @@
+//! mod auth sets the `Authorization` header of the requests, by
+//! `--basic user:pass`, `--bearer <TOKEN>`, or an access token of the OAuth2
+//! client credentials flow given by `--oauth2-token-url`.
+//!
+//! The access token is fetched before the test starts, and refreshed in the
+//! background before it expires, the requests to the token endpoint are sent
+//! by a separate client, so they are not counted in the statistics.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::Client;
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+
+use crate::Arg;
+
+/// the interval to retry when the token fails to be refreshed
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// the `Authorization` header given by `--basic` or `--bearer`
+pub(crate) fn static_header(arg: &Arg) -> anyhow::Result<Option<HeaderValue>> {
+    let value = match (&arg.basic, &arg.bearer) {
+        (Some(credentials), _) => {
+            format!("Basic {}", STANDARD.encode(credentials))
+        },
+        (None, Some(token)) => format!("Bearer {token}"),
+        (None, None) => return Ok(None),
+    };
+    let mut value = HeaderValue::from_str(&value)?;
+    value.set_sensitive(true);
+    Ok(Some(value))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// [OAuth2] keeps the access token of the client credentials flow
+#[derive(Debug)]
+pub(crate) struct OAuth2 {
+    client: Client,
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    header: RwLock<Option<HeaderValue>>,
+}
+
+impl OAuth2 {
+    /// None without `--oauth2-token-url`
+    pub(crate) fn new(arg: &Arg) -> anyhow::Result<Option<Self>> {
+        let Some(token_url) = &arg.oauth2_token_url else {
+            return Ok(None);
+        };
+        let client = Client::builder()
+            .timeout(arg.timeout)
+            .danger_accept_invalid_certs(arg.insecure)
+            .build()?;
+        Ok(Some(Self {
+            client,
+            token_url: token_url.clone(),
+            client_id: arg.oauth2_client_id.clone().unwrap_or_default(),
+            client_secret: arg.oauth2_client_secret.clone().unwrap_or_default(),
+            scope: arg.oauth2_scope.clone(),
+            header: RwLock::new(None),
+        }))
+    }
+
+    /// the `Authorization` header with the current access token
+    pub(crate) fn header(&self) -> Option<HeaderValue> {
+        self.header.read().unwrap().clone()
+    }
+
+    /// fetch a new access token, returns how long it's valid for
+    async fn fetch(&self) -> anyhow::Result<Option<Duration>> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+        let response = self
+            .client
+            .post(&self.token_url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "failed to fetch the oauth2 token from {}: {e}",
+                    self.token_url
+                )
+            })?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!(
+                "failed to fetch the oauth2 token from {}: {status} {}",
+                self.token_url,
+                response.text().await.unwrap_or_default()
+            );
+        }
+        let token: TokenResponse = response.json().await.map_err(|e| {
+            anyhow::anyhow!("invalid oauth2 token response: {e}")
+        })?;
+
+        // the token type is case insensitive, e.g. `bearer`
+        let token_type = match token.token_type.as_deref() {
+            Some(token_type) if !token_type.eq_ignore_ascii_case("bearer") => {
+                token_type
+            },
+            _ => "Bearer",
+        };
+        let mut header = HeaderValue::from_str(&format!(
+            "{token_type} {}",
+            token.access_token
+        ))?;
+        header.set_sensitive(true);
+        *self.header.write().unwrap() = Some(header);
+        Ok(token.expires_in.map(Duration::from_secs))
+    }
+
+    /// fetch the first access token, and keep refreshing it in the background
+    /// before it expires
+    pub(crate) async fn start(self: Arc<Self>) -> anyhow::Result<()> {
+        let mut lifetime = self.fetch().await?;
+        tokio::spawn(async move {
+            while let Some(valid_for) = lifetime {
+                tokio::time::sleep(refresh_after(valid_for)).await;
+                loop {
+                    match self.fetch().await {
+                        Ok(next) => {
+                            lifetime = next;
+                            break;
+                        },
+                        Err(e) => {
+                            log::warn!("{e}, retry in {RETRY_INTERVAL:?}");
+                            tokio::time::sleep(RETRY_INTERVAL).await;
+                        },
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// the token is refreshed once 90% of its lifetime has passed
+fn refresh_after(valid_for: Duration) -> Duration {
+    valid_for.mul_f64(0.9).max(RETRY_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_static_header() {
+        let arg =
+            Arg::parse_from(["rsb", "-n", "1", "--basic", "u:p", "http://a"]);
+        let header = static_header(&arg).unwrap().unwrap();
+        assert_eq!(header, "Basic dTpw");
+        assert!(header.is_sensitive());
+
+        let arg =
+            Arg::parse_from(["rsb", "-n", "1", "--bearer", "t1", "http://a"]);
+        assert_eq!(static_header(&arg).unwrap().unwrap(), "Bearer t1");
+
+        let arg = Arg::parse_from(["rsb", "-n", "1", "http://a"]);
+        assert!(static_header(&arg).unwrap().is_none());
+
+        let arg =
+            Arg::parse_from(["rsb", "-n", "1", "--bearer", "a\nb", "http://a"]);
+        assert!(static_header(&arg).is_err());
+    }
+
+    #[test]
+    fn test_refresh_after() {
+        assert_eq!(
+            refresh_after(Duration::from_secs(300)),
+            Duration::from_secs(270)
+        );
+        assert_eq!(refresh_after(Duration::ZERO), RETRY_INTERVAL);
+    }
+}
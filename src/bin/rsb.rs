@@ -6,6 +6,9 @@ use clap_complete::generate;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use rsb::{Arg, Task, arg::OutputFormat};
 
+/// the exit code when any criterion given by `--threshold` is not met
+const THRESHOLDS_FAILED_EXIT_CODE: i32 = 99;
+
 #[cfg(not(tarpaulin_include))]
 fn create_count_progress_bar(arg: &Arg) -> ProgressBar {
     let pb = ProgressBar::new(arg.requests.unwrap());
@@ -34,7 +37,7 @@ fn create_count_progress_bar(arg: &Arg) -> ProgressBar {
 
 #[cfg(not(tarpaulin_include))]
 fn create_duration_progress_bar(arg: &Arg) -> ProgressBar {
-    let pb = ProgressBar::new(arg.duration.unwrap().as_secs());
+    let pb = ProgressBar::new(arg.test_duration().unwrap().as_secs());
     pb.set_style(
         ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{bar:.cyan/blue}] {pos}s/{len}s ({percent}%) {msg}",
@@ -63,23 +66,52 @@ fn create_progress_bar(arg: &Arg) -> ProgressBar {
     }
 }
 
+#[cfg(not(tarpaulin_include))]
+fn target(arg: &Arg) -> String {
+    match (&arg.scenario, &arg.har) {
+        (Some(scenario), _) => format!("Scenario {scenario:?}"),
+        (None, Some(har)) => format!("HAR {har:?}"),
+        (None, None) => match &arg.openapi {
+            Some(openapi) => format!("OpenAPI {openapi:?}"),
+            None if arg.is_websocket() => {
+                format!("WebSocket {:?}", arg.url.clone().unwrap())
+            },
+            None => match arg.grpc_method() {
+                Some(method) => {
+                    format!("gRPC {method} {:?}", arg.url.clone().unwrap())
+                },
+                None => {
+                    format!("{} {:?}", arg.method, arg.url.clone().unwrap())
+                },
+            },
+        },
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 fn print_tip(arg: &Arg) -> anyhow::Result<()> {
     if let Some(requests) = arg.requests {
         writeln!(
             &mut io::stdout(),
-            "{:?} {:?} with {} requests using {} connections",
-            arg.method,
-            arg.url.clone().unwrap(),
+            "{} with {} requests using {} connections",
+            target(arg),
             requests,
             arg.connections
         )?;
+    } else if !arg.stages.is_empty() {
+        writeln!(
+            &mut io::stdout(),
+            "{} with {} stages for {:?} using {} connections",
+            target(arg),
+            arg.stages.len(),
+            arg.test_duration().unwrap(),
+            arg.connections
+        )?;
     } else if let Some(duration) = arg.duration {
         writeln!(
             &mut io::stdout(),
-            "{:?} {:?} with for {:?} using {} connections",
-            arg.method,
-            arg.url.clone().unwrap(),
+            "{} with for {:?} using {} connections",
+            target(arg),
             duration,
             arg.connections
         )?;
@@ -90,7 +122,7 @@ fn print_tip(arg: &Arg) -> anyhow::Result<()> {
 #[cfg(not(tarpaulin_include))]
 fn main() -> anyhow::Result<()> {
     env_logger::init();
-    let arg = Arg::parse();
+    let mut arg = Arg::parse();
 
     if let Some(shell) = arg.completions {
         let mut cmd = Arg::command();
@@ -99,19 +131,27 @@ fn main() -> anyhow::Result<()> {
         std::process::exit(0);
     }
 
+    if let Some(operations) = arg.openapi_operations()? {
+        writeln!(&mut io::stdout(), "{operations}")?;
+        return Ok(());
+    }
+
     rlimit::increase_nofile_limit(u64::MAX).unwrap();
 
+    arg.apply_from_curl()?;
+
     print_tip(&arg)?;
     let pb = create_progress_bar(&arg);
     let output_format = arg.output_format;
     let task = Arc::new(Task::new(arg, Some(pb))?).run()?;
+    let output = task.clone().json_output()?;
     let result = match output_format {
         OutputFormat::Text => task.text_output()?,
-        OutputFormat::Json => {
-            let output = task.json_output()?;
-            serde_json::to_string_pretty(&output)?
-        },
+        OutputFormat::Json => serde_json::to_string_pretty(&output)?,
     };
     writeln!(&mut io::stdout(), "{result}")?;
+    if !output.thresholds_passed() {
+        std::process::exit(THRESHOLDS_FAILED_EXIT_CODE);
+    }
     Ok(())
 }
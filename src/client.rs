@@ -1,35 +1,62 @@
+use std::fmt;
 use std::fs as sfs;
 
+use native_tls::{Identity, TlsConnector};
 use reqwest::{
-    Client,
-    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Response, Version,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
     redirect::Policy,
 };
 
 use crate::Arg;
+use crate::auth;
+use crate::template::is_template;
 
-pub(crate) fn build_client(arg: &Arg) -> anyhow::Result<Client> {
-    let mut builder = Client::builder();
+/// [HandshakeError] is the failure to establish the connection of HTTP/3, it's
+/// counted apart from the other errors
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HandshakeError(pub(crate) String);
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
-    // build headers
+impl std::error::Error for HandshakeError {}
+
+/// the headers sent by every request, which are set as the default headers
+/// of the client
+pub(crate) fn default_headers(arg: &Arg) -> anyhow::Result<HeaderMap> {
     let mut headers = HeaderMap::new();
     for header in &arg.headers {
         let parts = header.trim().split_once(':');
-        if let Some(parts) = parts {
+        // templated headers are rendered for each request
+        if let Some(parts) = parts
+            && !is_template(parts.1)
+        {
             headers.insert(
-                HeaderName::from_bytes(parts.0.as_bytes())?,
-                HeaderValue::from_str(parts.1)?,
+                HeaderName::from_bytes(parts.0.trim().as_bytes())?,
+                HeaderValue::from_str(parts.1.trim())?,
             );
         }
     }
 
+    // `--basic` and `--bearer` take precedence over `-H`
+    if let Some(authorization) = auth::static_header(arg)? {
+        headers.insert(AUTHORIZATION, authorization);
+    }
+
     // disable http keep alive
     if arg.disable_keep_alive {
         headers.insert("Connection", HeaderValue::from_static("Close"));
     }
+    Ok(headers)
+}
 
-    builder = builder
-        .default_headers(headers)
+pub(crate) fn build_client(arg: &Arg) -> anyhow::Result<Client> {
+    let mut builder = Client::builder()
+        .default_headers(default_headers(arg)?)
         .timeout(arg.timeout)
         .connect_timeout(arg.timeout)
         .danger_accept_invalid_certs(arg.insecure)
@@ -48,19 +75,102 @@ pub(crate) fn build_client(arg: &Arg) -> anyhow::Result<Client> {
     // forbidden redirect
     builder = builder.redirect(Policy::none());
 
+    // `--http2` negotiates h2 by ALPN, the TLS handshake only offers h2, while
+    // `--http2-prior-knowledge` speaks HTTP/2 without negotiation
+    if arg.http1_only {
+        builder = builder.http1_only();
+    } else if arg.http2 {
+        if arg.url.as_deref().is_some_and(is_cleartext) {
+            anyhow::bail!(
+                "--http2 negotiates HTTP/2 by TLS ALPN, use \
+                 --http2-prior-knowledge for http urls"
+            );
+        }
+        builder =
+            builder.use_preconfigured_tls(build_tls_connector(arg, &["h2"])?);
+    } else if arg.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+
     match builder.build() {
         Ok(client) => Ok(client),
         Err(e) => Err(Box::new(e).into()),
     }
 }
 
+fn is_cleartext(url: &str) -> bool {
+    url.get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+}
+
+/// fail the responses which weren't received over HTTP/2 with `--http2`, the
+/// server may ignore the ALPN of the TLS handshake, and the urls of scenarios
+/// and the other sources may be cleartext
+pub(crate) fn check_version(
+    arg: &Arg,
+    response: Response,
+) -> anyhow::Result<Response> {
+    if arg.http2 && response.version() != Version::HTTP_2 {
+        anyhow::bail!(
+            "HTTP/2 wasn't negotiated with {}, the response is {:?}",
+            response.url(),
+            response.version()
+        );
+    }
+    Ok(response)
+}
+
+/// the TLS connector of the transports which aren't built by reqwest, which
+/// offers the given protocols by ALPN
+pub(crate) fn build_tls_connector(
+    arg: &Arg,
+    alpn: &[&str],
+) -> anyhow::Result<TlsConnector> {
+    let mut builder = TlsConnector::builder();
+    builder
+        .danger_accept_invalid_certs(arg.insecure)
+        .danger_accept_invalid_hostnames(arg.insecure);
+    if !alpn.is_empty() {
+        builder.request_alpns(alpn);
+    }
+    if let Some(cert) = &arg.cert
+        && let Some(key) = &arg.key
+    {
+        let cert = sfs::read(cert)?;
+        let key = sfs::read(key)?;
+        builder.identity(Identity::from_pkcs8(&cert, &key)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// number of connections shared by the workers, all requests over one of them
+/// are multiplexed with HTTP/2, so one is opened for every
+/// `--http2-max-streams` workers
+pub(crate) fn shared_connections(arg: &Arg) -> usize {
+    arg.http2_max_streams
+        .map_or(1, |streams| arg.connections.div_ceil(streams).max(1))
+        as usize
+}
+
+/// build the clients shared by the workers, each of them keeps one connection
+/// with HTTP/2, see [shared_connections]
+pub(crate) fn build_clients(arg: &Arg) -> anyhow::Result<Vec<Client>> {
+    (0..shared_connections(arg))
+        .map(|_| build_client(arg))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
     use std::ffi::OsStr;
-    use std::path::Path;
+    use std::net::SocketAddr;
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc;
 
-    use clap::{CommandFactory, FromArgMatches};
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
 
     use super::*;
     const URI: &str = "https://localhost/test";
@@ -115,11 +225,151 @@ mod tests {
     }
 
     #[test]
-    fn test_build_client_with_client_cert() {
+    fn test_build_clients() {
+        for (args, count) in [
+            (vec![], 1),
+            (vec!["--http2"], 1),
+            (vec!["--http2", "--http2-max-streams=4"], 3),
+            (
+                vec!["--http2-prior-knowledge", "--http2-max-streams=100"],
+                1,
+            ),
+        ] {
+            let arg = Arg::try_parse_from(
+                [BINARY, "-n", "20", "-c", "10"]
+                    .into_iter()
+                    .chain(args.clone())
+                    .chain([URI]),
+            )
+            .unwrap();
+            assert_eq!(build_clients(&arg).unwrap().len(), count, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_build_client_http2_cleartext() {
+        let build = |args: &[&str]| {
+            let arg =
+                Arg::try_parse_from([BINARY, "-n", "20"].iter().chain(args))
+                    .unwrap();
+            build_client(&arg)
+        };
+        assert!(build(&["--http2", "http://localhost/test"]).is_err());
+        assert!(build(&["--http2", "HTTP://localhost/test"]).is_err());
+        assert!(build(&["--http2", URI]).is_ok());
+        assert!(
+            build(&["--http2-prior-knowledge", "http://localhost/test"])
+                .is_ok()
+        );
+    }
+
+    fn tests_dir() -> PathBuf {
         let root = env::var("CARGO_MANIFEST_DIR").unwrap();
-        let tests = Path::new(OsStr::new(root.as_str()))
+        Path::new(OsStr::new(root.as_str()))
             .join("resources")
-            .join("tests");
+            .join("tests")
+    }
+
+    /// start a server in the background, which speaks h2c, or HTTP/1.1 over
+    /// TLS without ALPN
+    fn start_server(tls: bool) -> SocketAddr {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let cert = sfs::read(tests_dir().join("client.pem")).unwrap();
+            let key = sfs::read(tests_dir().join("client-key.pem")).unwrap();
+            let identity = Identity::from_pkcs8(&cert, &key).unwrap();
+            let acceptor = tokio_native_tls::TlsAcceptor::from(
+                native_tls::TlsAcceptor::new(identity).unwrap(),
+            );
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                while let Ok((stream, _)) = listener.accept().await {
+                    match tls {
+                        true => {
+                            let acceptor = acceptor.clone();
+                            tokio::spawn(async move {
+                                if let Ok(stream) =
+                                    acceptor.accept(stream).await
+                                {
+                                    serve_http1(stream).await;
+                                }
+                            });
+                        },
+                        false => {
+                            tokio::spawn(serve_h2c(stream));
+                        },
+                    }
+                }
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    async fn serve_http1<S: AsyncReadExt + AsyncWriteExt + Unpin>(
+        mut stream: S,
+    ) {
+        let mut request = vec![0; 4096];
+        if stream.read(&mut request).await.is_ok() {
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\
+                      connection: close\r\n\r\n",
+                )
+                .await;
+        }
+    }
+
+    async fn serve_h2c(stream: TcpStream) {
+        let Ok(mut connection) = h2::server::handshake(stream).await else {
+            return;
+        };
+        while let Some(Ok((_, mut respond))) = connection.accept().await {
+            let response = http::Response::builder().body(()).unwrap();
+            let _ = respond.send_response(response, true);
+        }
+    }
+
+    /// send one request, returns the version of the response
+    fn send(args: &[&str], url: &str) -> anyhow::Result<Version> {
+        let arg = Arg::try_parse_from(
+            [BINARY, "-n", "1", "-k"].iter().chain(args).chain([&url]),
+        )?;
+        let client = build_client(&arg)?;
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(async {
+            let response = client.get(url).send().await?;
+            Ok(check_version(&arg, response)?.version())
+        })
+    }
+
+    #[test]
+    fn test_http2_modes() {
+        let h2c = format!("http://{}/", start_server(false));
+        assert_eq!(
+            send(&["--http2-prior-knowledge"], &h2c).unwrap(),
+            Version::HTTP_2
+        );
+        assert!(send(&[], &h2c).is_err());
+        assert!(send(&["--http2"], &h2c).is_err());
+
+        // the server doesn't select h2 by ALPN
+        let tls = format!("https://localhost:{}/", start_server(true).port());
+        assert_eq!(send(&[], &tls).unwrap(), Version::HTTP_11);
+        let err = send(&["--http2"], &tls).unwrap_err();
+        assert!(err.to_string().contains("wasn't negotiated"), "{err}");
+        assert!(send(&["--http2-prior-knowledge"], &tls).is_err());
+    }
+
+    #[test]
+    fn test_build_client_with_client_cert() {
+        let tests = tests_dir();
         let binding = tests.join("client.pem");
         let cert = binding.to_str().unwrap();
 
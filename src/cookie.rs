@@ -0,0 +1,327 @@
+//! mod cookie keeps the cookies of one virtual user, enabled by
+//! `--cookie-jar`, so that the `Set-Cookie` of one response is sent by the
+//! later requests of the same connection, like a browser session.
+//!
+//! The jar can be seeded from a Netscape cookie file given by
+//! `--cookie-file`, which is written by `curl -c` and browser extensions,
+//! each line has 7 fields separated by tabs, the domain, whether the
+//! subdomains are included, the path, whether it's only sent over https, the
+//! unix timestamp at which it expires, or 0 for a session cookie, the name and
+//! the value, e.g. `.example.com TRUE / FALSE 0 session a1b2`.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::Url;
+use reqwest::header::{HeaderMap, SET_COOKIE};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// only sent to the host which set it, no `Domain` is given
+    host_only: bool,
+    path: String,
+    secure: bool,
+    /// None for session cookies
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// parse the `Set-Cookie` header received from the url
+    fn parse(header: &str, url: &Url) -> Option<Self> {
+        let host = url.host_str()?.to_lowercase();
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) =
+                attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+                    // a cookie can't be set for other sites
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                },
+                "path" if value.starts_with('/') => {
+                    cookie.path = value.to_string()
+                },
+                "secure" => cookie.secure = true,
+                "expires" => {
+                    if let Ok(expires) = httpdate::parse_http_date(value) {
+                        cookie.expires = Some(expires);
+                    }
+                },
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                _ => {},
+            }
+        }
+        // Max-Age takes precedence over Expires
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(match u64::try_from(max_age) {
+                Ok(secs) if secs > 0 => {
+                    SystemTime::now() + Duration::from_secs(secs)
+                },
+                _ => UNIX_EPOCH,
+            });
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// whether the cookie is sent to the url
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_lowercase();
+        let domain_ok = match self.host_only {
+            true => host == self.domain,
+            false => domain_matches(&host, &self.domain),
+        };
+        let path = url.path();
+        let path_ok = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/')
+                    || path[self.path.len()..].starts_with('/')));
+        domain_ok && path_ok && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// whether the host is the domain or its subdomain
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// the directory of the path of the url, which is the path of the cookies
+/// without `Path`
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    }
+}
+
+/// [CookieJar] is the cookies of one virtual user
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// load the cookies from the Netscape cookie file
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("failed to read cookie file {path:?}: {e}")
+        })?;
+        let mut cookies = vec![];
+        for (no, line) in content.lines().enumerate() {
+            // curl writes the cookies with HttpOnly like comments
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [
+                domain,
+                subdomains,
+                cookie_path,
+                secure,
+                expires,
+                name,
+                value,
+            ] = fields[..]
+            else {
+                anyhow::bail!(
+                    "invalid line {} of cookie file {path:?}, expected 7 \
+                    fields separated by tabs",
+                    no + 1
+                );
+            };
+            let expires: u64 = expires.parse().map_err(|_| {
+                anyhow::anyhow!(
+                    "invalid expires `{expires}` at line {} of cookie file \
+                    {path:?}",
+                    no + 1
+                )
+            })?;
+            cookies.push(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_lowercase(),
+                host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+                path: cookie_path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                // 0 is a session cookie
+                expires: (expires > 0)
+                    .then(|| UNIX_EPOCH + Duration::from_secs(expires)),
+            });
+        }
+        Ok(Self { cookies })
+    }
+
+    /// keep the cookies set by the response received from the url, the
+    /// expired ones are removed
+    pub(crate) fn store(&mut self, url: &Url, headers: &HeaderMap) {
+        let now = SystemTime::now();
+        for header in headers.get_all(SET_COOKIE) {
+            let Some(cookie) = header
+                .to_str()
+                .ok()
+                .and_then(|header| Cookie::parse(header, url))
+            else {
+                continue;
+            };
+            self.cookies.retain(|c| {
+                c.name != cookie.name
+                    || c.domain != cookie.domain
+                    || c.path != cookie.path
+            });
+            if !cookie.is_expired(now) {
+                self.cookies.push(cookie);
+            }
+        }
+    }
+
+    /// the value of the `Cookie` header sent to the url, the cookies with
+    /// longer paths are listed first
+    pub(crate) fn header(&self, url: &Url) -> Option<String> {
+        let now = SystemTime::now();
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(url))
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+    use crate::test_util::write_temp;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    fn set_cookies(cookies: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for cookie in cookies {
+            headers.append(SET_COOKIE, HeaderValue::from_str(cookie).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_cookie_jar_store() {
+        let mut jar = CookieJar::default();
+        jar.store(
+            &url("http://www.a.com/account/login"),
+            &set_cookies(&[
+                "sid=1; Path=/; HttpOnly",
+                "lang=en; Domain=.a.com; Path=/",
+                "tab=2",
+                "token=t; Secure; Path=/",
+                "other=1; Domain=b.com",
+                "invalid",
+            ]),
+        );
+        assert_eq!(
+            jar.header(&url("http://www.a.com/account/settings"))
+                .unwrap(),
+            "tab=2; sid=1; lang=en"
+        );
+        assert_eq!(jar.header(&url("http://a.com/")).unwrap(), "lang=en");
+        assert_eq!(
+            jar.header(&url("https://www.a.com/")).unwrap(),
+            "sid=1; lang=en; token=t"
+        );
+        assert_eq!(
+            jar.header(&url("http://www.a.com/accounts")).unwrap(),
+            "sid=1; lang=en"
+        );
+        assert_eq!(jar.header(&url("http://b.com/")), None);
+
+        // replaced, and removed once expired
+        jar.store(
+            &url("http://www.a.com/"),
+            &set_cookies(&[
+                "sid=2; Path=/",
+                "lang=; Domain=a.com; Max-Age=0",
+                "tab=3; Path=/account; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            ]),
+        );
+        assert_eq!(
+            jar.header(&url("http://www.a.com/account/")).unwrap(),
+            "sid=2"
+        );
+    }
+
+    #[test]
+    fn test_cookie_jar_load() {
+        let path = write_temp(
+            "rsb_test_cookies.txt",
+            "# Netscape HTTP Cookie File\n\n\
+            .a.com\tTRUE\t/\tFALSE\t0\tsid\t1\n\
+            #HttpOnly_www.a.com\tFALSE\t/api\tTRUE\t4102444800\ttoken\tt\n\
+            a.com\tFALSE\t/\tFALSE\t1\told\tx\n",
+        );
+        let jar = CookieJar::load(&path).unwrap();
+        assert_eq!(jar.cookies.len(), 3);
+        assert_eq!(jar.header(&url("http://x.a.com/")).unwrap(), "sid=1");
+        assert_eq!(
+            jar.header(&url("https://www.a.com/api/users")).unwrap(),
+            "token=t; sid=1"
+        );
+
+        fs::write(&path, "a.com\tFALSE\t/\n").unwrap();
+        assert!(CookieJar::load(&path).is_err());
+        fs::write(&path, "a.com\tFALSE\t/\tFALSE\tnever\tsid\t1\n").unwrap();
+        assert!(CookieJar::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        assert!(domain_matches("a.com", "a.com"));
+        assert!(domain_matches("www.a.com", "a.com"));
+        assert!(!domain_matches("wwwa.com", "a.com"));
+        assert!(!domain_matches("a.com", "www.a.com"));
+    }
+}
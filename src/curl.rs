@@ -0,0 +1,403 @@
+//! mod curl parses the command line given by `--from-curl`, such as the one
+//! copied by "Copy as cURL" of browsers, into the fields of [Arg], e.g.
+//!
+//! ```shell
+//! curl 'https://example.com/api' -X POST -H 'content-type: application/json' \
+//!   --data-raw '{"name":"rsb"}' -k
+//! ```
+//!
+//! The body of `-d`, `--data-raw` and `--data-binary` is decided by the
+//! `Content-Type` header, JSON for `application/json`, and the bytes as they
+//! are for the others, with `application/x-www-form-urlencoded` if no
+//! `Content-Type` is given like curl.
+
+use std::path::PathBuf;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::ValueEnum;
+use reqwest::Url;
+
+use crate::Arg;
+use crate::arg::Method;
+use crate::shell;
+
+/// the options which don't change the request, they are accepted and ignored
+const IGNORED_OPTIONS: [&str; 8] = [
+    "--compressed",
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-v",
+    "--verbose",
+    "-i",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Data {
+    Text(String),
+    File(PathBuf),
+}
+
+/// [Curl] is the request described by a curl command line
+#[derive(Debug, Default, PartialEq)]
+struct Curl {
+    url: Option<String>,
+    method: Option<Method>,
+    head: bool,
+    headers: Vec<(String, String)>,
+    data: Vec<Data>,
+    form: Vec<(String, String)>,
+    form_files: Vec<(String, PathBuf)>,
+    insecure: bool,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
+
+/// split `-Xvalue` and `--request=value` into the option and the value
+fn split_option(word: &str) -> (&str, Option<&str>) {
+    if let Some(long) = word.strip_prefix("--") {
+        return match long.split_once('=') {
+            Some((name, value)) => (&word[..name.len() + 2], Some(value)),
+            None => (word, None),
+        };
+    }
+    if word.len() > 2 && word.is_char_boundary(2) {
+        return (&word[..2], Some(&word[2..]));
+    }
+    (word, None)
+}
+
+fn parse(command: &str) -> anyhow::Result<Curl> {
+    let words = shell::split(command)?;
+    let mut words = words.iter().map(String::as_str);
+    if words.next() != Some("curl") {
+        anyhow::bail!(
+            "invalid curl command `{command}`, it must start with curl"
+        );
+    }
+
+    let mut curl = Curl::default();
+    while let Some(word) = words.next() {
+        if !word.starts_with('-') || word == "-" {
+            if curl.url.replace(word.to_string()).is_some() {
+                anyhow::bail!("more than one url in curl command `{command}`");
+            }
+            continue;
+        }
+        if IGNORED_OPTIONS.contains(&word) {
+            continue;
+        }
+        match word {
+            "-k" | "--insecure" => {
+                curl.insecure = true;
+                continue;
+            },
+            "-I" | "--head" => {
+                curl.head = true;
+                continue;
+            },
+            _ => {},
+        }
+
+        let (option, value) = split_option(word);
+        let mut next_value = || {
+            value.or_else(|| words.next()).ok_or(anyhow::anyhow!(
+                "missing value of curl option `{option}`"
+            ))
+        };
+        match option {
+            "--url" => curl.url = Some(next_value()?.to_string()),
+            "-X" | "--request" => {
+                let method = next_value()?;
+                curl.method =
+                    Some(Method::from_str(method, true).map_err(|_| {
+                        anyhow::anyhow!(
+                            "invalid method `{method}` in curl command"
+                        )
+                    })?);
+            },
+            "-H" | "--header" => {
+                let header = next_value()?;
+                let (name, value) =
+                    header.split_once(':').ok_or(anyhow::anyhow!(
+                        "invalid header `{header}` in curl command"
+                    ))?;
+                curl.headers
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            },
+            "-b" | "--cookie" => {
+                let cookie = next_value()?;
+                if !cookie.contains('=') {
+                    anyhow::bail!(
+                        "unsupported cookie file `{cookie}` in curl command, \
+                        only `name=value` is supported"
+                    );
+                }
+                curl.headers
+                    .push(("cookie".to_string(), cookie.to_string()));
+            },
+            "-u" | "--user" => {
+                let user = STANDARD.encode(next_value()?);
+                curl.headers.push((
+                    "authorization".to_string(),
+                    format!("Basic {user}"),
+                ));
+            },
+            "-d" | "--data" | "--data-ascii" | "--data-binary" => {
+                let data = next_value()?;
+                curl.data.push(match data.strip_prefix('@') {
+                    Some(path) => Data::File(PathBuf::from(path)),
+                    None => Data::Text(data.to_string()),
+                });
+            },
+            "--data-raw" => {
+                curl.data.push(Data::Text(next_value()?.to_string()))
+            },
+            "-F" | "--form" => {
+                let field = next_value()?;
+                let (name, value) =
+                    field.split_once('=').ok_or(anyhow::anyhow!(
+                        "invalid form field `{field}` in curl command"
+                    ))?;
+                match value.strip_prefix('@') {
+                    // the file may be followed by `;type=...`
+                    Some(path) => curl.form_files.push((
+                        name.to_string(),
+                        PathBuf::from(path.split(';').next().unwrap_or(path)),
+                    )),
+                    None => {
+                        curl.form.push((name.to_string(), value.to_string()))
+                    },
+                }
+            },
+            "-E" | "--cert" => curl.cert = Some(PathBuf::from(next_value()?)),
+            "--key" => curl.key = Some(PathBuf::from(next_value()?)),
+            _ => anyhow::bail!(
+                "unsupported curl option `{option}`, supports: -X, -H, -b, -u, \
+                -d, --data-raw, --data-binary, -F, -k, -I, --cert, --key and \
+                --url"
+            ),
+        }
+    }
+    Ok(curl)
+}
+
+impl Curl {
+    fn content_type(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// fill the fields of the arg with the request
+    fn apply(self, arg: &mut Arg) -> anyhow::Result<()> {
+        let url = self
+            .url
+            .as_deref()
+            .ok_or(anyhow::anyhow!("no url in the command of --from-curl"))?;
+        Url::parse(url)?;
+        arg.url = Some(url.to_string());
+
+        let has_body = !self.data.is_empty()
+            || !self.form.is_empty()
+            || !self.form_files.is_empty();
+        arg.method = match self.method.clone() {
+            Some(method) => method,
+            None if self.head => Method::Head,
+            None if has_body => Method::Post,
+            None => Method::Get,
+        };
+
+        let content_type =
+            self.content_type().unwrap_or_default().to_lowercase();
+        match self.data.as_slice() {
+            [] => {},
+            [Data::File(path)] if content_type.contains("json") => {
+                arg.json_file = Some(path.clone())
+            },
+            [Data::File(path)] => arg.text_file = Some(path.clone()),
+            data => {
+                // curl joins multiple data with `&`
+                let mut texts = vec![];
+                for data in data {
+                    match data {
+                        Data::Text(text) => texts.push(text.as_str()),
+                        Data::File(path) => anyhow::bail!(
+                            "the file {path:?} can't be sent with other data \
+                            in the command of --from-curl"
+                        ),
+                    }
+                }
+                let text = texts.join("&");
+                if content_type.contains("json") {
+                    arg.json_body = Some(text);
+                } else {
+                    arg.text_body = Some(text);
+                }
+            },
+        }
+        // curl sends the data as they are, which are already encoded
+        if !self.data.is_empty() && content_type.is_empty() {
+            arg.headers
+                .push("content-type:application/x-www-form-urlencoded".into());
+        }
+        if !self.form.is_empty() && !self.data.is_empty() {
+            anyhow::bail!(
+                "-F can't be used with -d in the command of --from-curl"
+            );
+        }
+        arg.mp = self.form.iter().map(|(k, v)| format!("{k}:{v}")).collect();
+        arg.mp_file = self.form_files;
+
+        // the content type is decided by the body
+        let has_body_type = arg.json_body.is_some()
+            || arg.json_file.is_some()
+            || !arg.mp.is_empty()
+            || !arg.mp_file.is_empty();
+        for (name, value) in self.headers {
+            if has_body_type && name.eq_ignore_ascii_case("content-type") {
+                continue;
+            }
+            arg.headers.push(format!("{name}:{value}"));
+        }
+        arg.insecure |= self.insecure;
+        arg.cert = self.cert.or(arg.cert.take());
+        arg.key = self.key.or(arg.key.take());
+        Ok(())
+    }
+}
+
+/// parse the curl command, and fill the fields of the arg with it
+pub(crate) fn apply(command: &str, arg: &mut Arg) -> anyhow::Result<()> {
+    parse(command)?.apply(arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn from_curl(command: &str) -> anyhow::Result<Arg> {
+        let mut arg =
+            Arg::parse_from(["rsb", "-n", "1", "--from-curl", command]);
+        apply(command, &mut arg)?;
+        Ok(arg)
+    }
+
+    #[test]
+    fn test_parse_curl() {
+        let curl = parse(
+            "curl 'https://a.com/api' -X PUT -H 'Accept: */*' -HX-Id:1 \
+            --data-raw '{\"a\":1}' --data-binary @body.bin -u user:pass -k \
+            --cert=c.pem --key k.pem --compressed -F f=@a.txt;type=text/plain \
+            -F k=v",
+        )
+        .unwrap();
+        assert_eq!(curl.url.as_deref(), Some("https://a.com/api"));
+        assert_eq!(curl.method, Some(Method::Put));
+        assert_eq!(
+            curl.headers,
+            vec![
+                ("Accept".to_string(), "*/*".to_string()),
+                ("X-Id".to_string(), "1".to_string()),
+                (
+                    "authorization".to_string(),
+                    "Basic dXNlcjpwYXNz".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            curl.data,
+            vec![
+                Data::Text("{\"a\":1}".to_string()),
+                Data::File(PathBuf::from("body.bin"))
+            ]
+        );
+        assert_eq!(curl.form, vec![("k".to_string(), "v".to_string())]);
+        assert_eq!(
+            curl.form_files,
+            vec![("f".to_string(), PathBuf::from("a.txt"))]
+        );
+        assert!(curl.insecure);
+        assert_eq!(curl.cert, Some(PathBuf::from("c.pem")));
+        assert_eq!(curl.key, Some(PathBuf::from("k.pem")));
+    }
+
+    #[test]
+    fn test_parse_curl_invalid() {
+        for command in [
+            "wget http://a.com",
+            "curl http://a.com -o out.txt",
+            "curl http://a.com -X",
+            "curl http://a.com -X 'GET /'",
+            "curl http://a.com -H 'no colon'",
+            "curl http://a.com -b cookies.txt",
+            "curl http://a.com http://b.com",
+            "curl 'http://a.com",
+        ] {
+            assert!(parse(command).is_err(), "{command}");
+        }
+    }
+
+    #[test]
+    fn test_apply_curl_json() {
+        let arg = from_curl(
+            "curl 'https://a.com/api' -H 'content-type: application/json' \
+            --data-raw $'{\"name\":\"rsb\"}'",
+        )
+        .unwrap();
+        assert_eq!(arg.url.as_deref(), Some("https://a.com/api"));
+        assert_eq!(arg.method, Method::Post);
+        assert_eq!(arg.json_body.as_deref(), Some("{\"name\":\"rsb\"}"));
+        assert!(arg.headers.is_empty());
+    }
+
+    #[test]
+    fn test_apply_curl_form_and_text() {
+        let arg =
+            from_curl("curl https://a.com -d 'a=1&b=x%20y' -d c=2").unwrap();
+        assert!(arg.form.is_empty());
+        assert_eq!(arg.text_body.as_deref(), Some("a=1&b=x%20y&c=2"));
+        assert_eq!(
+            arg.headers,
+            vec!["content-type:application/x-www-form-urlencoded"]
+        );
+
+        // the data is kept as it is, such as json without a content type
+        let arg = from_curl(
+            "curl https://a.com -d '{\"a\":1}' -d 'k:1=a&k:1=b' \
+            -H 'Content-Type: application/x-www-form-urlencoded'",
+        )
+        .unwrap();
+        assert_eq!(arg.text_body.as_deref(), Some("{\"a\":1}&k:1=a&k:1=b"));
+        assert_eq!(
+            arg.headers,
+            vec!["Content-Type:application/x-www-form-urlencoded"]
+        );
+
+        let arg = from_curl(
+            "curl https://a.com -X PUT -H 'content-type: text/csv' -d 'a,b'",
+        )
+        .unwrap();
+        assert_eq!(arg.method, Method::Put);
+        assert_eq!(arg.text_body.as_deref(), Some("a,b"));
+        assert_eq!(arg.headers, vec!["content-type:text/csv"]);
+
+        let arg =
+            from_curl("curl https://a.com --data-binary @body.bin -I").unwrap();
+        assert_eq!(arg.method, Method::Head);
+        assert_eq!(arg.text_file, Some(PathBuf::from("body.bin")));
+        assert_eq!(
+            arg.headers,
+            vec!["content-type:application/x-www-form-urlencoded"]
+        );
+
+        assert!(from_curl("curl -k").is_err());
+        assert!(from_curl("curl https://a.com -d @a.json -d b").is_err());
+        assert!(from_curl("curl https://a.com -d a -F b=c").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! mod data feeds the rows of the file given by `--data-file` to the requests,
+//! the columns of each row become template variables, e.g. with the CSV file
+//!
+//! ```csv
+//! user_id,name
+//! 1001,alice
+//! 1002,bob
+//! ```
+//!
+//! `{{user_id}}` in the url, headers and body is replaced by `1001` for one
+//! request, and by `1002` for another. JSONL files have one JSON object per
+//! line, whose keys are the columns.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde_json::{Map, Value};
+
+use crate::arg::DataMode;
+use crate::json_path::value_to_string;
+
+/// [DataFile] is the rows loaded from `--data-file`, and the cursor of the
+/// rows already used
+#[derive(Debug)]
+pub(crate) struct DataFile {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    mode: DataMode,
+    once: bool,
+    cursor: AtomicUsize,
+}
+
+impl DataFile {
+    /// load the rows from the file, whose format is decided by the extension
+    pub(crate) fn load(
+        path: &Path,
+        mode: DataMode,
+        once: bool,
+    ) -> anyhow::Result<Self> {
+        let (columns, rows) =
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("csv") => read_csv(path)?,
+                Some("jsonl" | "ndjson") => read_jsonl(path)?,
+                _ => anyhow::bail!(
+                    "unsupported data file {path:?}, the extension must be one \
+                    of csv, jsonl and ndjson"
+                ),
+            };
+        Self::new(columns, rows, mode, once)
+    }
+
+    fn new(
+        columns: Vec<String>,
+        mut rows: Vec<Vec<String>>,
+        mode: DataMode,
+        once: bool,
+    ) -> anyhow::Result<Self> {
+        if rows.is_empty() {
+            anyhow::bail!("the data file has no rows");
+        }
+        if once && mode == DataMode::PerWorker {
+            anyhow::bail!(
+                "--data-once can't be used with --data-mode PER_WORKER, each \
+                connection keeps sending its own row"
+            );
+        }
+        // each row is used only once in random order
+        if once && mode == DataMode::Random {
+            rows.shuffle(&mut rand::thread_rng());
+        }
+
+        Ok(Self {
+            columns,
+            rows,
+            mode,
+            once,
+            cursor: AtomicUsize::new(0),
+        })
+    }
+
+    /// the names of the columns, in the order of the file
+    pub(crate) fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// the row for the next request sent by the worker, None if all rows are
+    /// used with `--data-once`
+    pub(crate) fn next(&self, worker_id: usize) -> Option<&[String]> {
+        let len = self.rows.len();
+        let index = match self.mode {
+            DataMode::Random if !self.once => {
+                rand::thread_rng().gen_range(0..len)
+            },
+            DataMode::PerWorker => worker_id % len,
+            _ => {
+                let index = self.cursor.fetch_add(1, Ordering::Relaxed);
+                if self.once && index >= len {
+                    return None;
+                }
+                index % len
+            },
+        };
+        Some(&self.rows[index])
+    }
+}
+
+type Rows = (Vec<String>, Vec<Vec<String>>);
+
+fn read_csv(path: &Path) -> anyhow::Result<Rows> {
+    let mut reader = csv::Reader::from_path(path).map_err(|e| {
+        anyhow::anyhow!("failed to read data file {path:?}: {e}")
+    })?;
+    let columns = reader.headers()?.iter().map(String::from).collect();
+    let mut rows = vec![];
+    for record in reader.records() {
+        rows.push(record?.iter().map(String::from).collect());
+    }
+    Ok((columns, rows))
+}
+
+/// the columns are all keys in the file, in the order they first appear, a
+/// key missing in a row is an empty string
+fn read_jsonl(path: &Path) -> anyhow::Result<Rows> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        anyhow::anyhow!("failed to read data file {path:?}: {e}")
+    })?;
+    let mut columns: Vec<String> = vec![];
+    let mut objects = vec![];
+    for (no, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let object: Map<String, Value> =
+            serde_json::from_str(line).map_err(|e| {
+                anyhow::anyhow!(
+                    "invalid line {} of data file {path:?}, expected a json \
+                    object: {e}",
+                    no + 1
+                )
+            })?;
+        for key in object.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+        objects.push(object);
+    }
+
+    let rows = objects
+        .iter()
+        .map(|object| {
+            columns
+                .iter()
+                .map(|column| {
+                    object.get(column).map_or(String::new(), value_to_string)
+                })
+                .collect()
+        })
+        .collect();
+    Ok((columns, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::env;
+
+    use super::*;
+    use crate::test_util::write_temp;
+
+    fn rows(n: usize) -> Vec<Vec<String>> {
+        (0..n).map(|i| vec![i.to_string()]).collect()
+    }
+
+    #[test]
+    fn test_data_file_load_csv() {
+        let path = write_temp(
+            "rsb_test_data.csv",
+            "id,name\n1,alice\n2,\"bob, jr\"\n",
+        );
+        let data = DataFile::load(&path, DataMode::Sequential, false).unwrap();
+        assert_eq!(data.columns(), ["id", "name"]);
+        assert_eq!(data.rows, vec![vec!["1", "alice"], vec!["2", "bob, jr"]]);
+
+        let path = write_temp("rsb_test_data_uneven.csv", "id,name\n1\n");
+        assert!(DataFile::load(&path, DataMode::Sequential, false).is_err());
+    }
+
+    #[test]
+    fn test_data_file_load_jsonl() {
+        let path = write_temp(
+            "rsb_test_data.jsonl",
+            "{\"id\": 1, \"name\": \"alice\"}\n\n{\"id\": 2, \"tag\": [1]}\n",
+        );
+        let data = DataFile::load(&path, DataMode::Sequential, false).unwrap();
+        assert_eq!(data.columns(), ["id", "name", "tag"]);
+        assert_eq!(
+            data.rows,
+            vec![vec!["1", "alice", ""], vec!["2", "", "[1]"]]
+        );
+
+        let path = write_temp("rsb_test_data_invalid.jsonl", "[1, 2]\n");
+        assert!(DataFile::load(&path, DataMode::Sequential, false).is_err());
+    }
+
+    #[test]
+    fn test_data_file_load_invalid() {
+        let path = write_temp("rsb_test_data.txt", "id\n1\n");
+        assert!(DataFile::load(&path, DataMode::Sequential, false).is_err());
+
+        let path = write_temp("rsb_test_data_empty.csv", "id\n");
+        assert!(DataFile::load(&path, DataMode::Sequential, false).is_err());
+
+        let missing = env::temp_dir().join("rsb_test_data_missing.csv");
+        assert!(DataFile::load(&missing, DataMode::Sequential, false).is_err());
+
+        let columns = vec!["id".to_string()];
+        assert!(
+            DataFile::new(columns, rows(1), DataMode::PerWorker, true).is_err()
+        );
+    }
+
+    #[test]
+    fn test_data_file_next_sequential() {
+        let columns = vec!["id".to_string()];
+        let data = DataFile::new(
+            columns.clone(),
+            rows(2),
+            DataMode::Sequential,
+            false,
+        )
+        .unwrap();
+        let ids: Vec<&str> =
+            (0..5).map(|_| data.next(0).unwrap()[0].as_str()).collect();
+        assert_eq!(ids, ["0", "1", "0", "1", "0"]);
+
+        let data = DataFile::new(columns, rows(2), DataMode::Sequential, true)
+            .unwrap();
+        assert_eq!(data.next(0).unwrap()[0], "0");
+        assert_eq!(data.next(1).unwrap()[0], "1");
+        assert!(data.next(0).is_none());
+    }
+
+    #[test]
+    fn test_data_file_next_random() {
+        let columns = vec!["id".to_string()];
+        let data =
+            DataFile::new(columns.clone(), rows(3), DataMode::Random, false)
+                .unwrap();
+        for _ in 0..100 {
+            assert!(data.next(0).is_some());
+        }
+
+        let data =
+            DataFile::new(columns, rows(100), DataMode::Random, true).unwrap();
+        let ids: HashSet<&str> = (0..100)
+            .map(|_| data.next(0).unwrap()[0].as_str())
+            .collect();
+        assert_eq!(ids.len(), 100);
+        assert!(data.next(0).is_none());
+    }
+
+    #[test]
+    fn test_data_file_next_per_worker() {
+        let columns = vec!["id".to_string()];
+        let data = DataFile::new(columns, rows(2), DataMode::PerWorker, false)
+            .unwrap();
+        assert_eq!(data.next(0).unwrap()[0], "0");
+        assert_eq!(data.next(0).unwrap()[0], "0");
+        assert_eq!(data.next(1).unwrap()[0], "1");
+        assert_eq!(data.next(2).unwrap()[0], "0");
+    }
+}
@@ -1,11 +1,14 @@
 //! dispatcher module is used to distribute tasks according to different models
 
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::*};
+use std::cmp::min;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering::*};
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use tokio::time;
 
+use crate::arg::Stage;
 use crate::limiter::Limiter;
 
 #[async_trait]
@@ -18,10 +21,33 @@ pub(crate) trait Dispatcher: Send + Sync {
     // return specific dispatcher inner limiter
     fn get_limiter(&self) -> &Option<Limiter>;
 
-    /// apply a token for execute task
-    async fn apply_token(&self) -> bool {
+    // return specific dispatcher inner schedule, only exists in open model
+    fn get_schedule(&self) -> &Option<Schedule>;
+
+    /// apply a token for execute task, return the time at which the job is
+    /// intended to start, in closed model it's the time of getting the token
+    async fn apply_token(&self) -> Option<Instant> {
         if self.is_canceled_or_done() {
-            return false;
+            return None;
+        }
+
+        if let Some(schedule) = self.get_schedule() {
+            let intended_at = schedule.next_slot();
+            loop {
+                let now = Instant::now();
+                if now >= intended_at {
+                    break;
+                }
+                if self.is_canceled_or_done() {
+                    return None;
+                }
+                time::sleep(min(intended_at - now, MAX_SCHEDULE_WAIT)).await;
+            }
+
+            if self.is_canceled_or_done() {
+                return None;
+            }
+            return Some(intended_at);
         }
 
         if let Some(limiter) = self.get_limiter() {
@@ -31,30 +57,72 @@ pub(crate) trait Dispatcher: Send + Sync {
                     break;
                 }
                 if self.is_canceled_or_done() {
-                    return false;
+                    return None;
                 }
                 time::sleep(Duration::from_micros(1)).await;
             }
         }
 
         if self.is_canceled_or_done() {
-            return false;
+            return None;
         }
-        true
+        Some(Instant::now())
     }
 
-    /// worker apply a job from dispatcher, return true continue to handle,
-    /// return false worker will exit.
-    async fn try_apply_job(&self) -> bool;
+    /// worker apply a job from dispatcher, return the intended start time of
+    /// the job to continue to handle, return None worker will exit.
+    async fn try_apply_job(&self) -> Option<Instant>;
 
     /// when worker complete job, it will notify the dispatcher
     fn complete_job(&self);
 
+    /// return the index of the stage which the last applied job belongs to,
+    /// only staged dispatcher has stages
+    fn current_stage(&self) -> Option<usize> {
+        None
+    }
+
     /// when the program receives an external termination signal, notify the
     /// Dispatcher to process it
     fn cancel(&mut self);
 }
 
+/// the longest time to sleep at once while waiting for a scheduled slot, so
+/// that cancellation can be noticed in time
+const MAX_SCHEDULE_WAIT: Duration = Duration::from_millis(10);
+
+/// [Schedule] hands out the intended send time of each request in open model,
+/// requests are evenly spaced according to the rate, no matter how long the
+/// previous requests took
+pub(crate) struct Schedule {
+    /// the interval between two consecutive requests
+    interval: Duration,
+
+    /// the time at which the first slot was handed out
+    started_at: OnceLock<Instant>,
+
+    /// number of slots already handed out
+    issued: AtomicU64,
+}
+
+impl Schedule {
+    /// give rate, return [Schedule]
+    pub(crate) fn new(rate: u16) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / rate as u32,
+            started_at: OnceLock::new(),
+            issued: AtomicU64::new(0),
+        }
+    }
+
+    /// reserve the next slot and return its intended send time
+    pub(crate) fn next_slot(&self) -> Instant {
+        let started_at = *self.started_at.get_or_init(Instant::now);
+        let slot = self.issued.fetch_add(1, SeqCst);
+        started_at + self.interval.mul_f64(slot as f64)
+    }
+}
+
 /// [CountDispatcher] is a count based task dispatcher
 pub(crate) struct CountDispatcher {
     /// total requests number will send to server
@@ -75,10 +143,16 @@ pub(crate) struct CountDispatcher {
     /// a rate limiter that limits the acquisition of a fixed number of tokens
     /// per second
     limiter: Option<Limiter>,
+
+    /// schedule of intended send times, replaces the limiter in open model
+    schedule: Option<Schedule>,
 }
 
-fn new_limiter(rate: &Option<u16>) -> Option<Limiter> {
+fn new_limiter(rate: &Option<u16>, open_model: bool) -> Option<Limiter> {
     let mut limiter: Option<Limiter> = None;
+    if open_model {
+        return limiter;
+    }
     if let Some(rate) = rate {
         limiter = Some(Limiter::new(*rate));
         // consume initial token at one time
@@ -87,12 +161,25 @@ fn new_limiter(rate: &Option<u16>) -> Option<Limiter> {
     limiter
 }
 
+fn new_schedule(rate: &Option<u16>, open_model: bool) -> Option<Schedule> {
+    match rate {
+        Some(rate) if open_model => Some(Schedule::new(*rate)),
+        _ => None,
+    }
+}
+
 impl CountDispatcher {
-    /// give total and rat, return [CountDispatcher]
-    pub(crate) fn new(total: u64, rate: &Option<u16>) -> Self {
+    /// give total and rat, return [CountDispatcher], in open model requests
+    /// are scheduled at a constant arrival rate instead of being limited
+    pub(crate) fn new(
+        total: u64,
+        rate: &Option<u16>,
+        open_model: bool,
+    ) -> Self {
         Self {
             total,
-            limiter: new_limiter(rate),
+            limiter: new_limiter(rate, open_model),
+            schedule: new_schedule(rate, open_model),
             applied: AtomicU64::new(0),
             completed: AtomicU64::new(0),
             is_canceled: AtomicBool::new(false),
@@ -111,22 +198,23 @@ impl Dispatcher for CountDispatcher {
         &self.limiter
     }
 
-    async fn try_apply_job(&self) -> bool {
-        if !self.apply_token().await {
-            return false;
-        }
+    fn get_schedule(&self) -> &Option<Schedule> {
+        &self.schedule
+    }
 
-        // is there any chance of apply a job
+    async fn try_apply_job(&self) -> Option<Instant> {
+        // is there any chance of apply a job, check it before applying the
+        // token, so that no slot of the schedule is wasted waiting
         if self.applied.load(Acquire) < self.total {
             let previous = self.applied.fetch_add(1, SeqCst);
             if previous >= self.total {
-                return false;
+                return None;
             }
         } else {
-            return false;
+            return None;
         }
 
-        true
+        self.apply_token().await
     }
 
     fn complete_job(&self) {
@@ -160,6 +248,9 @@ pub(crate) struct DurationDispatcher {
     /// per second
     limiter: Option<Limiter>,
 
+    /// schedule of intended send times, replaces the limiter in open model
+    schedule: Option<Schedule>,
+
     /// indicates whether it is canceled
     is_canceled: AtomicBool,
 
@@ -171,13 +262,20 @@ pub(crate) struct DurationDispatcher {
 }
 
 impl DurationDispatcher {
-    /// give total and rat, return [DurationDispatcher]
-    pub(crate) fn new(duration: Duration, rate: &Option<u16>) -> Self {
+    /// give total and rat, return [DurationDispatcher], in open model
+    /// requests are scheduled at a constant arrival rate instead of being
+    /// limited
+    pub(crate) fn new(
+        duration: Duration,
+        rate: &Option<u16>,
+        open_model: bool,
+    ) -> Self {
         Self {
             duration,
             canceled_at: None,
             start: Instant::now(),
-            limiter: new_limiter(rate),
+            limiter: new_limiter(rate, open_model),
+            schedule: new_schedule(rate, open_model),
             total: AtomicU64::new(0),
             is_canceled: AtomicBool::new(false),
             is_done: AtomicBool::new(false),
@@ -195,18 +293,20 @@ impl Dispatcher for DurationDispatcher {
         &self.limiter
     }
 
-    async fn try_apply_job(&self) -> bool {
-        if !self.apply_token().await {
-            return false;
-        }
+    fn get_schedule(&self) -> &Option<Schedule> {
+        &self.schedule
+    }
+
+    async fn try_apply_job(&self) -> Option<Instant> {
+        let intended_at = self.apply_token().await?;
 
         // when get the token, the time has expired, return and exit
-        if Instant::now() - self.start >= self.duration {
-            return false;
+        if intended_at.saturating_duration_since(self.start) >= self.duration {
+            return None;
         }
 
         self.total.fetch_add(1, SeqCst);
-        true
+        Some(intended_at)
     }
 
     fn complete_job(&self) {
@@ -225,6 +325,180 @@ impl Dispatcher for DurationDispatcher {
     }
 }
 
+/// [StageDispatcher] is a task dispatcher driven by a multi-stage load
+/// profile, the rate of the limiter is adjusted every second and the number of
+/// active workers is changed from stage to stage
+pub(crate) struct StageDispatcher {
+    /// stages of the load profile
+    stages: Vec<Stage>,
+
+    /// the number of workers, which is the default of active workers
+    connections: u16,
+
+    /// start time for executing test, which follows the clock of tokio, so
+    /// that the stages can be tested with paused time
+    start: time::Instant,
+
+    /// a rate limiter whose quota follows the current stage
+    limiter: Option<Limiter>,
+
+    /// the current quota of the limiter
+    rate: AtomicU64,
+
+    /// the second since start at which the quota was last updated
+    updated_second: AtomicU64,
+
+    /// the index of the current stage
+    stage: AtomicUsize,
+
+    /// number of jobs being executed
+    in_flight: AtomicU64,
+
+    /// the number of requests executed
+    total: AtomicU64,
+
+    /// indicates whether it is canceled
+    is_canceled: AtomicBool,
+
+    /// indicate whether to complete
+    is_done: AtomicBool,
+}
+
+impl StageDispatcher {
+    /// give stages and connections, return [StageDispatcher]
+    pub(crate) fn new(stages: Vec<Stage>, connections: u16) -> Self {
+        Self {
+            stages,
+            connections,
+            start: time::Instant::now(),
+            limiter: new_limiter(&Some(1), false),
+            rate: AtomicU64::new(1),
+            updated_second: AtomicU64::new(u64::MAX),
+            stage: AtomicUsize::new(0),
+            in_flight: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            is_canceled: AtomicBool::new(false),
+            is_done: AtomicBool::new(false),
+        }
+    }
+
+    /// find the stage at the elapsed time, return its index, the rate at that
+    /// time and the number of active connections, return None if all stages
+    /// are finished
+    fn stage_at(&self, elapsed: Duration) -> Option<(usize, u16, u16)> {
+        let mut stage_start = Duration::ZERO;
+        let mut previous_rate = 0f64;
+        for (index, stage) in self.stages.iter().enumerate() {
+            let stage_end = stage_start + stage.duration;
+            if elapsed < stage_end {
+                let progress = (elapsed - stage_start).as_secs_f64()
+                    / stage.duration.as_secs_f64();
+                let rate = previous_rate
+                    + (stage.rate as f64 - previous_rate) * progress;
+                let connections = stage
+                    .connections
+                    .map_or(self.connections, |c| min(c, self.connections));
+                return Some((index, rate.round() as u16, connections));
+            }
+            stage_start = stage_end;
+            previous_rate = stage.rate as f64;
+        }
+        None
+    }
+
+    /// update the quota of the limiter once per second, the rate is sampled
+    /// at the middle of the second
+    fn update_stage(&self) -> Option<(u16, u16)> {
+        let elapsed = time::Instant::now() - self.start;
+        let second = elapsed.as_secs();
+        let middle = Duration::from_secs(second) + Duration::from_millis(500);
+        let (_, rate, _) = self.stage_at(middle).or(self.stage_at(elapsed))?;
+        let (index, _, connections) = self.stage_at(elapsed)?;
+
+        let updated = self.updated_second.load(Acquire);
+        if updated != second
+            && self
+                .updated_second
+                .compare_exchange(updated, second, SeqCst, Acquire)
+                .is_ok()
+        {
+            self.stage.store(index, SeqCst);
+            if rate > 0 && self.rate.swap(rate as u64, SeqCst) != rate as u64 {
+                self.limiter.as_ref().unwrap().set_rate(rate);
+            }
+        }
+        Some((rate, connections))
+    }
+}
+
+#[async_trait]
+impl Dispatcher for StageDispatcher {
+    fn is_canceled_or_done(&self) -> bool {
+        self.is_done.load(Acquire) || self.is_canceled.load(Acquire)
+    }
+
+    fn get_limiter(&self) -> &Option<Limiter> {
+        &self.limiter
+    }
+
+    fn get_schedule(&self) -> &Option<Schedule> {
+        &None
+    }
+
+    async fn try_apply_job(&self) -> Option<Instant> {
+        let mut is_active = false;
+        loop {
+            let stage = match self.is_canceled_or_done() {
+                true => None,
+                false => self.update_stage(),
+            };
+            let Some((rate, connections)) = stage else {
+                if is_active {
+                    self.in_flight.fetch_sub(1, SeqCst);
+                }
+                return None;
+            };
+
+            // only the given number of workers are active in this stage
+            if !is_active {
+                is_active =
+                    self.in_flight.fetch_add(1, SeqCst) < connections as u64;
+                if !is_active {
+                    self.in_flight.fetch_sub(1, SeqCst);
+                }
+            }
+
+            if is_active
+                && rate > 0
+                && self.limiter.as_ref().unwrap().allow_fast().await.is_ok()
+            {
+                self.total.fetch_add(1, SeqCst);
+                return Some(Instant::now());
+            }
+            time::sleep(Duration::from_micros(1)).await;
+        }
+    }
+
+    fn complete_job(&self) {
+        self.in_flight.fetch_sub(1, SeqCst);
+        if self.stage_at(time::Instant::now() - self.start).is_none()
+            && !self.is_done.load(Acquire)
+        {
+            self.is_done.store(true, SeqCst);
+        }
+    }
+
+    fn current_stage(&self) -> Option<usize> {
+        Some(self.stage.load(Acquire))
+    }
+
+    fn cancel(&mut self) {
+        if !self.is_canceled.load(Acquire) {
+            self.is_canceled.store(true, SeqCst);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,20 +506,58 @@ mod tests {
     #[test]
     fn test_new_limiter_with_rate() {
         let rate = Some(10);
-        let limiter = new_limiter(&rate);
+        let limiter = new_limiter(&rate, false);
         assert!(limiter.is_some());
     }
 
     #[test]
     fn test_new_limiter_without_rate() {
         let rate: Option<u16> = None;
-        let limiter = new_limiter(&rate);
+        let limiter = new_limiter(&rate, false);
         assert!(limiter.is_none());
     }
 
+    #[test]
+    fn test_new_limiter_in_open_model() {
+        let limiter = new_limiter(&Some(10), true);
+        assert!(limiter.is_none());
+    }
+
+    #[test]
+    fn test_new_schedule() {
+        assert!(new_schedule(&Some(10), true).is_some());
+        assert!(new_schedule(&Some(10), false).is_none());
+        assert!(new_schedule(&None, true).is_none());
+    }
+
+    #[test]
+    fn test_schedule_next_slot() {
+        let schedule = Schedule::new(10);
+        let first = schedule.next_slot();
+        let second = schedule.next_slot();
+        let third = schedule.next_slot();
+        assert_eq!(second - first, Duration::from_millis(100));
+        assert_eq!(third - first, Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_count_dispatcher_open_model_keeps_intended_time() {
+        let dispatcher = CountDispatcher::new(3, &Some(100), true);
+        assert!(dispatcher.limiter.is_none());
+
+        let first = dispatcher.try_apply_job().await.unwrap();
+        // pretend the server stalled, the next slots are not shifted
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let second = dispatcher.try_apply_job().await.unwrap();
+        let third = dispatcher.try_apply_job().await.unwrap();
+        assert_eq!(second - first, Duration::from_millis(10));
+        assert_eq!(third - first, Duration::from_millis(20));
+        assert!(dispatcher.try_apply_job().await.is_none());
+    }
+
     #[test]
     fn test_count_dispatcher_new() {
-        let dispatcher = CountDispatcher::new(100, &Some(10));
+        let dispatcher = CountDispatcher::new(100, &Some(10), false);
         assert_eq!(dispatcher.applied.load(Acquire), 0);
         assert_eq!(dispatcher.completed.load(Acquire), 0);
         assert!(!dispatcher.is_canceled.load(Acquire));
@@ -254,7 +566,7 @@ mod tests {
 
     #[test]
     fn test_count_dispatcher_is_canceled_or_done() {
-        let dispatcher = CountDispatcher::new(100, &None);
+        let dispatcher = CountDispatcher::new(100, &None, false);
         assert!(!dispatcher.is_canceled_or_done());
 
         dispatcher.is_done.store(true, SeqCst);
@@ -263,22 +575,22 @@ mod tests {
 
     #[tokio::test]
     async fn test_count_dispatcher_try_apply_job() {
-        let dispatcher = CountDispatcher::new(5, &None);
+        let dispatcher = CountDispatcher::new(5, &None, false);
 
         // Apply 5 jobs
         for i in 0..5 {
             let result = dispatcher.try_apply_job().await;
-            assert!(result, "Job {} should succeed", i);
+            assert!(result.is_some(), "Job {} should succeed", i);
         }
 
         // 6th job should fail
         let result = dispatcher.try_apply_job().await;
-        assert!(!result);
+        assert!(result.is_none());
     }
 
     #[test]
     fn test_count_dispatcher_complete_job() {
-        let dispatcher = CountDispatcher::new(5, &None);
+        let dispatcher = CountDispatcher::new(5, &None, false);
 
         // Complete 5 jobs
         for _ in 0..5 {
@@ -291,7 +603,7 @@ mod tests {
 
     #[test]
     fn test_count_dispatcher_cancel() {
-        let mut dispatcher = CountDispatcher::new(100, &None);
+        let mut dispatcher = CountDispatcher::new(100, &None, false);
         assert!(!dispatcher.is_canceled.load(Acquire));
 
         dispatcher.cancel();
@@ -301,7 +613,7 @@ mod tests {
     #[test]
     fn test_duration_dispatcher_new() {
         let duration = Duration::from_secs(60);
-        let dispatcher = DurationDispatcher::new(duration, &Some(10));
+        let dispatcher = DurationDispatcher::new(duration, &Some(10), false);
         assert_eq!(dispatcher.total.load(Acquire), 0);
         assert!(!dispatcher.is_canceled.load(Acquire));
         assert!(!dispatcher.is_done.load(Acquire));
@@ -310,7 +622,7 @@ mod tests {
     #[test]
     fn test_duration_dispatcher_is_canceled_or_done() {
         let duration = Duration::from_secs(60);
-        let dispatcher = DurationDispatcher::new(duration, &None);
+        let dispatcher = DurationDispatcher::new(duration, &None, false);
         assert!(!dispatcher.is_canceled_or_done());
 
         dispatcher.is_done.store(true, SeqCst);
@@ -320,18 +632,18 @@ mod tests {
     #[tokio::test]
     async fn test_duration_dispatcher_try_apply_job() {
         let duration = Duration::from_secs(1);
-        let dispatcher = DurationDispatcher::new(duration, &None);
+        let dispatcher = DurationDispatcher::new(duration, &None, false);
 
         // Should be able to apply jobs within duration
         let result = dispatcher.try_apply_job().await;
-        assert!(result);
+        assert!(result.is_some());
         assert_eq!(dispatcher.total.load(Acquire), 1);
     }
 
     #[test]
     fn test_duration_dispatcher_complete_job() {
         let duration = Duration::from_secs(60);
-        let dispatcher = DurationDispatcher::new(duration, &None);
+        let dispatcher = DurationDispatcher::new(duration, &None, false);
 
         dispatcher.complete_job();
         // Should not be done since duration hasn't elapsed
@@ -341,7 +653,7 @@ mod tests {
     #[test]
     fn test_duration_dispatcher_cancel() {
         let duration = Duration::from_secs(60);
-        let mut dispatcher = DurationDispatcher::new(duration, &None);
+        let mut dispatcher = DurationDispatcher::new(duration, &None, false);
         assert!(!dispatcher.is_canceled.load(Acquire));
         assert!(dispatcher.canceled_at.is_none());
 
@@ -349,4 +661,88 @@ mod tests {
         assert!(dispatcher.is_canceled.load(Acquire));
         assert!(dispatcher.canceled_at.is_some());
     }
+
+    fn new_stage(duration: u64, rate: u16, connections: Option<u16>) -> Stage {
+        Stage {
+            duration: Duration::from_secs(duration),
+            rate,
+            connections,
+        }
+    }
+
+    #[test]
+    fn test_stage_dispatcher_stage_at() {
+        let stages = vec![
+            new_stage(10, 100, None),
+            new_stage(10, 100, Some(5)),
+            new_stage(10, 0, Some(100)),
+        ];
+        let dispatcher = StageDispatcher::new(stages, 10);
+
+        // ramp up from 0 to 100
+        assert_eq!(
+            dispatcher.stage_at(Duration::from_secs(0)),
+            Some((0, 0, 10))
+        );
+        assert_eq!(
+            dispatcher.stage_at(Duration::from_secs(5)),
+            Some((0, 50, 10))
+        );
+
+        // hold
+        assert_eq!(
+            dispatcher.stage_at(Duration::from_secs(15)),
+            Some((1, 100, 5))
+        );
+
+        // ramp down, the connections are limited by the workers
+        assert_eq!(
+            dispatcher.stage_at(Duration::from_millis(27500)),
+            Some((2, 25, 10))
+        );
+
+        // finished
+        assert_eq!(dispatcher.stage_at(Duration::from_secs(30)), None);
+    }
+
+    #[tokio::test]
+    async fn test_stage_dispatcher_try_apply_job() {
+        let stages = vec![new_stage(1, 1000, Some(1))];
+        let dispatcher = StageDispatcher::new(stages, 10);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(dispatcher.try_apply_job().await.is_some());
+        assert_eq!(dispatcher.current_stage(), Some(0));
+        assert_eq!(dispatcher.in_flight.load(Acquire), 1);
+
+        // only one worker is active in this stage
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            dispatcher.try_apply_job(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        dispatcher.complete_job();
+        assert_eq!(dispatcher.in_flight.load(Acquire), 0);
+        assert!(dispatcher.try_apply_job().await.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stage_dispatcher_finished() {
+        let stages = vec![new_stage(1, 1000, None)];
+        let dispatcher = StageDispatcher::new(stages, 10);
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(dispatcher.try_apply_job().await.is_none());
+        assert_eq!(dispatcher.in_flight.load(Acquire), 0);
+    }
+
+    #[test]
+    fn test_stage_dispatcher_cancel() {
+        let mut dispatcher =
+            StageDispatcher::new(vec![new_stage(1, 10, None)], 10);
+        assert!(!dispatcher.is_canceled_or_done());
+        dispatcher.cancel();
+        assert!(dispatcher.is_canceled_or_done());
+    }
 }
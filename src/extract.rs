@@ -0,0 +1,174 @@
+//! mod extract pulls values out of the responses of scenario steps, which are
+//! used by the templates of the later steps, e.g.
+//!
+//! ```toml
+//! [[steps]]
+//! name = "login"
+//! method = "POST"
+//! url = "/login"
+//! extract = { token = { json = "$.data.token" }, sid = { header = "x-sid" } }
+//!
+//! [[steps]]
+//! name = "profile"
+//! url = "/me"
+//! headers = { authorization = "Bearer {{token}}" }
+//! ```
+//!
+//! The supported sources are `json`, a [JsonPath], `regex`, whose first
+//! capture group, or the whole match if it has none, is taken from the body,
+//! and `header`, the value of a response header.
+
+use std::cell::OnceCell;
+
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::json_path::{JsonPath, value_to_string};
+
+/// exactly one of the fields is given, as YAML doesn't support enums as maps
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Source {
+    json: Option<String>,
+    regex: Option<String>,
+    header: Option<String>,
+}
+
+/// [Extract] is where a value is taken from the response
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Source")]
+pub(crate) enum Extract {
+    Json(JsonPath),
+    Regex(Regex),
+    Header(HeaderName),
+}
+
+impl TryFrom<Source> for Extract {
+    type Error = anyhow::Error;
+
+    fn try_from(source: Source) -> Result<Self, Self::Error> {
+        let extract = match (source.json, source.regex, source.header) {
+            (Some(path), None, None) => Extract::Json(JsonPath::parse(&path)?),
+            (None, Some(regex), None) => Extract::Regex(Regex::new(&regex)?),
+            (None, None, Some(name)) => {
+                Extract::Header(HeaderName::from_bytes(name.as_bytes())?)
+            },
+            _ => anyhow::bail!(
+                "invalid extract, one of json, regex and header is required"
+            ),
+        };
+        Ok(extract)
+    }
+}
+
+impl Extract {
+    /// whether the body of the response is needed
+    pub(crate) fn needs_body(&self) -> bool {
+        matches!(self, Extract::Json(_) | Extract::Regex(_))
+    }
+
+    /// take the value from the response, None if it's not found
+    pub(crate) fn apply(&self, payload: &Payload<'_>) -> Option<String> {
+        match self {
+            Extract::Json(path) => {
+                path.select(payload.json()?).map(value_to_string)
+            },
+            Extract::Regex(regex) => {
+                let text = String::from_utf8_lossy(payload.body);
+                let captures = regex.captures(&text)?;
+                captures
+                    .get(1)
+                    .or(captures.get(0))
+                    .map(|value| value.as_str().to_string())
+            },
+            Extract::Header(name) => payload
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from),
+        }
+    }
+}
+
+/// [Payload] is the part of the response which values are extracted from,
+/// the body is parsed as JSON at most once
+pub(crate) struct Payload<'a> {
+    headers: &'a HeaderMap,
+    body: &'a [u8],
+    json: OnceCell<Option<Value>>,
+}
+
+impl<'a> Payload<'a> {
+    pub(crate) fn new(headers: &'a HeaderMap, body: &'a [u8]) -> Self {
+        Self {
+            headers,
+            body,
+            json: OnceCell::new(),
+        }
+    }
+
+    fn json(&self) -> Option<&Value> {
+        self.json
+            .get_or_init(|| serde_json::from_slice(self.body).ok())
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn extract(source: &str) -> anyhow::Result<Extract> {
+        Ok(serde_json::from_str(source)?)
+    }
+
+    #[test]
+    fn test_extract_parse() {
+        assert!(matches!(
+            extract(r#"{"json": "$.token"}"#).unwrap(),
+            Extract::Json(_)
+        ));
+        assert!(matches!(
+            extract(r#"{"regex": "id=(\\d+)"}"#).unwrap(),
+            Extract::Regex(_)
+        ));
+        assert!(matches!(
+            extract(r#"{"header": "X-Sid"}"#).unwrap(),
+            Extract::Header(_)
+        ));
+        assert!(extract(r#"{"json": "token"}"#).is_err());
+        assert!(extract(r#"{"regex": "("}"#).is_err());
+        assert!(extract(r#"{"header": "a b"}"#).is_err());
+        assert!(extract(r#"{"cookie": "sid"}"#).is_err());
+        assert!(extract(r#"{"json": "$.a", "header": "b"}"#).is_err());
+        assert!(extract("{}").is_err());
+    }
+
+    #[test]
+    fn test_extract_apply() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-sid", HeaderValue::from_static("s1"));
+        let body = br#"{"data": {"token": "t1", "id": 7}, "csrf": "c=ab12"}"#;
+        let payload = Payload::new(&headers, body);
+
+        let apply = |source| extract(source).unwrap().apply(&payload);
+        assert_eq!(apply(r#"{"json": "$.data.token"}"#).unwrap(), "t1");
+        assert_eq!(apply(r#"{"json": "$.data.id"}"#).unwrap(), "7");
+        assert_eq!(apply(r#"{"json": "$.data.missing"}"#), None);
+        assert_eq!(apply(r#"{"regex": "c=(\\w+)"}"#).unwrap(), "ab12");
+        assert_eq!(apply(r#"{"regex": "c=\\w+"}"#).unwrap(), "c=ab12");
+        assert_eq!(apply(r#"{"regex": "nothing"}"#), None);
+        assert_eq!(apply(r#"{"header": "X-Sid"}"#).unwrap(), "s1");
+        assert_eq!(apply(r#"{"header": "x-missing"}"#), None);
+
+        let payload = Payload::new(&headers, b"not json");
+        assert_eq!(
+            extract(r#"{"json": "$.a"}"#).unwrap().apply(&payload),
+            None
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! mod generator runs `--json-command` to generate the bodies of the requests,
+//! the command line is split like a shell, see [crate::shell], and the
+//! command runs in one of the modes:
+//!
+//! - once before the test, its output is the body of all requests
+//! - once for each request with `--rebuild-body`
+//! - as a long-lived generator with `--json-generator`, which writes one body
+//!   per line on stdout, a new line is read for each request, and the test
+//!   stops once the generator exits
+//!
+//! The non-zero exits and timeouts of the command for each request, or of the
+//! generator, are counted as generator errors, and no request is sent.
+
+use std::fmt;
+use std::time::Duration;
+
+use async_process::{Child, ChildStdout, Command, Stdio};
+use bytes::Bytes;
+use futures_lite::io::{AsyncBufReadExt, BufReader};
+use tokio::sync as tsync;
+
+use crate::shell;
+
+/// [GeneratorError] is the failure to generate a body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum GeneratorError {
+    /// the command exits with non-zero code, or times out
+    Failed(String),
+    /// the generator has no more bodies
+    Exhausted,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::Failed(reason) => f.write_str(reason),
+            GeneratorError::Exhausted => f.write_str("generator exhausted"),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+/// [JsonCommand] is the parsed command line of `--json-command`
+#[derive(Debug, Clone)]
+pub(crate) struct JsonCommand {
+    program: String,
+    args: Vec<String>,
+    /// the timeout of each run, or of reading each line of the generator
+    timeout: Duration,
+}
+
+impl JsonCommand {
+    pub(crate) fn parse(
+        command: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let mut words = shell::split(command)?.into_iter();
+        let Some(program) = words.next() else {
+            anyhow::bail!("invalid json command `{command}`");
+        };
+        Ok(Self {
+            program,
+            args: words.collect(),
+            timeout,
+        })
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true);
+        command
+    }
+
+    /// run the command, its output is the body, it's killed once timed out
+    pub(crate) async fn run(&self) -> Result<Bytes, GeneratorError> {
+        let output =
+            tokio::time::timeout(self.timeout, self.command().output())
+                .await
+                .map_err(|_| {
+                    GeneratorError::Failed(format!(
+                        "json command timed out after {:?}",
+                        self.timeout
+                    ))
+                })?
+                .map_err(|e| {
+                    GeneratorError::Failed(format!("json command failed: {e}"))
+                })?;
+        if !output.status.success() {
+            return Err(GeneratorError::Failed(format!(
+                "json command failed, {}",
+                output.status
+            )));
+        }
+        Ok(Bytes::from(output.stdout))
+    }
+
+    /// start the command as a long-lived generator
+    pub(crate) fn spawn(&self) -> anyhow::Result<Generator> {
+        let mut child = self.command().stdout(Stdio::piped()).spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or(anyhow::anyhow!("no stdout of json generator"))?;
+        Ok(Generator {
+            timeout: self.timeout,
+            state: tsync::Mutex::new(State {
+                child,
+                stdout: BufReader::new(stdout),
+                line: vec![],
+                exhausted: false,
+            }),
+        })
+    }
+}
+
+struct State {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    /// the line being read, which is kept when reading it times out
+    line: Vec<u8>,
+    exhausted: bool,
+}
+
+/// [Generator] reads the bodies written by a long-lived command, one per line
+pub(crate) struct Generator {
+    timeout: Duration,
+    state: tsync::Mutex<State>,
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Generator")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Generator {
+    /// read the next body, the empty lines are skipped, the exit of the
+    /// generator is reported once, and it's exhausted afterwards
+    pub(crate) async fn next(&self) -> Result<Bytes, GeneratorError> {
+        let mut state = self.state.lock().await;
+        let State {
+            child,
+            stdout,
+            line,
+            exhausted,
+        } = &mut *state;
+        while !*exhausted {
+            let read = tokio::time::timeout(
+                self.timeout,
+                stdout.read_until(b'\n', line),
+            )
+            .await
+            .map_err(|_| {
+                GeneratorError::Failed(format!(
+                    "json generator timed out after {:?}",
+                    self.timeout
+                ))
+            })?;
+            match read {
+                // end of stdout, the generator has exited
+                Ok(0) => {
+                    *exhausted = true;
+                    return match child.status().await {
+                        Ok(status) if status.success() => {
+                            Err(GeneratorError::Exhausted)
+                        },
+                        Ok(status) => Err(GeneratorError::Failed(format!(
+                            "json generator failed, {status}"
+                        ))),
+                        Err(e) => Err(GeneratorError::Failed(format!(
+                            "json generator failed: {e}"
+                        ))),
+                    };
+                },
+                Ok(_) => {
+                    let body = std::mem::take(line);
+                    let body = body.trim_ascii();
+                    if !body.is_empty() {
+                        return Ok(Bytes::copy_from_slice(body));
+                    }
+                },
+                Err(e) => {
+                    return Err(GeneratorError::Failed(format!(
+                        "json generator failed: {e}"
+                    )));
+                },
+            }
+        }
+        Err(GeneratorError::Exhausted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(command: &str) -> JsonCommand {
+        JsonCommand::parse(command, Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn test_json_command_parse() {
+        let parsed = command(r#"echo '{"name": "rsb"}' "a b""#);
+        assert_eq!(parsed.program, "echo");
+        assert_eq!(parsed.args, [r#"{"name": "rsb"}"#, "a b"]);
+        assert!(JsonCommand::parse("  ", Duration::from_secs(1)).is_err());
+        assert!(JsonCommand::parse("echo 'a", Duration::from_secs(1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_command_run() {
+        let body = command(r#"echo '{"name": "rsb"}'"#).run().await.unwrap();
+        assert_eq!(body, "{\"name\": \"rsb\"}\n");
+
+        let error = command("sh -c 'exit 3'").run().await.unwrap_err();
+        assert!(error.to_string().contains("exit status: 3"), "{error}");
+
+        let command =
+            JsonCommand::parse("sleep 5", Duration::from_millis(100)).unwrap();
+        let error = command.run().await.unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_json_generator() {
+        let generator = command("printf '{\"id\": 1}\\n\\n{\"id\": 2}\\n'")
+            .spawn()
+            .unwrap();
+        assert_eq!(generator.next().await.unwrap(), "{\"id\": 1}");
+        assert_eq!(generator.next().await.unwrap(), "{\"id\": 2}");
+        assert_eq!(generator.next().await, Err(GeneratorError::Exhausted));
+        assert_eq!(generator.next().await, Err(GeneratorError::Exhausted));
+
+        let generator = command("sh -c 'echo 1; exit 2'").spawn().unwrap();
+        assert_eq!(generator.next().await.unwrap(), "1");
+        assert!(matches!(
+            generator.next().await,
+            Err(GeneratorError::Failed(_))
+        ));
+        assert_eq!(generator.next().await, Err(GeneratorError::Exhausted));
+    }
+}
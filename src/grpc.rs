@@ -0,0 +1,618 @@
+//! mod grpc calls the unary gRPC method given by `--grpc-method`, whose
+//! service is loaded from the `.proto` file of `--proto`, or the descriptor
+//! set of `--protoset`.
+//!
+//! The request message is given as json by `--json-body` or `--json-file`,
+//! and encoded by the descriptor of the method, the literal ones are encoded
+//! once. The calls are multiplexed over HTTP/2 connections, which are shared
+//! like the clients of HTTP/2, see [shared_connections], h2c is spoken with
+//! `http://` urls, and TLS with `https://` ones. The failures to connect are
+//! reported as [HandshakeError].
+
+use std::fs as sfs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use h2::client::SendRequest;
+use http::header::{CONTENT_TYPE, TE};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Uri};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use protobuf::Message as _;
+use protobuf::descriptor::FileDescriptorSet;
+use reqwest::header::{CONNECTION, HOST, TRANSFER_ENCODING};
+use tokio::net::TcpStream;
+use tokio::sync as tsync;
+use tokio::time::{self as ttime, Instant};
+use tokio_native_tls::TlsConnector;
+
+use crate::Arg;
+use crate::client::{
+    HandshakeError, build_tls_connector, default_headers, shared_connections,
+};
+use crate::template::{Context, Templates, is_template};
+
+/// the names of the gRPC status codes, indexed by the code
+pub(crate) const CODES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+const UNKNOWN: u32 = 2;
+
+/// the headers which are specific to HTTP/1.1 connections, they're not allowed
+/// in HTTP/2
+const CONNECTION_HEADERS: [&str; 3] =
+    ["keep-alive", "proxy-connection", "upgrade"];
+
+/// [Connection] is one of the HTTP/2 connections shared by the workers
+struct Connection {
+    send_request: SendRequest<Bytes>,
+    /// set once the connection is closed
+    closed: Arc<AtomicBool>,
+}
+
+/// [Call] is the result of a call which received its status
+#[derive(Debug)]
+pub(crate) struct Call {
+    /// the gRPC status code, the codes of the failed HTTP responses are
+    /// mapped like the gRPC clients do
+    pub(crate) code: u32,
+    /// number of bytes of the response messages
+    pub(crate) rsp_bytes: u64,
+}
+
+/// [GrpcClient] encodes the request messages and calls the method
+pub(crate) struct GrpcClient {
+    /// the url of the method, e.g. `http://127.0.0.1:50051/pkg.Service/Method`
+    uri: Uri,
+    input: MessageDescriptor,
+    /// the framed request message, None if it's rendered for each call
+    message: Option<Bytes>,
+    connector: TlsConnector,
+    headers: HeaderMap,
+    timeout: Duration,
+    connections: Vec<tsync::Mutex<Option<Connection>>>,
+}
+
+impl GrpcClient {
+    pub(crate) fn new(arg: &Arg) -> anyhow::Result<Self> {
+        let method = arg.grpc_method.as_deref().expect("gRPC method");
+        let url = arg.url.as_deref().unwrap_or_default().trim();
+        if is_template(url) {
+            anyhow::bail!("templated urls aren't supported with --grpc-method");
+        }
+        let pool = match (&arg.proto, &arg.protoset) {
+            (Some(proto), _) => parse_proto(proto, &arg.import_paths)?,
+            (None, Some(protoset)) => {
+                DescriptorPool::decode(Bytes::from(sfs::read(protoset)?))?
+            },
+            (None, None) => anyhow::bail!("--grpc-method needs --proto"),
+        };
+        // `pkg.Service/Method`, or `pkg.Service.Method`
+        let (service, name) = method
+            .rsplit_once('/')
+            .or_else(|| method.rsplit_once('.'))
+            .ok_or(anyhow::anyhow!("invalid gRPC method `{method}`"))?;
+        let descriptor = pool
+            .get_service_by_name(service)
+            .ok_or(anyhow::anyhow!("no gRPC service `{service}`"))?
+            .methods()
+            .find(|descriptor| descriptor.name() == name)
+            .ok_or(anyhow::anyhow!("no gRPC method `{name}` in `{service}`"))?;
+        if descriptor.is_client_streaming() || descriptor.is_server_streaming()
+        {
+            anyhow::bail!("gRPC method `{method}` isn't unary");
+        }
+        let uri = format!(
+            "{}/{}/{name}",
+            url.trim_end_matches('/'),
+            descriptor.parent_service().full_name()
+        )
+        .parse::<Uri>()?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) {
+            anyhow::bail!("gRPC urls must be http:// or https:// ones");
+        }
+
+        let input = descriptor.input();
+        let message = match (&arg.json_file, &arg.json_body) {
+            (Some(path), _) => Some(encode(&input, &sfs::read(path)?)?),
+            (None, Some(body)) if is_template(body) => None,
+            (None, Some(body)) => Some(encode(&input, body.as_bytes())?),
+            (None, None) => Some(encode(&input, b"{}")?),
+        };
+        Ok(Self {
+            uri,
+            input,
+            message,
+            connector: TlsConnector::from(build_tls_connector(arg, &["h2"])?),
+            headers: convert_headers(&default_headers(arg)?)?,
+            timeout: arg.timeout,
+            connections: (0..shared_connections(arg))
+                .map(|_| tsync::Mutex::new(None))
+                .collect(),
+        })
+    }
+
+    /// the framed request message of a call
+    pub(crate) fn message(
+        &self,
+        templates: &Templates,
+        context: &Context,
+    ) -> anyhow::Result<Bytes> {
+        if let Some(message) = &self.message {
+            return Ok(message.clone());
+        }
+        let template = templates.json_body.as_ref().expect("templated body");
+        encode(&self.input, template.render(context).as_bytes())
+    }
+
+    /// connect to the server, and start HTTP/2 over the connection
+    async fn connect(&self) -> Result<Connection, HandshakeError> {
+        let host = self.uri.host().unwrap_or_default();
+        let tls = self.uri.scheme_str() == Some("https");
+        let port = self.uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+        let tcp = TcpStream::connect((host.trim_matches(['[', ']']), port))
+            .await
+            .map_err(|e| HandshakeError(format!("{e}")))?;
+        let _ = tcp.set_nodelay(true);
+        let closed = Arc::new(AtomicBool::new(false));
+        let flag = closed.clone();
+        let send_request = match tls {
+            true => {
+                let stream = self
+                    .connector
+                    .connect(host, tcp)
+                    .await
+                    .map_err(|e| HandshakeError(format!("{e}")))?;
+                let (send_request, connection) = h2::client::handshake(stream)
+                    .await
+                    .map_err(|e| HandshakeError(format!("{e}")))?;
+                tokio::spawn(async move {
+                    let _ = connection.await;
+                    flag.store(true, Ordering::Release);
+                });
+                send_request
+            },
+            false => {
+                let (send_request, connection) = h2::client::handshake(tcp)
+                    .await
+                    .map_err(|e| HandshakeError(format!("{e}")))?;
+                tokio::spawn(async move {
+                    let _ = connection.await;
+                    flag.store(true, Ordering::Release);
+                });
+                send_request
+            },
+        };
+        Ok(Connection {
+            send_request,
+            closed,
+        })
+    }
+
+    /// the connection shared by the worker, which is connected on the first
+    /// call, or once it's closed
+    async fn send_request(
+        &self,
+        worker_id: usize,
+    ) -> Result<SendRequest<Bytes>, HandshakeError> {
+        let slot = &self.connections[worker_id % self.connections.len()];
+        let mut slot = slot.lock().await;
+        if let Some(connection) = slot.as_ref()
+            && !connection.closed.load(Ordering::Acquire)
+        {
+            return Ok(connection.send_request.clone());
+        }
+        let connected = ttime::timeout(self.timeout, self.connect())
+            .await
+            .map_err(|_| {
+            HandshakeError("handshake timed out".to_string())
+        })??;
+        let send_request = connected.send_request.clone();
+        *slot = Some(connected);
+        Ok(send_request)
+    }
+
+    /// call the method, the timeout covers the whole call including the
+    /// handshake, `headers` are rendered for the call, and take precedence
+    /// over the default ones
+    pub(crate) async fn call(
+        &self,
+        worker_id: usize,
+        message: Bytes,
+        headers: &reqwest::header::HeaderMap,
+    ) -> anyhow::Result<Call> {
+        let deadline = Instant::now() + self.timeout;
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.uri.clone())
+            .body(())?;
+        let merged = request.headers_mut();
+        merged.extend(self.headers.clone());
+        merged.extend(convert_headers(headers)?);
+        merged
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+        merged.insert(TE, HeaderValue::from_static("trailers"));
+        let millis = self.timeout.as_millis().min(99_999_999);
+        let value = format!("{millis}m");
+        merged.insert("grpc-timeout", HeaderValue::from_str(&value)?);
+
+        let exchange = async {
+            let send_request = self.send_request(worker_id).await?;
+            let mut send_request = send_request.ready().await?;
+            let (response, mut stream) =
+                send_request.send_request(request, false)?;
+            stream.send_data(message, true)?;
+            let (parts, mut body) = response.await?.into_parts();
+            if !parts.status.is_success() {
+                return Ok(Call {
+                    code: http_code(parts.status.as_u16()),
+                    rsp_bytes: 0,
+                });
+            }
+            // the status is in the headers of the trailers-only responses
+            if let Some(code) = status_code(&parts.headers) {
+                return Ok(Call { code, rsp_bytes: 0 });
+            }
+            let mut rsp_bytes = 0;
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk?;
+                rsp_bytes += chunk.len() as u64;
+                let _ = body.flow_control().release_capacity(chunk.len());
+            }
+            let trailers = body.trailers().await?.unwrap_or_default();
+            anyhow::Ok(Call {
+                code: status_code(&trailers).unwrap_or(UNKNOWN),
+                rsp_bytes,
+            })
+        };
+        ttime::timeout_at(deadline, exchange)
+            .await
+            .map_err(|_| anyhow::anyhow!("operation timed out"))?
+    }
+}
+
+/// parse the .proto file, the imports are searched in the directory of the
+/// file and the import paths, the well-known types are built in
+fn parse_proto(
+    proto: &Path,
+    import_paths: &[impl AsRef<Path>],
+) -> anyhow::Result<DescriptorPool> {
+    let dir = match proto.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let parsed = protobuf_parse::Parser::new()
+        .pure()
+        .include(dir)
+        .includes(import_paths)
+        .input(proto)
+        .parse_and_typecheck()?;
+    let mut set = FileDescriptorSet::new();
+    set.file = parsed.file_descriptors;
+    Ok(DescriptorPool::decode(Bytes::from(set.write_to_bytes()?))?)
+}
+
+/// encode the json message, and frame it with the uncompressed flag and its
+/// length
+fn encode(input: &MessageDescriptor, json: &[u8]) -> anyhow::Result<Bytes> {
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let message = DynamicMessage::deserialize(input.clone(), &mut deserializer)
+        .map_err(|e| {
+            anyhow::anyhow!("invalid message of `{}`: {e}", input.full_name())
+        })?;
+    deserializer.end()?;
+    let encoded = prost_reflect::prost::Message::encode_to_vec(&message);
+    let mut framed = BytesMut::with_capacity(encoded.len() + 5);
+    framed.put_u8(0);
+    framed.put_u32(encoded.len() as u32);
+    framed.put_slice(&encoded);
+    Ok(framed.freeze())
+}
+
+/// convert the headers of reqwest, the connection-specific ones are removed
+fn convert_headers(
+    headers: &reqwest::header::HeaderMap,
+) -> anyhow::Result<HeaderMap> {
+    let mut converted = HeaderMap::new();
+    for (name, value) in headers {
+        if name == CONNECTION
+            || name == HOST
+            || name == TRANSFER_ENCODING
+            || CONNECTION_HEADERS.contains(&name.as_str())
+        {
+            continue;
+        }
+        converted.insert(
+            HeaderName::from_bytes(name.as_str().as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+    Ok(converted)
+}
+
+fn status_code(headers: &HeaderMap) -> Option<u32> {
+    headers.get("grpc-status")?.to_str().ok()?.parse().ok()
+}
+
+/// the code of a failed HTTP response, which is mapped by the gRPC clients
+fn http_code(status: u16) -> u32 {
+    match status {
+        // INTERNAL
+        400 => 13,
+        // UNAUTHENTICATED
+        401 => 16,
+        // PERMISSION_DENIED
+        403 => 7,
+        // UNIMPLEMENTED
+        404 => 12,
+        // UNAVAILABLE
+        429 | 502 | 503 | 504 => 14,
+        _ => UNKNOWN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+
+    use clap::Parser;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::Task;
+
+    /// write the protos of the tests, the greeter imports the messages from
+    /// the directory given by `--import-path`
+    fn write_protos() -> PathBuf {
+        let dir = env::temp_dir().join("rsb_test_grpc");
+        sfs::create_dir_all(dir.join("common")).unwrap();
+        sfs::write(
+            dir.join("common/hello.proto"),
+            r#"syntax = "proto3";
+package helloworld;
+message HelloRequest { string name = 1; int32 times = 2; }
+message HelloReply { string message = 1; }
+"#,
+        )
+        .unwrap();
+        let proto = dir.join("greeter.proto");
+        sfs::write(
+            &proto,
+            r#"syntax = "proto3";
+package helloworld;
+import "hello.proto";
+service Greeter {
+  rpc SayHello (HelloRequest) returns (HelloReply);
+  rpc SayBye (HelloRequest) returns (HelloReply);
+  rpc SayHellos (HelloRequest) returns (stream HelloReply);
+}
+"#,
+        )
+        .unwrap();
+        proto
+    }
+
+    /// start a gRPC server in the background, which echoes the message of
+    /// SayHello, and fails the ones whose name is `unavailable`
+    fn start_server() -> SocketAddr {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream));
+                }
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    async fn serve(stream: TcpStream) {
+        let Ok(mut connection) = h2::server::handshake(stream).await else {
+            return;
+        };
+        while let Some(Ok((request, mut respond))) = connection.accept().await {
+            tokio::spawn(async move {
+                let (parts, mut body) = request.into_parts();
+                let mut message = BytesMut::new();
+                while let Some(Ok(chunk)) = body.data().await {
+                    let _ = body.flow_control().release_capacity(chunk.len());
+                    message.extend_from_slice(&chunk);
+                }
+                let status = match parts.uri.path() {
+                    "/helloworld.Greeter/SayHello" => 200,
+                    _ => 404,
+                };
+                let response = http::Response::builder()
+                    .status(status)
+                    .header(CONTENT_TYPE, "application/grpc");
+                if status != 200 {
+                    let response = response.body(()).unwrap();
+                    let _ = respond.send_response(response, true);
+                    return;
+                }
+                let unavailable =
+                    message.windows(11).any(|word| word == b"unavailable");
+                let response = match unavailable {
+                    true => response.header("grpc-status", "14"),
+                    false => response,
+                };
+                let Ok(mut stream) = respond
+                    .send_response(response.body(()).unwrap(), unavailable)
+                else {
+                    return;
+                };
+                if unavailable {
+                    return;
+                }
+                let _ = stream.send_data(message.freeze(), false);
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                let _ = stream.send_trailers(trailers);
+            });
+        }
+    }
+
+    fn arg(args: &[&str]) -> Arg {
+        Arg::parse_from(["rsb"].iter().chain(args))
+    }
+
+    #[test]
+    fn test_grpc_message() {
+        let proto = write_protos();
+        let proto = proto.to_str().unwrap();
+        let import = env::temp_dir().join("rsb_test_grpc/common");
+        let import = import.to_str().unwrap();
+        let base = [
+            "-n",
+            "1",
+            "--proto",
+            proto,
+            "--import-path",
+            import,
+            "--grpc-method",
+        ];
+        let client = GrpcClient::new(&arg(&[
+            &base[..],
+            &["helloworld.Greeter.SayHello", "http://[::1]:50051"],
+        ]
+        .concat()))
+        .unwrap();
+        assert_eq!(
+            client.uri,
+            "http://[::1]:50051/helloworld.Greeter/SayHello"
+        );
+        // the empty message by default
+        assert_eq!(client.message.as_deref(), Some(&[0u8, 0, 0, 0, 0][..]));
+
+        let client = GrpcClient::new(&arg(&[
+            &base[..],
+            &[
+                "helloworld.Greeter/SayHello",
+                "--json-body",
+                r#"{"name": "rsb", "times": 2}"#,
+                "http://localhost/",
+            ],
+        ]
+        .concat()))
+        .unwrap();
+        assert_eq!(
+            client.message.as_deref(),
+            Some(&b"\0\0\0\0\x07\x0a\x03rsb\x10\x02"[..])
+        );
+
+        for (method, body) in [
+            ("helloworld.Greeter/SayHellos", "{}"),
+            ("helloworld.Greeter/SayNothing", "{}"),
+            ("helloworld.Farewell/SayHello", "{}"),
+            ("helloworld.Greeter/SayHello", r#"{"unknown": 1}"#),
+            ("helloworld.Greeter/SayHello", r#"{"times": "a"}"#),
+        ] {
+            let arg = arg(&[
+                &base[..],
+                &[method, "--json-body", body, "http://localhost/"],
+            ]
+            .concat());
+            assert!(GrpcClient::new(&arg).is_err(), "{method} {body}");
+        }
+
+        // the imports aren't found without the import path
+        let arg = arg(&[
+            "-n",
+            "1",
+            "--proto",
+            proto,
+            "--grpc-method",
+            "helloworld.Greeter/SayHello",
+            "http://localhost/",
+        ]);
+        assert!(GrpcClient::new(&arg).is_err());
+    }
+
+    #[test]
+    fn test_grpc_task() {
+        let proto = write_protos();
+        let import = env::temp_dir().join("rsb_test_grpc/common");
+        let pool = parse_proto(&proto, &[&import]).unwrap();
+        let protoset = env::temp_dir().join("rsb_test_grpc/greeter.pb");
+        sfs::write(&protoset, pool.encode_to_vec()).unwrap();
+
+        let addr = start_server();
+        let url = format!("http://{addr}");
+        let run = |method: &str, body: &str, url: &str| {
+            let arg = arg(&[
+                "-n",
+                "20",
+                "-c",
+                "4",
+                "--http2-max-streams",
+                "2",
+                "--protoset",
+                protoset.to_str().unwrap(),
+                "--grpc-method",
+                method,
+                "--json-body",
+                body,
+                url,
+            ]);
+            Arc::new(Task::new(arg, None).unwrap())
+                .run()
+                .unwrap()
+                .json_output()
+                .unwrap()
+        };
+
+        let output = run(
+            "helloworld.Greeter/SayHello",
+            r#"{"name": "{{seq}}"}"#,
+            &url,
+        );
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(output.rsp2xx, 0);
+        let grpc = output.grpc.unwrap();
+        assert_eq!(grpc.codes["OK"], 20);
+        assert_eq!(grpc.total(), 20);
+        assert_eq!(output.total_req_bytes, output.total_rsp_bytes);
+
+        let body = r#"{"name": "unavailable"}"#;
+        let output = run("helloworld.Greeter/SayHello", body, &url);
+        assert_eq!(output.grpc.unwrap().codes["UNAVAILABLE"], 20);
+
+        // the method isn't served
+        let output = run("helloworld.Greeter/SayBye", "{}", &url);
+        assert_eq!(output.grpc.unwrap().codes["UNIMPLEMENTED"], 20);
+
+        // nothing listens on the discard port
+        let url = "http://127.0.0.1:9";
+        let output = run("helloworld.Greeter/SayHello", "{}", url);
+        assert_eq!(output.handshake_errors.values().sum::<u64>(), 20);
+        assert_eq!(output.grpc.unwrap().total(), 0);
+    }
+}
@@ -0,0 +1,324 @@
+//! mod har imports the entries of a HAR file given by `--har`, which is
+//! captured by browsers, as the requests of a [Scenario].
+//!
+//! Each entry keeps its method, url, headers and body, and is named by its url
+//! pattern, in which the path segments looking like ids are replaced by
+//! `{id}`, e.g. `GET /users/{id}/orders`, so that the statistics of the same
+//! api are grouped together.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::arg::{Method, ReplayMode};
+use crate::scenario::{Scenario, ScenarioRequest};
+
+/// the headers which are set by the client, or only valid for the original
+/// connection
+const SKIPPED_HEADERS: [&str; 4] =
+    ["host", "content-length", "connection", "transfer-encoding"];
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: Log,
+}
+
+#[derive(Debug, Deserialize)]
+struct Log {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    request: HarRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<Header>,
+    post_data: Option<PostData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    #[serde(default)]
+    mime_type: String,
+    text: Option<String>,
+}
+
+/// load the entries of the HAR file as a scenario, the scheme, host and port
+/// of the entries are replaced by `base_url` when it's given
+pub(crate) fn load(
+    path: &Path,
+    base_url: Option<&str>,
+    mode: ReplayMode,
+) -> anyhow::Result<Scenario> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read har {path:?}: {e}"))?;
+    let har: Har = serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("invalid har {path:?}: {e}"))?;
+    let base_url = base_url.map(Url::parse).transpose()?;
+
+    let mut entries = vec![];
+    for (no, entry) in har.log.entries.into_iter().enumerate() {
+        let started_at = parse_started_at(&entry.started_date_time)?;
+        let request = entry.request;
+        let mut url = Url::parse(&request.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            continue;
+        }
+        let Ok(method) = Method::from_str(&request.method, true) else {
+            log::warn!(
+                "skip entry {} of har, invalid method {}",
+                no + 1,
+                request.method
+            );
+            continue;
+        };
+        if let Some(base_url) = &base_url {
+            let mut rebased = base_url.clone();
+            rebased.set_path(url.path());
+            rebased.set_query(url.query());
+            url = rebased;
+        }
+        entries.push((started_at, method, url, request));
+    }
+    entries.sort_by_key(|(started_at, ..)| *started_at);
+
+    // the host is only a part of the name if there are more than one
+    let with_host = entries
+        .iter()
+        .map(|(_, _, url, _)| url.host_str())
+        .collect::<HashSet<_>>()
+        .len()
+        > 1;
+    let mut requests = vec![];
+    let mut delays = vec![];
+    let mut last_started_at = None;
+    for (started_at, method, url, request) in entries {
+        let mut headers = BTreeMap::new();
+        for header in request.headers {
+            let name = header.name.to_lowercase();
+            if name.starts_with(':') || SKIPPED_HEADERS.contains(&name.as_str())
+            {
+                continue;
+            }
+            headers.insert(name, header.value);
+        }
+        let mut body = None;
+        if let Some(post_data) = request.post_data {
+            if !post_data.mime_type.is_empty() {
+                headers
+                    .entry("content-type".to_string())
+                    .or_insert(post_data.mime_type);
+            }
+            body = post_data.text;
+        }
+
+        delays.push(last_started_at.map_or(
+            Duration::ZERO,
+            |last_started_at| {
+                started_at
+                    .duration_since(last_started_at)
+                    .unwrap_or_default()
+            },
+        ));
+        last_started_at = Some(started_at);
+        requests.push(ScenarioRequest {
+            name: format!(
+                "{} {}",
+                method.to_reqwest_method(),
+                url_pattern(&url, with_host)
+            ),
+            weight: 1,
+            method,
+            url: url.to_string(),
+            headers,
+            body,
+            json: None,
+            form: None,
+            extract: BTreeMap::new(),
+        });
+    }
+    if requests.is_empty() {
+        anyhow::bail!("no http entries in har {path:?}");
+    }
+    Scenario::replay(requests, delays, mode, None)
+}
+
+/// parse the `startedDateTime` of the entry, the offset is dropped, which is
+/// the same for all entries of one capture
+fn parse_started_at(input: &str) -> anyhow::Result<SystemTime> {
+    let end = input
+        .char_indices()
+        .skip(19)
+        .find(|(_, c)| !(c.is_ascii_digit() || *c == '.'))
+        .map_or(input.len(), |(pos, _)| pos);
+    humantime::parse_rfc3339_weak(&input[..end])
+        .map_err(|e| anyhow::anyhow!("invalid startedDateTime `{input}`: {e}"))
+}
+
+/// whether the path segment looks like an id, numbers, UUIDs and long hex
+/// strings are
+fn is_id(segment: &str) -> bool {
+    !segment.is_empty()
+        && (segment.chars().all(|c| c.is_ascii_digit())
+            || (segment.len() >= 16
+                && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-')))
+}
+
+/// the path of the url, whose ids are replaced by `{id}`, the query is
+/// dropped
+fn url_pattern(url: &Url, with_host: bool) -> String {
+    let path = url
+        .path()
+        .split('/')
+        .map(|segment| if is_id(segment) { "{id}" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/");
+    match url.host_str() {
+        Some(host) if with_host => format!("{host}{path}"),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::test_util::write_temp;
+
+    const HAR: &str = r#"{
+      "log": {
+        "version": "1.2",
+        "entries": [
+          {
+            "startedDateTime": "2026-01-02T03:04:05.100Z",
+            "request": {
+              "method": "GET",
+              "url": "https://shop.com/users/1001/orders?page=1",
+              "headers": [
+                {"name": ":authority", "value": "shop.com"},
+                {"name": "Accept", "value": "application/json"},
+                {"name": "Content-Length", "value": "0"}
+              ]
+            }
+          },
+          {
+            "startedDateTime": "2026-01-02T03:04:05.350+00:00",
+            "request": {
+              "method": "POST",
+              "url": "https://shop.com/orders",
+              "headers": [],
+              "postData": {"mimeType": "application/json", "text": "{\"id\":1}"}
+            }
+          },
+          {
+            "startedDateTime": "2026-01-02T03:04:05.000Z",
+            "request": {"method": "GET", "url": "data:image/png;base64,AA=="}
+          },
+          {
+            "startedDateTime": "2026-01-02T03:04:06.350Z",
+            "request": {
+              "method": "GET",
+              "url": "https://shop.com/users/1002/orders",
+              "headers": []
+            }
+          }
+        ]
+      }
+    }"#;
+
+    #[test]
+    fn test_har_load() {
+        let path = write_temp("rsb_test.har", HAR);
+        let scenario = load(&path, None, ReplayMode::Timed).unwrap();
+        assert_eq!(
+            scenario.names(),
+            vec!["GET /users/{id}/orders", "POST /orders"]
+        );
+        assert_eq!(scenario.requests.len(), 3);
+        assert_eq!(scenario.group(2), 0);
+
+        let get = &scenario.requests[0];
+        assert_eq!(get.url, "https://shop.com/users/1001/orders?page=1");
+        assert_eq!(get.headers.len(), 1);
+        assert_eq!(get.headers["accept"], "application/json");
+
+        let post = &scenario.requests[1];
+        assert_eq!(post.method, Method::Post);
+        assert_eq!(post.body.as_deref(), Some("{\"id\":1}"));
+        assert_eq!(post.headers["content-type"], "application/json");
+
+        assert_eq!(scenario.delay(0), Duration::ZERO);
+        assert_eq!(scenario.delay(1), Duration::from_millis(250));
+        assert_eq!(scenario.delay(2), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_har_load_with_base_url() {
+        let path = write_temp("rsb_test_base_url.har", HAR);
+        let scenario = load(
+            &path,
+            Some("http://127.0.0.1:8080/ignored"),
+            ReplayMode::Ordered,
+        )
+        .unwrap();
+        assert_eq!(
+            scenario.requests[0].url,
+            "http://127.0.0.1:8080/users/1001/orders?page=1"
+        );
+    }
+
+    #[test]
+    fn test_har_load_invalid() {
+        let path = write_temp("rsb_test_invalid.har", "{\"log\": {}}");
+        assert!(load(&path, None, ReplayMode::Ordered).is_err());
+
+        let path =
+            write_temp("rsb_test_empty.har", "{\"log\": {\"entries\": []}}");
+        assert!(load(&path, None, ReplayMode::Ordered).is_err());
+
+        let missing = env::temp_dir().join("rsb_test_missing.har");
+        assert!(load(&missing, None, ReplayMode::Ordered).is_err());
+    }
+
+    #[test]
+    fn test_url_pattern() {
+        let pattern = |url: &str, with_host| {
+            url_pattern(&Url::parse(url).unwrap(), with_host)
+        };
+        assert_eq!(pattern("http://a.com/", false), "/");
+        assert_eq!(
+            pattern("http://a.com/v1/users/42?x=1", false),
+            "/v1/users/{id}"
+        );
+        assert_eq!(
+            pattern(
+                "http://a.com/files/6f1c1a4e-2b7d-4c3e-9a8b-0c1d2e3f4a5b/raw",
+                true
+            ),
+            "a.com/files/{id}/raw"
+        );
+        assert_eq!(pattern("http://a.com/cafe", false), "/cafe");
+    }
+}
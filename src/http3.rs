@@ -0,0 +1,462 @@
+//! mod http3 sends the requests over HTTP/3, which is built with the `http3`
+//! feature and enabled by `--http3`.
+//!
+//! The requests are still built by reqwest, and the responses are converted
+//! back to [reqwest::Response], so the workers and the statistics are shared
+//! with the other HTTP versions. All requests to one host are multiplexed over
+//! one QUIC connection, which is connected again once it's closed, the
+//! failures to connect are reported as [HandshakeError].
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use h3::client::SendRequest;
+use h3_quinn::OpenStreams;
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig, Endpoint};
+use reqwest::header::{CONNECTION, HOST, HeaderMap, TRANSFER_ENCODING};
+use reqwest::{Body, Request, Response, ResponseBuilderExt, Url};
+use rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio::sync as tsync;
+use tokio::time::{self as ttime, Instant};
+
+use crate::Arg;
+use crate::client::{HandshakeError, default_headers};
+
+/// the headers which are specific to HTTP/1.1 connections, they're not allowed
+/// in HTTP/3
+const CONNECTION_HEADERS: [&str; 4] =
+    ["keep-alive", "proxy-connection", "te", "upgrade"];
+
+/// [Connection] is the QUIC connection to one host
+struct Connection {
+    /// the connection is closed once its endpoint is dropped
+    _endpoint: Endpoint,
+    connection: quinn::Connection,
+    send_request: SendRequest<OpenStreams, Bytes>,
+}
+
+/// [Http3Client] sends the requests over HTTP/3
+pub(crate) struct Http3Client {
+    config: ClientConfig,
+    headers: HeaderMap,
+    timeout: Duration,
+    connections: tsync::Mutex<HashMap<(String, u16), Connection>>,
+}
+
+impl Http3Client {
+    pub(crate) fn new(arg: &Arg) -> anyhow::Result<Self> {
+        let provider = Arc::new(ring::default_provider());
+        let builder =
+            rustls::ClientConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(&[&rustls::version::TLS13])?;
+        let mut tls = match arg.insecure {
+            true => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerifier(
+                    provider,
+                )))
+                .with_no_client_auth(),
+            false => {
+                let mut roots = RootCertStore::empty();
+                roots.add_parsable_certificates(
+                    rustls_native_certs::load_native_certs().certs,
+                );
+                builder.with_root_certificates(roots).with_no_client_auth()
+            },
+        };
+        tls.alpn_protocols = vec![b"h3".to_vec()];
+        let config =
+            ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls)?));
+        Ok(Self {
+            config,
+            headers: default_headers(arg)?,
+            timeout: arg.timeout,
+            connections: tsync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// connect to the host of the url by QUIC, and start HTTP/3 over it
+    async fn connect(&self, url: &Url) -> Result<Connection, HandshakeError> {
+        // the brackets of ipv6 addresses are removed
+        let host = url
+            .host_str()
+            .ok_or(HandshakeError(format!("no host in {url}")))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addr = tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|e| HandshakeError(format!("{e}")))?
+            .next()
+            .ok_or(HandshakeError(format!("failed to lookup `{host}`")))?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let mut endpoint = Endpoint::client(local)
+            .map_err(|e| HandshakeError(format!("{e}")))?;
+        endpoint.set_default_client_config(self.config.clone());
+
+        let handshake = async {
+            let connection = endpoint
+                .connect(addr, &host)
+                .map_err(|e| HandshakeError(format!("{e}")))?
+                .await
+                .map_err(|e| HandshakeError(format!("{e}")))?;
+            let (mut driver, send_request) =
+                h3::client::new(h3_quinn::Connection::new(connection.clone()))
+                    .await
+                    .map_err(|e| HandshakeError(format!("{e}")))?;
+            tokio::spawn(async move { driver.wait_idle().await });
+            Ok((connection, send_request))
+        };
+        let (connection, send_request) =
+            ttime::timeout(self.timeout, handshake)
+                .await
+                .map_err(|_| {
+                    HandshakeError("handshake timed out".to_string())
+                })??;
+        Ok(Connection {
+            _endpoint: endpoint,
+            connection,
+            send_request,
+        })
+    }
+
+    /// the connection to the host of the url, which is connected on the first
+    /// request, or once it's closed
+    async fn send_request(
+        &self,
+        url: &Url,
+    ) -> Result<SendRequest<OpenStreams, Bytes>, HandshakeError> {
+        let key = (
+            url.host_str().unwrap_or_default().to_string(),
+            url.port_or_known_default().unwrap_or(443),
+        );
+        let mut connections = self.connections.lock().await;
+        if let Some(connection) = connections.get(&key)
+            && connection.connection.close_reason().is_none()
+        {
+            return Ok(connection.send_request.clone());
+        }
+        let connection = self.connect(url).await?;
+        let send_request = connection.send_request.clone();
+        connections.insert(key, connection);
+        Ok(send_request)
+    }
+
+    /// send the request, the timeout covers the whole response like reqwest,
+    /// the body of the response is read while it's consumed
+    pub(crate) async fn execute(
+        &self,
+        request: Request,
+    ) -> anyhow::Result<Response> {
+        let deadline = Instant::now() + self.timeout;
+        let url = request.url().clone();
+        let mut send_request = self.send_request(&url).await?;
+        let body = match request.body() {
+            Some(body) => Some(Bytes::copy_from_slice(body.as_bytes().ok_or(
+                anyhow::anyhow!("streaming bodies are not supported by HTTP/3"),
+            )?)),
+            None => None,
+        };
+
+        let mut builder = http1::Request::builder()
+            .method(request.method().as_str())
+            .uri(url.as_str());
+        let defaults = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !request.headers().contains_key(*name));
+        for (name, value) in request.headers().iter().chain(defaults) {
+            if name == CONNECTION
+                || name == HOST
+                || name == TRANSFER_ENCODING
+                || CONNECTION_HEADERS.contains(&name.as_str())
+            {
+                continue;
+            }
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+        let request = builder.body(())?;
+
+        let exchange = async {
+            let mut stream = send_request.send_request(request).await?;
+            if let Some(body) = body {
+                stream.send_data(body).await?;
+            }
+            stream.finish().await?;
+            let response = stream.recv_response().await?;
+            anyhow::Ok((stream, response))
+        };
+        let (mut stream, response) = ttime::timeout_at(deadline, exchange)
+            .await
+            .map_err(|_| anyhow::anyhow!("operation timed out"))??;
+
+        // the body is sent by chunks, the stream is stopped once the response
+        // is dropped
+        let (sender, receiver) = tsync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let chunk =
+                    match ttime::timeout_at(deadline, stream.recv_data()).await
+                    {
+                        Ok(Ok(Some(mut chunk))) => {
+                            Ok(chunk.copy_to_bytes(chunk.remaining()))
+                        },
+                        Ok(Ok(None)) => break,
+                        Ok(Err(e)) => Err(io::Error::other(e)),
+                        Err(_) => Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "operation timed out",
+                        )),
+                    };
+                let failed = chunk.is_err();
+                if sender.send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let body = Body::wrap_stream(futures_lite::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                receiver.recv().await.map(|chunk| (chunk, receiver))
+            },
+        ));
+
+        let mut builder = http::Response::builder()
+            .status(response.status().as_u16())
+            .version(http::Version::HTTP_3)
+            .url(url);
+        for (name, value) in response.headers() {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+        Ok(Response::from(builder.body(body)?))
+    }
+}
+
+/// [NoVerifier] accepts any certificate of the server with `--insecure`, the
+/// signatures of the handshake are still verified
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    use clap::Parser;
+    use quinn::crypto::rustls::QuicServerConfig;
+    use rustls::pki_types::PrivateKeyDer;
+    use rustls::pki_types::pem::PemObject;
+
+    use super::*;
+    use crate::Task;
+
+    /// start an HTTP/3 server in the background, which answers the path and
+    /// the body of each request, and the given `x-api-key` header
+    fn start_server() -> SocketAddr {
+        let tests =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/tests");
+        let certs = CertificateDer::pem_file_iter(tests.join("client.pem"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key =
+            PrivateKeyDer::from_pem_file(tests.join("client-key.pem")).unwrap();
+        let mut tls = rustls::ServerConfig::builder_with_provider(Arc::new(
+            ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap();
+        tls.alpn_protocols = vec![b"h3".to_vec()];
+        let config = quinn::ServerConfig::with_crypto(Arc::new(
+            QuicServerConfig::try_from(tls).unwrap(),
+        ));
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let endpoint =
+                    Endpoint::server(config, ([127, 0, 0, 1], 0).into())
+                        .unwrap();
+                sender.send(endpoint.local_addr().unwrap()).unwrap();
+                while let Some(incoming) = endpoint.accept().await {
+                    tokio::spawn(serve(incoming));
+                }
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    async fn serve(incoming: quinn::Incoming) {
+        let Ok(connection) = incoming.await else {
+            return;
+        };
+        let mut connection = h3::server::Connection::<_, Bytes>::new(
+            h3_quinn::Connection::new(connection),
+        )
+        .await
+        .unwrap();
+        while let Ok(Some(resolver)) = connection.accept().await {
+            tokio::spawn(async move {
+                let (request, mut stream) =
+                    resolver.resolve_request().await.unwrap();
+                let mut body = request.uri().path().to_string();
+                while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+                    let chunk = chunk.copy_to_bytes(chunk.remaining());
+                    body.push_str(&String::from_utf8_lossy(&chunk));
+                }
+                let mut response = http1::Response::builder().status(201);
+                if let Some(key) = request.headers().get("x-api-key") {
+                    response = response.header("x-api-key", key);
+                }
+                stream
+                    .send_response(response.body(()).unwrap())
+                    .await
+                    .unwrap();
+                stream.send_data(Bytes::from(body)).await.unwrap();
+                stream.finish().await.unwrap();
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http3_execute() {
+        let addr = start_server();
+        let url = format!("https://localhost:{}/echo", addr.port());
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "-k",
+            "-a",
+            "--http3",
+            "-H=x-api-key:k1",
+            "--",
+            &url,
+        ]);
+        let client = Http3Client::new(&arg).unwrap();
+        for body in ["a", "b"] {
+            let request = reqwest::Client::new()
+                .post(&url)
+                .body(body)
+                .build()
+                .unwrap();
+            let response = client.execute(request).await.unwrap();
+            assert_eq!(response.status(), 201);
+            assert_eq!(response.version(), reqwest::Version::HTTP_3);
+            assert_eq!(response.url().as_str(), url);
+            assert_eq!(response.headers()["x-api-key"], "k1");
+            assert_eq!(response.text().await.unwrap(), format!("/echo{body}"));
+        }
+        // the requests share one connection
+        assert_eq!(client.connections.lock().await.len(), 1);
+
+        // the certificate of the server isn't trusted
+        let arg = Arg::parse_from(["rsb", "-n", "1", "--http3", &url]);
+        let client = Http3Client::new(&arg).unwrap();
+        let request = reqwest::Client::new().get(&url).build().unwrap();
+        let error = client.execute(request).await.unwrap_err();
+        assert!(error.is::<HandshakeError>(), "{error}");
+    }
+
+    #[test]
+    fn test_http3_task() {
+        let addr = start_server();
+        let url = format!("https://localhost:{}/", addr.port());
+        let arg = Arg::parse_from([
+            "rsb", "-n", "20", "-c", "4", "-k", "--http3", &url,
+        ]);
+        let output = Arc::new(Task::new(arg, None).unwrap())
+            .run()
+            .unwrap()
+            .json_output()
+            .unwrap();
+        assert_eq!(output.rsp2xx, 20);
+        assert_eq!(output.protocols["HTTP/3.0"], 20);
+
+        // no QUIC server listens on the discard port
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "2",
+            "-c",
+            "1",
+            "-t",
+            "1s",
+            "--http3",
+            "https://127.0.0.1:9/",
+        ]);
+        let output = Arc::new(Task::new(arg, None).unwrap())
+            .run()
+            .unwrap()
+            .json_output()
+            .unwrap();
+        assert_eq!(output.handshake_errors.values().sum::<u64>(), 2);
+        assert!(output.errors.is_empty());
+    }
+}
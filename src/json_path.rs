@@ -0,0 +1,146 @@
+//! mod json_path implements a small subset of JSONPath, which selects a single
+//! value from a JSON document by keys and array indexes, e.g.
+//! `$.data.items[0].id` or `$['data']['id']`
+
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// [JsonPath] is a parsed JSONPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JsonPath {
+    expr: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// parse the expression, which must start with `$`
+    pub(crate) fn parse(expr: &str) -> anyhow::Result<Self> {
+        let expr = expr.trim();
+        let mut rest = expr.strip_prefix('$').ok_or(anyhow::anyhow!(
+            "invalid json path `{expr}`, it must start with `$`"
+        ))?;
+
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let end = tail.find(['.', '[']).unwrap_or(tail.len());
+                if end == 0 {
+                    anyhow::bail!("invalid json path `{expr}`, empty key");
+                }
+                segments.push(Segment::Key(tail[..end].to_string()));
+                rest = &tail[end..];
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let end = tail.find(']').ok_or(anyhow::anyhow!(
+                    "invalid json path `{expr}`, missing `]`"
+                ))?;
+                let inner = tail[..end].trim();
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|key| key.strip_suffix('\''))
+                    .or_else(|| {
+                        inner
+                            .strip_prefix('"')
+                            .and_then(|key| key.strip_suffix('"'))
+                    });
+                segments.push(match quoted {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(inner.parse().map_err(|_| {
+                        anyhow::anyhow!(
+                            "invalid json path `{expr}`, `{inner}` is \
+                            neither an index nor a quoted key"
+                        )
+                    })?),
+                });
+                rest = &tail[end + 1..];
+            } else {
+                anyhow::bail!(
+                    "invalid json path `{expr}`, unexpected `{rest}`"
+                );
+            }
+        }
+
+        Ok(Self {
+            expr: expr.to_string(),
+            segments,
+        })
+    }
+
+    /// select the value at the path, returns None if it doesn't exist
+    pub(crate) fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(index),
+            })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+/// convert the value to a plain string, strings are not quoted
+pub(crate) fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_path_parse() {
+        let path = JsonPath::parse("$.data.items[0]['id']").unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Key("data".to_string()),
+                Segment::Key("items".to_string()),
+                Segment::Index(0),
+                Segment::Key("id".to_string()),
+            ]
+        );
+        assert_eq!(path.to_string(), "$.data.items[0]['id']");
+        assert!(JsonPath::parse("$").unwrap().segments.is_empty());
+
+        assert!(JsonPath::parse("data.id").is_err());
+        assert!(JsonPath::parse("$..id").is_err());
+        assert!(JsonPath::parse("$.items[0").is_err());
+        assert!(JsonPath::parse("$.items[x]").is_err());
+    }
+
+    #[test]
+    fn test_json_path_select() {
+        let value = json!({"data": {"items": [{"id": 1}, {"id": "b"}]}});
+        let select =
+            |expr: &str| JsonPath::parse(expr).unwrap().select(&value).cloned();
+        assert_eq!(select("$.data.items[0].id"), Some(json!(1)));
+        assert_eq!(select("$[\"data\"].items[1].id"), Some(json!("b")));
+        assert_eq!(select("$.data.items[2]"), None);
+        assert_eq!(select("$.data.missing"), None);
+        assert_eq!(select("$"), Some(value.clone()));
+    }
+
+    #[test]
+    fn test_value_to_string() {
+        assert_eq!(value_to_string(&json!("abc")), "abc");
+        assert_eq!(value_to_string(&json!(12)), "12");
+        assert_eq!(value_to_string(&json!(true)), "true");
+        assert_eq!(value_to_string(&json!({"a": 1})), "{\"a\":1}");
+    }
+}
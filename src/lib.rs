@@ -4,13 +4,35 @@
 //! rsb is a http server benchmark tool.
 
 pub mod arg;
+pub(crate) mod assertion;
+pub(crate) mod auth;
 pub(crate) mod client;
+pub(crate) mod cookie;
+pub(crate) mod curl;
+pub(crate) mod data;
 pub(crate) mod dispatcher;
+pub(crate) mod extract;
+pub(crate) mod generator;
+pub(crate) mod grpc;
+pub(crate) mod har;
+#[cfg(feature = "http3")]
+pub(crate) mod http3;
+pub(crate) mod json_path;
 pub(crate) mod limiter;
+pub(crate) mod openapi;
 pub mod output;
 pub(crate) mod request;
+pub(crate) mod scenario;
+pub(crate) mod shell;
+pub(crate) mod sign;
 pub(crate) mod statistics;
+pub(crate) mod stream;
 pub mod task;
+pub(crate) mod template;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub(crate) mod threshold;
+pub(crate) mod websocket;
 
 pub use self::arg::Arg;
 pub use self::output::Output;
@@ -1,4 +1,5 @@
 use std::num::NonZeroU32;
+use std::sync::RwLock;
 
 use anyhow::anyhow;
 use governor::{
@@ -8,24 +9,32 @@ use governor::{
 };
 use tokio::time;
 
+type DirectRateLimiter = RateLimiter<NotKeyed, InMemoryState, DefaultClock>;
+
+fn new_rate_limiter(rate: u16) -> DirectRateLimiter {
+    RateLimiter::direct(Quota::per_second(
+        NonZeroU32::new(rate as u32).unwrap(),
+    ))
+}
+
 /// Limiter limit only sending a fixed number of requests per second
 pub(crate) struct Limiter {
-    inner: RateLimiter<NotKeyed, InMemoryState, DefaultClock>,
+    inner: RwLock<DirectRateLimiter>,
 }
 
 impl Limiter {
     /// create a new Limiter
     pub(crate) fn new(rate: u16) -> Limiter {
         Self {
-            inner: RateLimiter::direct(Quota::per_second(
-                NonZeroU32::new(rate as u32).unwrap(),
-            )),
+            inner: RwLock::new(new_rate_limiter(rate)),
         }
     }
 
     /// each check returns quickly, may fail or succeed
     pub(crate) async fn allow_fast(&self) -> anyhow::Result<()> {
         self.inner
+            .read()
+            .unwrap()
             .check()
             .map_err(|_| anyhow!("no available token"))
     }
@@ -33,13 +42,27 @@ impl Limiter {
     /// allow to obtain n tokens at one time
     pub(crate) fn allow_n(&self, n: usize) {
         loop {
-            let result = self.inner.check_n(NonZeroU32::new(n as u32).unwrap());
+            let result = self
+                .inner
+                .read()
+                .unwrap()
+                .check_n(NonZeroU32::new(n as u32).unwrap());
             if result.is_ok() {
                 break;
             }
             std::thread::sleep(time::Duration::from_nanos(100));
         }
     }
+
+    /// change the number of tokens per second, the new limiter is drained
+    /// before it's used, so that there is no burst
+    pub(crate) fn set_rate(&self, rate: u16) {
+        let limiter = new_rate_limiter(rate);
+        // the burst of a new limiter is one second of tokens, so they are
+        // consumed at once without waiting
+        let _ = limiter.check_n(NonZeroU32::new(rate as u32).unwrap());
+        *self.inner.write().unwrap() = limiter;
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +100,19 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_limiter_set_rate() {
+        let limiter = Limiter::new(1);
+        limiter.allow_n(1);
+        assert!(limiter.allow_fast().await.is_err());
+
+        // the initial tokens of the new quota are consumed too
+        limiter.set_rate(1000);
+        assert!(limiter.allow_fast().await.is_err());
+        time::sleep(time::Duration::from_millis(10)).await;
+        assert!(limiter.allow_fast().await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_limiter_rate_limiting() {
         let limiter = Limiter::new(2);
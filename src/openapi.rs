@@ -0,0 +1,568 @@
+//! mod openapi builds a [Scenario] from an OpenAPI 3 document given by
+//! `--openapi`, with one request for each operation of the document, e.g.
+//!
+//! ```yaml
+//! openapi: 3.0.3
+//! servers:
+//!   - url: http://127.0.0.1:8080/api
+//! paths:
+//!   /users/{user_id}:
+//!     get:
+//!       operationId: getUser
+//!       parameters:
+//!         - name: user_id
+//!           in: path
+//!           required: true
+//!           schema: { type: integer, example: 1001 }
+//! ```
+//!
+//! is the request `GET http://127.0.0.1:8080/api/users/1001` named `getUser`.
+//! The parameters and bodies are filled with the examples of the document,
+//! and a parameter named after a column of `--data-file` is filled with the
+//! rows of the file instead, e.g. `{{user_id}}`. `--openapi-list` prints the
+//! operations of the document instead of sending them.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::arg::{Method, ReplayMode};
+use crate::json_path::value_to_string;
+use crate::scenario::{Scenario, ScenarioRequest};
+
+/// the methods defined by OpenAPI, in the order of the path item object
+const METHODS: [(&str, Method); 8] = [
+    ("get", Method::Get),
+    ("put", Method::Put),
+    ("post", Method::Post),
+    ("delete", Method::Delete),
+    ("options", Method::Options),
+    ("head", Method::Head),
+    ("patch", Method::Patch),
+    ("trace", Method::Trace),
+];
+
+/// the max depth of `$ref`s and nested schemas, which stops recursive schemas
+const MAX_DEPTH: usize = 16;
+
+/// load the operations of the OpenAPI document as a scenario, `base_url`
+/// replaces the servers of the document when it's given, `variables` are the
+/// columns of `--data-file`
+pub(crate) fn load(
+    path: &Path,
+    base_url: Option<&str>,
+    variables: &[String],
+) -> anyhow::Result<Scenario> {
+    let document = read(path)?;
+    let server = match base_url {
+        Some(base_url) => base_url.to_string(),
+        None => server_url(&document)?,
+    };
+    let server = server.trim_end_matches('/');
+
+    let mut requests = vec![];
+    for (path, item) in paths(&document, path)? {
+        let item = resolve(&document, item)?;
+        for (name, method) in &METHODS {
+            let Some(operation) = item.get(name) else {
+                continue;
+            };
+            let operation = Operation {
+                document: &document,
+                variables,
+                path,
+                method: method.clone(),
+                item,
+                operation,
+            };
+            match operation.request(server) {
+                Ok(request) => requests.push(request),
+                Err(e) => log::warn!(
+                    "skip operation {} of openapi, {e}",
+                    operation.name()
+                ),
+            }
+        }
+    }
+    if requests.is_empty() {
+        anyhow::bail!("no operations to send in openapi {path:?}");
+    }
+    Scenario::replay(requests, vec![], ReplayMode::Ordered, Some(variables))
+}
+
+/// list the operations of the OpenAPI document for `--openapi-list`, one line
+/// for each with its method, path and `operationId`, `-` if it has none
+pub(crate) fn list(path: &Path) -> anyhow::Result<String> {
+    let document = read(path)?;
+    let mut operations = vec![];
+    for (path, item) in paths(&document, path)? {
+        let item = resolve(&document, item)?;
+        for (name, method) in &METHODS {
+            if let Some(operation) = item.get(name) {
+                let id = operation["operationId"].as_str().unwrap_or("-");
+                operations.push((method.to_reqwest_method(), path, id));
+            }
+        }
+    }
+    let width = operations.iter().map(|(_, path, _)| path.len()).max();
+    let width = width.unwrap_or_default();
+    let lines: Vec<String> = operations
+        .iter()
+        .map(|(method, path, id)| {
+            format!("{:<7} {path:<width$} {id}", method.as_str())
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// read the OpenAPI 3 document in JSON or YAML
+fn read(path: &Path) -> anyhow::Result<Value> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read openapi {path:?}: {e}"))?;
+    let document: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&content)?,
+        Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+        _ => anyhow::bail!(
+            "unsupported openapi {path:?}, the extension must be one of json, \
+            yaml and yml"
+        ),
+    };
+    if !document["openapi"]
+        .as_str()
+        .is_some_and(|v| v.starts_with("3."))
+    {
+        anyhow::bail!("unsupported openapi {path:?}, only OpenAPI 3 is");
+    }
+    Ok(document)
+}
+
+/// the path items of the document
+fn paths<'a>(
+    document: &'a Value,
+    path: &Path,
+) -> anyhow::Result<&'a Map<String, Value>> {
+    document["paths"]
+        .as_object()
+        .ok_or(anyhow::anyhow!("invalid openapi {path:?}: missing paths"))
+}
+
+/// the url of the first server, whose variables are replaced by their
+/// defaults
+fn server_url(document: &Value) -> anyhow::Result<String> {
+    let server = &document["servers"][0];
+    let mut url = server["url"].as_str().unwrap_or_default().to_string();
+    if let Some(variables) = server["variables"].as_object() {
+        for (name, variable) in variables {
+            let default = variable["default"].as_str().unwrap_or_default();
+            url = url.replace(&format!("{{{name}}}"), default);
+        }
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        anyhow::bail!(
+            "no absolute server url in openapi, `{url}` is given, the url \
+            must be given on the command line"
+        );
+    }
+    Ok(url)
+}
+
+/// follow the local `$ref` of the value, such as
+/// `#/components/schemas/User`
+fn resolve<'a>(
+    document: &'a Value,
+    value: &'a Value,
+) -> anyhow::Result<&'a Value> {
+    let mut value = value;
+    for _ in 0..MAX_DEPTH {
+        let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+            return Ok(value);
+        };
+        value = reference
+            .strip_prefix('#')
+            .and_then(|pointer| document.pointer(pointer))
+            .ok_or(anyhow::anyhow!("unresolved $ref `{reference}`"))?;
+    }
+    anyhow::bail!("too many nested $ref")
+}
+
+/// the example of the object with `example` or `examples`, which is a
+/// parameter, a media type or a schema
+fn example(document: &Value, object: &Value) -> Option<Value> {
+    if let Some(example) = object.get("example") {
+        return Some(example.clone());
+    }
+    match object.get("examples")? {
+        // the examples of schemas are an array
+        Value::Array(examples) => examples.first().cloned(),
+        Value::Object(examples) => {
+            let example = resolve(document, examples.values().next()?).ok()?;
+            example.get("value").cloned()
+        },
+        _ => None,
+    }
+}
+
+/// the example of the schema, which is built from the examples of its
+/// properties or items if it has none
+fn schema_example(
+    document: &Value,
+    schema: &Value,
+    depth: usize,
+) -> Option<Value> {
+    let schema = resolve(document, schema).ok()?;
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if let Some(example) = example(document, schema) {
+        return Some(example);
+    }
+    if let Some(default) = schema.get("default") {
+        return Some(default.clone());
+    }
+    if let Some(value) = schema["enum"].get(0) {
+        return Some(value.clone());
+    }
+    if let Some(schemas) = schema["allOf"].as_array() {
+        let mut object = Map::new();
+        for schema in schemas {
+            if let Some(Value::Object(example)) =
+                schema_example(document, schema, depth + 1)
+            {
+                object.extend(example);
+            }
+        }
+        return (!object.is_empty()).then_some(Value::Object(object));
+    }
+    if let Some(schema) = schema["oneOf"].get(0).or(schema["anyOf"].get(0)) {
+        return schema_example(document, schema, depth + 1);
+    }
+    if let Some(items) = schema.get("items") {
+        return schema_example(document, items, depth + 1)
+            .map(|item| Value::Array(vec![item]));
+    }
+    let properties = schema["properties"].as_object()?;
+    let object: Map<String, Value> = properties
+        .iter()
+        .filter_map(|(name, property)| {
+            schema_example(document, property, depth + 1)
+                .map(|example| (name.clone(), example))
+        })
+        .collect();
+    (!object.is_empty()).then_some(Value::Object(object))
+}
+
+/// percent-encode the value for the path and query of the url
+fn encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// [Operation] is one method of one path in the document
+struct Operation<'a> {
+    document: &'a Value,
+    variables: &'a [String],
+    path: &'a str,
+    method: Method,
+    item: &'a Value,
+    operation: &'a Value,
+}
+
+impl Operation<'_> {
+    /// the name of the request, the `operationId`, or the method and path
+    fn name(&self) -> String {
+        match self.operation["operationId"].as_str() {
+            Some(id) => id.to_string(),
+            None => {
+                format!("{} {}", self.method.to_reqwest_method(), self.path)
+            },
+        }
+    }
+
+    /// the parameters of the path item and the operation, the latter
+    /// overrides the former with the same name and location
+    fn parameters(&self) -> anyhow::Result<Vec<&Value>> {
+        let mut parameters: Vec<&Value> = vec![];
+        for parameter in
+            [&self.item["parameters"], &self.operation["parameters"]]
+                .into_iter()
+                .filter_map(Value::as_array)
+                .flatten()
+        {
+            let parameter = resolve(self.document, parameter)?;
+            parameters.retain(|p| {
+                p["name"] != parameter["name"] || p["in"] != parameter["in"]
+            });
+            parameters.push(parameter);
+        }
+        Ok(parameters)
+    }
+
+    /// the example of the parameter, or the one of its schema
+    fn parameter_example(&self, parameter: &Value) -> Option<String> {
+        example(self.document, parameter)
+            .or_else(|| schema_example(self.document, &parameter["schema"], 0))
+            .map(|value| value_to_string(&value))
+    }
+
+    fn request(&self, server: &str) -> anyhow::Result<ScenarioRequest> {
+        let mut path = self.path.to_string();
+        let mut query = vec![];
+        let mut headers = BTreeMap::new();
+        for parameter in self.parameters()? {
+            let name = parameter["name"].as_str().unwrap_or_default();
+            let location = parameter["in"].as_str().unwrap_or_default();
+            let required = location == "path"
+                || parameter["required"].as_bool().unwrap_or_default();
+            // the columns of the data file are always sent, the optional
+            // parameters are not sent with examples
+            let value = if self.variables.iter().any(|v| v == name) {
+                format!("{{{{{name}}}}}")
+            } else if !required {
+                continue;
+            } else {
+                let value = self.parameter_example(parameter).ok_or(
+                    anyhow::anyhow!(
+                        "no example of the {location} parameter `{name}`"
+                    ),
+                )?;
+                match location {
+                    "header" => value,
+                    _ => encode(&value),
+                }
+            };
+            match location {
+                "path" => path = path.replace(&format!("{{{name}}}"), &value),
+                "query" => query.push(format!("{}={value}", encode(name))),
+                "header" => {
+                    headers.insert(name.to_lowercase(), value);
+                },
+                _ => {},
+            }
+        }
+        let mut url = format!("{server}{path}");
+        if !query.is_empty() {
+            url = format!("{url}?{}", query.join("&"));
+        }
+
+        let mut request = ScenarioRequest {
+            name: self.name(),
+            weight: 1,
+            method: self.method.clone(),
+            url,
+            headers,
+            body: None,
+            json: None,
+            form: None,
+            extract: BTreeMap::new(),
+        };
+        self.set_body(&mut request)?;
+        Ok(request)
+    }
+
+    /// set the example of the request body, the JSON one is preferred
+    fn set_body(&self, request: &mut ScenarioRequest) -> anyhow::Result<()> {
+        let Some(body) = self.operation.get("requestBody") else {
+            return Ok(());
+        };
+        let body = resolve(self.document, body)?;
+        let required = body["required"].as_bool().unwrap_or_default();
+        let Some(content) = body["content"].as_object() else {
+            return Ok(());
+        };
+        let mut media_types: Vec<(&String, &Value)> = content.iter().collect();
+        media_types.sort_by_key(|(media_type, _)| !media_type.contains("json"));
+        for (media_type, media) in media_types {
+            let Some(example) = example(self.document, media)
+                .or_else(|| schema_example(self.document, &media["schema"], 0))
+            else {
+                continue;
+            };
+            if media_type.contains("json") {
+                request.json = Some(example);
+            } else if media_type == "application/x-www-form-urlencoded" {
+                let Value::Object(fields) = example else {
+                    continue;
+                };
+                request.form = Some(
+                    fields
+                        .iter()
+                        .map(|(k, v)| (k.clone(), value_to_string(v)))
+                        .collect(),
+                );
+            } else if media_type.starts_with("text/") {
+                request.body = Some(value_to_string(&example));
+                request
+                    .headers
+                    .insert("content-type".to_string(), media_type.clone());
+            } else {
+                continue;
+            }
+            return Ok(());
+        }
+        if required {
+            anyhow::bail!("no example of the request body");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_temp;
+
+    const OPENAPI: &str = r##"
+openapi: 3.0.3
+servers:
+  - url: "http://{host}:8080/api/"
+    variables:
+      host: { default: 127.0.0.1 }
+paths:
+  /users/{user_id}:
+    parameters:
+      - $ref: "#/components/parameters/UserId"
+    get:
+      operationId: getUser
+      parameters:
+        - { name: verbose, in: query, schema: { type: boolean } }
+        - { name: x-tenant, in: header, required: true, example: acme }
+    put:
+      operationId: updateUser
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema: { $ref: "#/components/schemas/User" }
+          text/plain:
+            example: ignored
+  /users:
+    post:
+      requestBody:
+        content:
+          application/x-www-form-urlencoded:
+            examples:
+              alice: { value: { name: alice, age: 20 } }
+    delete:
+      operationId: deleteUsers
+      parameters:
+        - { name: before, in: query, required: true, schema: { type: string } }
+    trace:
+      operationId: traceUsers
+components:
+  parameters:
+    UserId:
+      name: user_id
+      in: path
+      required: true
+      schema: { type: string, example: "a b" }
+  schemas:
+    User:
+      type: object
+      properties:
+        name: { type: string, example: alice }
+        tags: { type: array, items: { type: string, enum: [admin, user] } }
+        age: { type: integer }
+"##;
+
+    #[test]
+    fn test_openapi_load() {
+        let path = write_temp("rsb_test_openapi.yaml", OPENAPI);
+        let scenario = load(&path, None, &[]).unwrap();
+        // `deleteUsers` has no example of `before`
+        assert_eq!(
+            scenario.names(),
+            vec!["POST /users", "traceUsers", "getUser", "updateUser"]
+        );
+
+        let post = &scenario.requests[0];
+        assert_eq!(post.method, Method::Post);
+        assert_eq!(post.url, "http://127.0.0.1:8080/api/users");
+        let form = post.form.as_ref().unwrap();
+        assert_eq!(form["name"], "alice");
+        assert_eq!(form["age"], "20");
+
+        assert_eq!(scenario.requests[1].method, Method::Trace);
+
+        let get = &scenario.requests[2];
+        assert_eq!(get.url, "http://127.0.0.1:8080/api/users/a%20b");
+        assert_eq!(get.headers["x-tenant"], "acme");
+
+        let put = &scenario.requests[3];
+        assert_eq!(put.method, Method::Put);
+        assert_eq!(
+            put.json,
+            Some(serde_json::json!({"name": "alice", "tags": ["admin"]}))
+        );
+        assert!(put.body.is_none());
+    }
+
+    #[test]
+    fn test_openapi_load_with_variables() {
+        let path = write_temp("rsb_test_openapi_variables.yaml", OPENAPI);
+        let variables = vec!["user_id".to_string(), "verbose".to_string()];
+        let scenario =
+            load(&path, Some("http://localhost:3000/"), &variables).unwrap();
+        assert_eq!(
+            scenario.requests[2].url,
+            "http://localhost:3000/users/{{user_id}}?verbose={{verbose}}"
+        );
+    }
+
+    #[test]
+    fn test_openapi_load_invalid() {
+        let path =
+            write_temp("rsb_test_openapi_v2.json", "{\"swagger\": \"2.0\"}");
+        assert!(load(&path, None, &[]).is_err());
+
+        let path = write_temp(
+            "rsb_test_openapi_relative.json",
+            "{\"openapi\": \"3.1.0\", \"servers\": [{\"url\": \"/api\"}], \
+            \"paths\": {\"/\": {\"get\": {}}}}",
+        );
+        assert!(load(&path, None, &[]).is_err());
+        assert!(load(&path, Some("http://localhost"), &[]).is_ok());
+
+        let path = write_temp("rsb_test_openapi.txt", OPENAPI);
+        assert!(load(&path, None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_openapi_list() {
+        let path = write_temp("rsb_test_openapi_list.yaml", OPENAPI);
+        // the operations which can't be sent are listed too
+        assert_eq!(
+            list(&path).unwrap(),
+            "POST    /users           -\n\
+            DELETE  /users           deleteUsers\n\
+            TRACE   /users           traceUsers\n\
+            GET     /users/{user_id} getUser\n\
+            PUT     /users/{user_id} updateUser"
+        );
+
+        let path = write_temp(
+            "rsb_test_openapi_list_v2.json",
+            "{\"swagger\": \"2.0\"}",
+        );
+        assert!(list(&path).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("a-b_c.d~1"), "a-b_c.d~1");
+        assert_eq!(encode("a b/é"), "a%20b%2F%C3%A9");
+    }
+}
@@ -27,11 +27,21 @@ use std::fmt::{Display, Formatter, Write};
 use std::sync::atomic::Ordering;
 use std::time::Duration;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hdrhistogram::Histogram;
+use hdrhistogram::serialization::{
+    Deserializer, Serializer, V2DeflateSerializer,
+};
 use serde::{Deserialize, Serialize};
 use tokio::runtime;
 
 use crate::Arg;
-use crate::statistics::Statistics;
+use crate::grpc::CODES;
+use crate::statistics::{
+    GroupStatistics, GrpcStatistics, Statistics, StreamStatistics,
+    WebSocketStatistics,
+};
 
 /// the [Output] after executing the task, copied from the statistical results
 #[derive(Debug, Deserialize, Serialize)]
@@ -44,6 +54,11 @@ pub struct Output {
     /// calculates its standard deviation
     pub stdev_per_second: f64,
 
+    /// the data source is the same as `avg_req_per_second`, find its minimum
+    /// value, the incomplete last second is excluded
+    #[serde(default)]
+    pub min_req_per_second: f64,
+
     /// the data source is the same as `avg_req_per_second`, find its maximum
     /// value
     pub max_req_per_second: f64,
@@ -57,14 +72,25 @@ pub struct Output {
     /// standard deviation
     pub stdev_req_used_time: Micros,
 
+    /// the data source is the same as `avg_req_used_time`, find its minimum
+    /// value
+    #[serde(default)]
+    pub min_req_used_time: Micros,
+
     /// the data source is the same as `avg_req_used_time`, find its maximum
     /// value
     pub max_req_used_time: Micros,
 
-    /// sorts the response time of each request, then calculates it based on
-    /// the incoming percentage sequence parameter
+    /// the value at the rank of each incoming percentage, that is, the given
+    /// percentage of requests took no longer than it
     pub latencies: Vec<Latency>,
 
+    /// the histogram of the time taken for each request, unit: microseconds,
+    /// encoded in the compressed HdrHistogram V2 format and then base64, so it
+    /// can be decoded and merged by any HdrHistogram implementation
+    #[serde(default)]
+    pub histogram: String,
+
     /// status code [100, 200)
     pub rsp1xx: u64,
 
@@ -86,9 +112,91 @@ pub struct Output {
     /// errors encountered during the request and their count
     pub errors: HashMap<String, u64>,
 
+    /// failures to establish the connections of HTTP/3 and their count, they
+    /// aren't counted in the errors
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub handshake_errors: HashMap<String, u64>,
+
+    /// the negotiated HTTP versions of the responses and their count, e.g.
+    /// `HTTP/1.1` and `HTTP/2.0`
+    #[serde(default)]
+    pub protocols: HashMap<String, u64>,
+
+    /// failures of `--json-command` to generate the bodies and their count,
+    /// no request is sent for them, but they're counted as requests
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub generator_errors: HashMap<String, u64>,
+
+    /// number of requests, including the failed ones
+    #[serde(default)]
+    pub total: u64,
+
+    /// number of requests which failed, each of them is counted once, by an
+    /// error, an assertion, an extraction or the generator
+    #[serde(default)]
+    pub total_failed: u64,
+
+    /// number of responses which failed any assertion, they are counted in
+    /// the HTTP codes but not in the errors
+    #[serde(default)]
+    pub total_assertion_failed: u64,
+
+    /// number of responses which failed each assertion, only exists with
+    /// `--assert`, see [AssertionOutput]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<AssertionOutput>,
+
+    /// the result of each criterion given by `--threshold`, only exists with
+    /// thresholds, see [ThresholdOutput]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thresholds: Vec<ThresholdOutput>,
+
     /// Calculate the throughput of the Server, the calculation formula is:
     /// `connections / avg_req_used_time`
     pub throughput: f64,
+
+    /// total number of bytes sent in requests
+    #[serde(default)]
+    pub total_req_bytes: u64,
+
+    /// total number of bytes received in responses, response bodies are not
+    /// counted with `--skip-body`
+    #[serde(default)]
+    pub total_rsp_bytes: u64,
+
+    /// number of bytes received per second
+    #[serde(default)]
+    pub transfer_per_second: f64,
+
+    /// latencies measured from the intended start time of each request, only
+    /// exists in open model, see [CorrectedLatency]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected: Option<CorrectedLatency>,
+
+    /// statistics of each stage of the load profile, only exists with stages,
+    /// see [StageOutput]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageOutput>,
+
+    /// statistics of each named request of the scenario, only exists with
+    /// scenario, see [RequestOutput]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requests: Vec<RequestOutput>,
+
+    /// statistics of the sockets, only exists with WebSocket urls, whose
+    /// messages are counted as the requests, see [WebSocketOutput]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub websocket: Option<WebSocketOutput>,
+
+    /// the status codes of the gRPC calls, only exists with `--grpc-method`,
+    /// they take the place of the HTTP codes, see [GrpcOutput]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcOutput>,
+
+    /// the timing of the events of the responses, only exists with
+    /// `--stream`, see [StreamOutput]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<StreamOutput>,
 }
 
 impl Output {
@@ -96,15 +204,18 @@ impl Output {
         Self {
             avg_req_per_second: *(s.avg_req_per_second.lock().await),
             stdev_per_second: *(s.stdev_per_second.lock().await),
+            min_req_per_second: *(s.min_req_per_second.lock().await),
             max_req_per_second: *(s.max_req_per_second.lock().await),
             avg_req_used_time: (*(s.avg_req_used_time.lock().await)).into(),
             stdev_req_used_time: (*(s.stdev_req_used_time.lock().await)).into(),
+            min_req_used_time: (*(s.min_req_used_time.lock().await)).into(),
             max_req_used_time: (*(s.max_req_used_time.lock().await)).into(),
             latencies: (*(s.latencies.lock().await).clone())
                 .to_owned()
                 .iter()
                 .map(|x| Latency::new(x.0, x.1.into()))
                 .collect(),
+            histogram: encode_histogram(&*(s.used_time.lock().await)),
             rsp1xx: s.rsp1xx.load(Ordering::Acquire),
             rsp2xx: s.rsp2xx.load(Ordering::Acquire),
             rsp3xx: s.rsp3xx.load(Ordering::Acquire),
@@ -112,7 +223,45 @@ impl Output {
             rsp5xx: s.rsp5xx.load(Ordering::Acquire),
             rsp_others: s.rsp_others.load(Ordering::Acquire),
             errors: ((s.errors.lock().await).clone().to_owned()).to_owned(),
+            handshake_errors: s.handshake_errors.lock().await.clone(),
+            protocols: s.protocols.lock().await.clone(),
+            generator_errors: s.generator_errors.lock().await.clone(),
+            total: s.get_total(),
+            total_failed: s.total_failed.load(Ordering::Acquire),
+            total_assertion_failed: s
+                .total_assertion_failed
+                .load(Ordering::Acquire),
+            assertions: AssertionOutput::from_statistics(s),
+            thresholds: vec![],
             throughput: *(s.throughput.lock().await),
+            total_req_bytes: s.req_bytes.load(Ordering::Acquire),
+            total_rsp_bytes: s.rsp_bytes.load(Ordering::Acquire),
+            transfer_per_second: *(s.transfer_per_second.lock().await),
+            corrected: CorrectedLatency::from_statistics(s).await,
+            stages: s
+                .stages
+                .lock()
+                .await
+                .iter()
+                .enumerate()
+                .map(|(index, stage)| StageOutput {
+                    stage: index,
+                    statistics: stage.into(),
+                })
+                .collect(),
+            requests: s
+                .requests
+                .lock()
+                .await
+                .iter()
+                .map(|(name, request)| RequestOutput {
+                    name: name.clone(),
+                    statistics: request.into(),
+                })
+                .collect(),
+            websocket: WebSocketOutput::from_statistics(s).await,
+            grpc: s.grpc.as_ref().map(GrpcOutput::from),
+            stream: StreamOutput::from_statistics(s).await,
         }
     }
 
@@ -123,11 +272,337 @@ impl Output {
     }
 }
 
-/// Latency indicates how many seconds the first percentage of requests took
+/// [CorrectedLatency] is the latency corrected for coordinated omission, in
+/// open model each request is measured from the time at which it should have
+/// been sent rather than the time at which it was actually sent, so the time
+/// requests spent waiting for a stalled server is not hidden
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CorrectedLatency {
+    /// the average time taken for each request, measured from its intended
+    /// start time
+    pub avg_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, just calculates its
+    /// standard deviation
+    pub stdev_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, find its minimum
+    /// value
+    #[serde(default)]
+    pub min_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, find its maximum
+    /// value
+    pub max_req_used_time: Micros,
+
+    /// calculated based on the incoming percentage sequence parameter
+    pub latencies: Vec<Latency>,
+
+    /// the histogram of the corrected time, encoded in the same way as
+    /// [Output::histogram]
+    #[serde(default)]
+    pub histogram: String,
+}
+
+impl CorrectedLatency {
+    async fn from_statistics(s: &Statistics) -> Option<Self> {
+        if s.corrected_total.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+        Some(Self {
+            avg_req_used_time: (*(s.avg_corrected_used_time.lock().await))
+                .into(),
+            stdev_req_used_time: (*(s.stdev_corrected_used_time.lock().await))
+                .into(),
+            min_req_used_time: (*(s.min_corrected_used_time.lock().await))
+                .into(),
+            max_req_used_time: (*(s.max_corrected_used_time.lock().await))
+                .into(),
+            latencies: s
+                .corrected_latencies
+                .lock()
+                .await
+                .iter()
+                .map(|x| Latency::new(x.0, x.1.into()))
+                .collect(),
+            histogram: encode_histogram(&*(s.corrected_used_time.lock().await)),
+        })
+    }
+}
+
+/// [AssertionOutput] is the result of one rule given by `--assert`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AssertionOutput {
+    /// the rule, e.g. `status=200`
+    pub rule: String,
+
+    /// number of responses which failed the rule
+    pub failed: u64,
+}
+
+impl AssertionOutput {
+    fn from_statistics(s: &Statistics) -> Vec<Self> {
+        s.assertions
+            .iter()
+            .zip(&s.assertion_failures)
+            .map(|(assertion, failed)| Self {
+                rule: assertion.to_string(),
+                failed: failed.load(Ordering::Acquire),
+            })
+            .collect()
+    }
+}
+
+/// [ThresholdOutput] is the result of one criterion given by `--threshold`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ThresholdOutput {
+    /// the criterion, e.g. `p99<250ms`
+    pub threshold: String,
+
+    /// the actual value of the metric, None if it is unknown, e.g. the error
+    /// rate when no request was sent
+    pub actual: Option<String>,
+
+    /// whether the criterion is met
+    pub passed: bool,
+}
+
+impl Output {
+    /// whether all criteria given by `--threshold` are met
+    pub fn thresholds_passed(&self) -> bool {
+        self.thresholds.iter().all(|threshold| threshold.passed)
+    }
+}
+
+/// [GroupOutput] is the statistics of a group of requests, see [StageOutput]
+/// and [RequestOutput]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GroupOutput {
+    /// number of requests of the group, including failed ones
+    pub total: u64,
+
+    /// number of requests of the group which received a response
+    pub total_success: u64,
+
+    /// average number of successful requests per second of the group
+    pub avg_req_per_second: f64,
+
+    /// the average time taken for each request of the group
+    pub avg_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, just calculates its
+    /// standard deviation
+    pub stdev_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, find its minimum
+    /// value
+    #[serde(default)]
+    pub min_req_used_time: Micros,
+
+    /// the data source is the same as `avg_req_used_time`, find its maximum
+    /// value
+    pub max_req_used_time: Micros,
+
+    /// calculated based on the incoming percentage sequence parameter
+    pub latencies: Vec<Latency>,
+}
+
+impl From<&GroupStatistics> for GroupOutput {
+    fn from(s: &GroupStatistics) -> Self {
+        Self {
+            total: s.total,
+            total_success: s.total_success,
+            avg_req_per_second: s.avg_req_per_second,
+            avg_req_used_time: s.avg_req_used_time.into(),
+            stdev_req_used_time: s.stdev_req_used_time.into(),
+            min_req_used_time: s.min_req_used_time.into(),
+            max_req_used_time: s.max_req_used_time.into(),
+            latencies: s
+                .latencies
+                .iter()
+                .map(|x| Latency::new(x.0, x.1.into()))
+                .collect(),
+        }
+    }
+}
+
+/// [StageOutput] is the statistics of one stage of the load profile
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StageOutput {
+    /// the index of the stage, starts from 0
+    pub stage: usize,
+
+    /// the statistics of the requests sent during the stage
+    #[serde(flatten)]
+    pub statistics: GroupOutput,
+}
+
+/// [RequestOutput] is the statistics of one named request of the scenario
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestOutput {
+    /// the name of the request
+    pub name: String,
+
+    /// the statistics of the request
+    #[serde(flatten)]
+    pub statistics: GroupOutput,
+}
+
+/// [WebSocketOutput] is the statistics of the sockets of the WebSocket urls
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WebSocketOutput {
+    /// the time spent opening the sockets, including the handshake, the
+    /// failed ones are counted in `total` only
+    pub connects: GroupOutput,
+
+    /// the codes of the close frames of the server and their count, 1006
+    /// means a socket is closed without one
+    pub close_codes: HashMap<u16, u64>,
+
+    /// number of messages which received their replies, they take the place
+    /// of the HTTP codes
+    #[serde(default)]
+    pub replies: u64,
+
+    /// number of messages received which aren't the reply of any message
+    pub unmatched: u64,
+}
+
+impl WebSocketOutput {
+    async fn from_statistics(s: &Statistics) -> Option<Self> {
+        let websocket = s.websocket.as_ref()?;
+        Some(Self {
+            connects: (&*websocket.connects.lock().await).into(),
+            close_codes: websocket.close_codes.lock().await.clone(),
+            replies: websocket.replies.load(Ordering::Acquire),
+            unmatched: websocket.unmatched.load(Ordering::Acquire),
+        })
+    }
+}
+
+/// [GrpcOutput] is the status codes of the gRPC calls
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GrpcOutput {
+    /// the names of the codes received, e.g. `OK` and `UNAVAILABLE`, and
+    /// their count
+    pub codes: HashMap<String, u64>,
+
+    /// number of calls whose code is unknown to gRPC
+    pub others: u64,
+}
+
+impl GrpcOutput {
+    /// number of calls which received their status
+    pub fn total(&self) -> u64 {
+        self.codes.values().sum::<u64>() + self.others
+    }
+}
+
+impl From<&GrpcStatistics> for GrpcOutput {
+    fn from(grpc: &GrpcStatistics) -> Self {
+        Self {
+            codes: CODES
+                .iter()
+                .zip(&grpc.codes)
+                .map(|(name, count)| {
+                    (name.to_string(), count.load(Ordering::Acquire))
+                })
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            others: grpc.others.load(Ordering::Acquire),
+        }
+    }
+}
+
+/// [StreamOutput] is the timing of the events of the responses read as
+/// streams, the failed responses aren't counted
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StreamOutput {
+    /// the time to the headers of each response
+    pub ttfb: GroupOutput,
+
+    /// the time to the first event of each response which has any
+    pub first_event: GroupOutput,
+
+    /// the time between two consecutive events of a response
+    pub gaps: GroupOutput,
+
+    /// the time from each request to the end of its stream, or until it
+    /// stopped being read
+    pub duration: GroupOutput,
+
+    /// the number of events of each response, see [EventsOutput]
+    pub events: EventsOutput,
+}
+
+impl StreamOutput {
+    async fn from_statistics(s: &Statistics) -> Option<Self> {
+        let stream = s.stream.as_ref()?;
+        let events = stream.events.lock().await;
+        Some(Self {
+            ttfb: (&*stream.ttfb.lock().await).into(),
+            first_event: (&*stream.first_event.lock().await).into(),
+            gaps: (&*stream.gaps.lock().await).into(),
+            duration: (&*stream.durations.lock().await).into(),
+            events: EventsOutput {
+                total: stream.total_events.load(Ordering::Acquire),
+                avg: events.mean(),
+                stdev: events.stdev(),
+                min: events.min(),
+                max: events.max(),
+                percentiles: stream
+                    .event_counts
+                    .lock()
+                    .await
+                    .iter()
+                    .map(|(percent, events)| EventCount {
+                        percent: *percent,
+                        events: *events,
+                    })
+                    .collect(),
+            },
+        })
+    }
+}
+
+/// [EventsOutput] is the number of events of the streams
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EventsOutput {
+    /// number of events of all streams
+    pub total: u64,
+
+    /// the average number of events of each stream
+    pub avg: f64,
+
+    /// the standard deviation of the number of events
+    pub stdev: f64,
+
+    /// the minimum number of events of a stream
+    pub min: u64,
+
+    /// the maximum number of events of a stream
+    pub max: u64,
+
+    /// calculated based on the incoming percentage sequence parameter
+    pub percentiles: Vec<EventCount>,
+}
+
+/// [EventCount] is the number of events which the given percentage of streams
+/// had no more than
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct EventCount {
+    /// values from 0 to 1
+    pub percent: f64,
+    /// the number of events
+    pub events: u64,
+}
+
+/// Latency indicates the time within which the given percentage of requests
+/// completed, that is, the value at the rank of the percentile
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Latency {
     /// values from 0 to 1
-    pub percent: f32,
+    pub percent: f64,
     /// indicates the time taken to execute the request, unit: microseconds
     pub micros: Micros,
 }
@@ -140,13 +615,13 @@ impl Latency {
     /// * `percent` - values from 0 to 1
     /// * `micros` - indicates the time taken to execute the request, unit:
     ///   microseconds
-    pub fn new(percent: f32, micros: Micros) -> Self {
+    pub fn new(percent: f64, micros: Micros) -> Self {
         Self { percent, micros }
     }
 }
 
 /// Micros represents microseconds
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
 pub struct Micros(u64);
 
 impl From<Duration> for Micros {
@@ -161,6 +636,12 @@ impl From<&Duration> for Micros {
     }
 }
 
+impl From<Micros> for Duration {
+    fn from(micros: Micros) -> Self {
+        Duration::from_micros(micros.0)
+    }
+}
+
 impl Display for Micros {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let duration = Duration::from_micros(self.0);
@@ -168,6 +649,318 @@ impl Display for Micros {
     }
 }
 
+/// encode the histogram in the compressed HdrHistogram V2 format and then
+/// base64, an empty string is returned if it cannot be encoded
+fn encode_histogram(histogram: &Histogram<u64>) -> String {
+    let mut buf = Vec::new();
+    match V2DeflateSerializer::new().serialize(histogram, &mut buf) {
+        Ok(_) => STANDARD.encode(buf),
+        Err(_) => String::new(),
+    }
+}
+
+/// decode the histogram encoded by [encode_histogram]
+pub(crate) fn decode_histogram(encoded: &str) -> Option<Histogram<u64>> {
+    let buf = STANDARD.decode(encoded).ok()?;
+    Deserializer::new().deserialize(&mut buf.as_slice()).ok()
+}
+
+/// format the number of bytes in a human readable way, e.g. `1.50MB`
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024f64 && unit < UNITS.len() - 1 {
+        value /= 1024f64;
+        unit += 1;
+    }
+    format!("{value:.2}{}", UNITS[unit])
+}
+
+/// format percent as percentage, keep up to 4 decimal places, 0.999 is
+/// formatted as `99.9%`
+fn format_percent(percent: f64) -> String {
+    let percentage = format!("{:.4}", percent * 100f64);
+    let percentage = percentage.trim_end_matches('0').trim_end_matches('.');
+    format!("{percentage}%")
+}
+
+fn write_latency_rows(
+    output: &mut String,
+    indent: usize,
+    latencies: &[(f64, Duration)],
+    min: Duration,
+    max: Duration,
+) -> anyhow::Result<()> {
+    let rows = latencies
+        .iter()
+        .map(|(percent, duration)| (format_percent(*percent), *duration));
+    let rows = std::iter::once(("min".to_string(), min))
+        .chain(rows)
+        .chain(std::iter::once(("max".to_string(), max)));
+    for (label, duration) in rows {
+        writeln!(
+            output,
+            "{:indent$}{:^10}{:^10}",
+            "",
+            label,
+            format!("{duration:.2?}"),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_latency_distribution(
+    output: &mut String,
+    title: &str,
+    latencies: &[(f64, Duration)],
+    min: Duration,
+    max: Duration,
+) -> anyhow::Result<()> {
+    if !latencies.is_empty() {
+        writeln!(output, "  {title:<20}")?;
+        write_latency_rows(output, 2, latencies, min, max)?;
+    }
+    Ok(())
+}
+
+async fn write_stages(
+    output: &mut String,
+    s: &Statistics,
+    arg: &Arg,
+) -> anyhow::Result<()> {
+    let stages = s.stages.lock().await;
+    if stages.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(output, "  {:<20}", "Stages:")?;
+    writeln!(
+        output,
+        "    {:<6}{:^16}{:^10}{:^12}{:^12}{:^12}",
+        "Stage", "Target", "Reqs", "Reqs/sec", "Latency", "Max"
+    )?;
+    for (index, stage) in stages.iter().enumerate() {
+        let target = arg.stages.get(index).map_or(String::new(), |target| {
+            format!("{:?}:{}rps", target.duration, target.rate)
+        });
+        writeln!(
+            output,
+            "    {:<6}{:^16}{:^10}{:^12.2}{:^12}{:^12}",
+            index + 1,
+            target,
+            stage.total,
+            stage.avg_req_per_second,
+            format!("{:.2?}", stage.avg_req_used_time),
+            format!("{:.2?}", stage.max_req_used_time),
+        )?;
+        if arg.latencies && !stage.latencies.is_empty() {
+            write_latency_rows(
+                output,
+                6,
+                &stage.latencies,
+                stage.min_req_used_time,
+                stage.max_req_used_time,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// write the statistics of each named request of the scenario
+async fn write_requests(
+    output: &mut String,
+    s: &Statistics,
+    arg: &Arg,
+) -> anyhow::Result<()> {
+    let requests = s.requests.lock().await;
+    if requests.is_empty() {
+        return Ok(());
+    }
+
+    // long names like the url patterns of har entries widen the column
+    let width = requests
+        .iter()
+        .map(|(name, _)| name.chars().count() + 2)
+        .max()
+        .unwrap_or_default()
+        .max(20);
+    writeln!(output, "  {:<20}", "Requests:")?;
+    writeln!(
+        output,
+        "    {:<width$}{:^10}{:^12}{:^12}{:^12}",
+        "Name", "Reqs", "Reqs/sec", "Latency", "Max"
+    )?;
+    for (name, request) in requests.iter() {
+        writeln!(
+            output,
+            "    {:<width$}{:^10}{:^12.2}{:^12}{:^12}",
+            name,
+            request.total,
+            request.avg_req_per_second,
+            format!("{:.2?}", request.avg_req_used_time),
+            format!("{:.2?}", request.max_req_used_time),
+        )?;
+        if arg.latencies && !request.latencies.is_empty() {
+            write_latency_rows(
+                output,
+                6,
+                &request.latencies,
+                request.min_req_used_time,
+                request.max_req_used_time,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// write the rows of the times and the number of events of the streams, the
+/// times which have no value aren't written
+async fn write_stream_rows(
+    output: &mut String,
+    stream: &StreamStatistics,
+) -> anyhow::Result<()> {
+    for (label, group) in [
+        ("TTFB", &stream.ttfb),
+        ("First event", &stream.first_event),
+        ("Event gap", &stream.gaps),
+        ("Stream", &stream.durations),
+    ] {
+        let group = group.lock().await;
+        if group.total == 0 {
+            continue;
+        }
+        writeln!(
+            output,
+            "  {:<12}{:^14}{:^14}{:^14}{:^14}",
+            label,
+            format!("{:.2?}", group.avg_req_used_time),
+            format!("{:.2?}", group.stdev_req_used_time),
+            format!("{:.2?}", group.min_req_used_time),
+            format!("{:.2?}", group.max_req_used_time),
+        )?;
+    }
+    let events = stream.events.lock().await;
+    writeln!(
+        output,
+        "  {:<12}{:^14.2}{:^14.2}{:^14}{:^14}",
+        "Events",
+        events.mean(),
+        events.stdev(),
+        events.min(),
+        events.max(),
+    )?;
+    Ok(())
+}
+
+/// write the distributions of the times and the number of events of the
+/// streams
+async fn write_stream_distributions(
+    output: &mut String,
+    stream: &StreamStatistics,
+) -> anyhow::Result<()> {
+    for (title, group) in [
+        ("TTFB Distribution", &stream.ttfb),
+        ("First Event Distribution", &stream.first_event),
+        ("Event Gap Distribution", &stream.gaps),
+        ("Stream Distribution", &stream.durations),
+    ] {
+        let group = group.lock().await;
+        write_latency_distribution(
+            output,
+            title,
+            &group.latencies,
+            group.min_req_used_time,
+            group.max_req_used_time,
+        )?;
+    }
+    let event_counts = stream.event_counts.lock().await;
+    if !event_counts.is_empty() {
+        let events = stream.events.lock().await;
+        writeln!(output, "  {:<20}", "Events Distribution")?;
+        let rows = event_counts
+            .iter()
+            .map(|(percent, count)| (format_percent(*percent), *count));
+        let rows = std::iter::once(("min".to_string(), events.min()))
+            .chain(rows)
+            .chain(std::iter::once(("max".to_string(), events.max())));
+        for (label, count) in rows {
+            writeln!(output, "  {label:^10}{count:^10}")?;
+        }
+    }
+    Ok(())
+}
+
+/// write the close codes of the sockets, and the number of unmatched messages
+async fn write_close_codes(
+    output: &mut String,
+    websocket: &WebSocketStatistics,
+) -> anyhow::Result<()> {
+    let mut close_codes: Vec<_> = websocket
+        .close_codes
+        .lock()
+        .await
+        .clone()
+        .into_iter()
+        .collect();
+    close_codes.sort();
+    let close_codes: Vec<String> = close_codes
+        .into_iter()
+        .map(|(code, count)| format!("{code} - {count}"))
+        .collect();
+    writeln!(output, "  {:<20}", "Close codes:")?;
+    writeln!(output, "    {}", close_codes.join(", "))?;
+    let unmatched = websocket.unmatched.load(Ordering::Acquire);
+    if unmatched > 0 {
+        writeln!(output, "  {:<20}{unmatched}", "Unmatched messages:")?;
+    }
+    Ok(())
+}
+
+/// write the status codes of the gRPC calls, OK is always written, and the
+/// others once received
+fn write_grpc_codes(
+    output: &mut String,
+    grpc: &GrpcStatistics,
+) -> std::fmt::Result {
+    let codes: Vec<String> = CODES
+        .iter()
+        .zip(&grpc.codes)
+        .map(|(name, count)| (name, count.load(Ordering::Acquire)))
+        .enumerate()
+        .filter(|(code, (_, count))| *code == 0 || *count > 0)
+        .map(|(_, (name, count))| format!("{name} - {count}"))
+        .collect();
+    writeln!(output, "  {:<20}", "gRPC codes:")?;
+    writeln!(output, "    {}", codes.join(", "))?;
+    writeln!(
+        output,
+        "    others - {}",
+        grpc.others.load(Ordering::Acquire)
+    )
+}
+
+/// write the result of the thresholds, nothing is written without thresholds
+pub(crate) fn write_thresholds(
+    output: &mut String,
+    thresholds: &[ThresholdOutput],
+) -> std::fmt::Result {
+    if thresholds.is_empty() {
+        return Ok(());
+    }
+    write!(output, "\n  {:<20}", "Thresholds:")?;
+    for threshold in thresholds {
+        write!(
+            output,
+            "\n    {:<8}{:<24}{}",
+            if threshold.passed { "passed" } else { "FAILED" },
+            threshold.threshold,
+            threshold.actual.as_deref().unwrap_or("unknown")
+        )?;
+    }
+    Ok(())
+}
+
 pub(crate) fn sync_text_output(
     s: &Statistics,
     arg: &Arg,
@@ -182,55 +975,155 @@ pub(crate) async fn text_output(
     arg: &Arg,
 ) -> anyhow::Result<String> {
     let mut output = String::new();
+    // the messages of WebSocket urls are counted as the requests
+    let websocket = match &s.websocket {
+        Some(websocket) => Some(websocket.connects.lock().await),
+        None => None,
+    };
     writeln!(
         &mut output,
-        "{:<14}{:^14}{:^14}{:^14}
-  {:<12}{:^14.2}{:^14.2}{:^14.2}
-  {:<12}{:^14}{:^14}{:^14}",
+        "{:<14}{:^14}{:^14}{:^14}{:^14}
+  {:<12}{:^14.2}{:^14.2}{:^14.2}{:^14.2}
+  {:<12}{:^14}{:^14}{:^14}{:^14}",
         "Statistics",
         "Avg",
         "Stdev",
+        "Min",
         "Max",
-        "Reqs/sec",
+        if websocket.is_some() {
+            "Msgs/sec"
+        } else {
+            "Reqs/sec"
+        },
         *(s.avg_req_per_second.lock().await),
         *(s.stdev_per_second.lock().await),
+        *(s.min_req_per_second.lock().await),
         *(s.max_req_per_second.lock().await),
         "Latency",
         format!("{:.2?}", *(s.avg_req_used_time.lock().await)),
         format!("{:.2?}", *(s.stdev_req_used_time.lock().await)),
+        format!("{:.2?}", *(s.min_req_used_time.lock().await)),
         format!("{:.2?}", *(s.max_req_used_time.lock().await)),
     )?;
 
+    let is_corrected = s.corrected_total.load(Ordering::Acquire) > 0;
+    if is_corrected {
+        writeln!(
+            &mut output,
+            "  {:<12}{:^14}{:^14}{:^14}{:^14}",
+            "Corrected",
+            format!("{:.2?}", *(s.avg_corrected_used_time.lock().await)),
+            format!("{:.2?}", *(s.stdev_corrected_used_time.lock().await)),
+            format!("{:.2?}", *(s.min_corrected_used_time.lock().await)),
+            format!("{:.2?}", *(s.max_corrected_used_time.lock().await)),
+        )?;
+    }
+    if let Some(connects) = &websocket {
+        writeln!(
+            &mut output,
+            "  {:<12}{:^14}{:^14}{:^14}{:^14}",
+            "Connect",
+            format!("{:.2?}", connects.avg_req_used_time),
+            format!("{:.2?}", connects.stdev_req_used_time),
+            format!("{:.2?}", connects.min_req_used_time),
+            format!("{:.2?}", connects.max_req_used_time),
+        )?;
+    }
+
+    if let Some(stream) = &s.stream {
+        write_stream_rows(&mut output, stream).await?;
+    }
+
     if arg.latencies {
         let latencies = &*(s.latencies.lock().await);
-        if !latencies.is_empty() {
-            writeln!(&mut output, "  {:<20}", "Latency Distribution")?;
-            for (percent, duration) in latencies {
-                writeln!(
-                    &mut output,
-                    "  {:^10}{:^10}",
-                    format!("{:.0}%", *percent * 100f32),
-                    format!("{:.2?}", *duration),
-                )?;
-            }
+        write_latency_distribution(
+            &mut output,
+            "Latency Distribution",
+            latencies,
+            *(s.min_req_used_time.lock().await),
+            *(s.max_req_used_time.lock().await),
+        )?;
+        if is_corrected {
+            let latencies = &*(s.corrected_latencies.lock().await);
+            write_latency_distribution(
+                &mut output,
+                "Corrected Latency Distribution",
+                latencies,
+                *(s.min_corrected_used_time.lock().await),
+                *(s.max_corrected_used_time.lock().await),
+            )?;
+        }
+        if let Some(connects) = &websocket {
+            write_latency_distribution(
+                &mut output,
+                "Connect Distribution",
+                &connects.latencies,
+                connects.min_req_used_time,
+                connects.max_req_used_time,
+            )?;
+        }
+        if let Some(stream) = &s.stream {
+            write_stream_distributions(&mut output, stream).await?;
         }
     }
 
-    writeln!(&mut output, "  {:<20}", "HTTP codes:")?;
-    writeln!(
-        &mut output,
-        "    1XX - {}, 2XX - {}, 3XX - {}, 4XX - {}, 5XX - {}",
-        s.rsp1xx.load(Ordering::Acquire),
-        s.rsp2xx.load(Ordering::Acquire),
-        s.rsp3xx.load(Ordering::Acquire),
-        s.rsp4xx.load(Ordering::Acquire),
-        s.rsp5xx.load(Ordering::Acquire),
-    )?;
-    writeln!(
-        &mut output,
-        "    others - {}",
-        s.rsp_others.load(Ordering::Acquire)
-    )?;
+    match (&s.websocket, &s.grpc) {
+        (Some(websocket), _) => {
+            write_close_codes(&mut output, websocket).await?
+        },
+        (None, Some(grpc)) => write_grpc_codes(&mut output, grpc)?,
+        (None, None) => {
+            writeln!(&mut output, "  {:<20}", "HTTP codes:")?;
+            writeln!(
+                &mut output,
+                "    1XX - {}, 2XX - {}, 3XX - {}, 4XX - {}, 5XX - {}",
+                s.rsp1xx.load(Ordering::Acquire),
+                s.rsp2xx.load(Ordering::Acquire),
+                s.rsp3xx.load(Ordering::Acquire),
+                s.rsp4xx.load(Ordering::Acquire),
+                s.rsp5xx.load(Ordering::Acquire),
+            )?;
+            writeln!(
+                &mut output,
+                "    others - {}",
+                s.rsp_others.load(Ordering::Acquire)
+            )?;
+        },
+    }
+
+    // a downgrade to HTTP/1.1 is visible here
+    let protocols = s.protocols.lock().await;
+    if !protocols.is_empty() {
+        let mut protocols: Vec<_> = protocols.iter().collect();
+        protocols.sort();
+        let protocols: Vec<String> = protocols
+            .into_iter()
+            .map(|(version, count)| format!("{version} - {count}"))
+            .collect();
+        writeln!(&mut output, "  {:<20}", "Protocols:")?;
+        writeln!(&mut output, "    {}", protocols.join(", "))?;
+    }
+
+    if !s.assertions.is_empty() {
+        writeln!(
+            &mut output,
+            "  {:<20}{}",
+            "Assertions failed:",
+            s.total_assertion_failed.load(Ordering::Acquire)
+        )?;
+        for (assertion, failed) in
+            s.assertions.iter().zip(&s.assertion_failures)
+        {
+            writeln!(
+                &mut output,
+                "    \"{assertion}\":{:>8}",
+                failed.load(Ordering::Acquire)
+            )?;
+        }
+    }
+
+    write_stages(&mut output, s, arg).await?;
+    write_requests(&mut output, s, arg).await?;
 
     let errors = s.errors.lock().await;
     if !errors.is_empty() {
@@ -239,6 +1132,33 @@ pub(crate) async fn text_output(
             writeln!(&mut output, "    \"{k:>}\":{v:>8}")?;
         }
     }
+    let handshake_errors = s.handshake_errors.lock().await;
+    if !handshake_errors.is_empty() {
+        writeln!(&mut output, "  {:<10}", "Handshake errors:")?;
+        for (k, v) in &*handshake_errors {
+            writeln!(&mut output, "    \"{k:>}\":{v:>8}")?;
+        }
+    }
+    let generator_errors = s.generator_errors.lock().await;
+    if !generator_errors.is_empty() {
+        writeln!(&mut output, "  {:<10}", "Generator errors:")?;
+        for (k, v) in &*generator_errors {
+            writeln!(&mut output, "    \"{k:>}\":{v:>8}")?;
+        }
+    }
+    writeln!(
+        &mut output,
+        "  {:<12}{} received, {} sent",
+        "Transfer:",
+        format_bytes(s.rsp_bytes.load(Ordering::Acquire) as f64),
+        format_bytes(s.req_bytes.load(Ordering::Acquire) as f64),
+    )?;
+    writeln!(
+        &mut output,
+        "  {:<12}{:>10}/s",
+        "Transfer/sec:",
+        format_bytes(*(s.transfer_per_second.lock().await)),
+    )?;
     write!(
         &mut output,
         "  {:<12}{:>10.2}/s",
@@ -254,7 +1174,9 @@ mod tests {
     use std::time::Duration;
 
     use super::*;
-    use crate::arg::{Method, OutputFormat};
+    use crate::arg::{
+        DataMode, Method, OutputFormat, ReplayMode, SignatureEncoding,
+    };
 
     #[test]
     fn test_micros_convert() {
@@ -300,11 +1222,17 @@ mod tests {
             timeout: Duration::from_secs(30),
             latencies: false,
             percentiles: vec![],
+            precision: 3,
             method: Method::Get,
             disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
             headers: vec![],
             duration: None,
             rate: None,
+            open_model: false,
+            stages: vec![],
             cert: None,
             key: None,
             insecure: false,
@@ -313,9 +1241,49 @@ mod tests {
             json_file: None,
             json_body: None,
             json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
             form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
             mp: vec![],
             mp_file: vec![],
+            rebuild_body: false,
             output_format: OutputFormat::Text,
             completions: None,
         };
@@ -324,6 +1292,13 @@ mod tests {
         assert!(result.is_ok());
         let output = result.unwrap();
         assert!(output.contains("Statistics"));
+        let header = output.lines().next().unwrap();
+        assert_eq!(
+            header.split_whitespace().collect::<Vec<_>>(),
+            ["Statistics", "Avg", "Stdev", "Min", "Max"]
+        );
+        assert!(output.contains("Transfer:"));
+        assert!(output.contains("Transfer/sec:"));
     }
 
     #[test]
@@ -336,11 +1311,17 @@ mod tests {
             timeout: Duration::from_secs(30),
             latencies: true,
             percentiles: vec![0.5, 0.9],
+            precision: 3,
             method: Method::Get,
             disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
             headers: vec![],
             duration: None,
             rate: None,
+            open_model: false,
+            stages: vec![],
             cert: None,
             key: None,
             insecure: false,
@@ -349,9 +1330,49 @@ mod tests {
             json_file: None,
             json_body: None,
             json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
             form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
             mp: vec![],
             mp_file: vec![],
+            rebuild_body: false,
             output_format: OutputFormat::Text,
             completions: None,
         };
@@ -377,11 +1398,14 @@ mod tests {
         let output = Output {
             avg_req_per_second: 100.0,
             stdev_per_second: 10.0,
+            min_req_per_second: 50.0,
             max_req_per_second: 150.0,
             avg_req_used_time: Micros(1000),
             stdev_req_used_time: Micros(200),
+            min_req_used_time: Micros(100),
             max_req_used_time: Micros(5000),
             latencies: vec![Latency::new(0.5, Micros(1500))],
+            histogram: String::new(),
             rsp1xx: 0,
             rsp2xx: 100,
             rsp3xx: 0,
@@ -389,7 +1413,24 @@ mod tests {
             rsp5xx: 0,
             rsp_others: 0,
             errors: std::collections::HashMap::new(),
+            handshake_errors: std::collections::HashMap::new(),
+            protocols: std::collections::HashMap::new(),
+            generator_errors: std::collections::HashMap::new(),
+            total: 100,
+            total_failed: 0,
             throughput: 50.0,
+            total_req_bytes: 1000,
+            total_rsp_bytes: 20000,
+            transfer_per_second: 2000.0,
+            total_assertion_failed: 0,
+            assertions: vec![],
+            thresholds: vec![],
+            corrected: None,
+            stages: vec![],
+            requests: vec![],
+            websocket: None,
+            grpc: None,
+            stream: None,
         };
 
         // Test JSON serialization
@@ -403,6 +1444,429 @@ mod tests {
         assert!(deserialized.is_ok());
         let output_back = deserialized.unwrap();
         assert_eq!(output.avg_req_per_second, output_back.avg_req_per_second);
+        assert!(!json_str.contains("corrected"));
+        assert!(!json_str.contains("stages"));
+        assert!(!json_str.contains("requests"));
+        assert!(!json_str.contains("\"assertions\""));
+        assert!(!json_str.contains("websocket"));
+        assert!(!json_str.contains("grpc"));
+        assert!(!json_str.contains("stream"));
+    }
+
+    #[test]
+    fn test_output_histogram() {
+        use hdrhistogram::serialization::Deserializer;
+
+        let stats = Statistics::new();
+        {
+            let mut used_time = stats.used_time.blocking_lock();
+            used_time.record(1000).unwrap();
+            used_time.record(2000).unwrap();
+        }
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert!(!output.histogram.is_empty());
+
+        // decode and merge with another histogram
+        let buf = STANDARD.decode(output.histogram).unwrap();
+        let decoded: Histogram<u64> = Deserializer::new()
+            .deserialize(&mut buf.as_slice())
+            .unwrap();
+        let mut merged = crate::statistics::new_histogram(3);
+        merged.record(3000).unwrap();
+        merged.add(&decoded).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert!(merged.equivalent(merged.max(), 3000));
+    }
+
+    #[test]
+    fn test_output_with_assertions() {
+        use crate::assertion::parse_assertion;
+
+        let stats = Statistics::new().with_assertions(vec![
+            parse_assertion("status=200").unwrap(),
+            parse_assertion("latency<1s").unwrap(),
+        ]);
+        stats.assertion_failures[0].fetch_add(3, Ordering::SeqCst);
+        stats.total_assertion_failed.fetch_add(3, Ordering::SeqCst);
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert_eq!(output.total_assertion_failed, 3);
+        assert_eq!(output.assertions.len(), 2);
+        assert_eq!(output.assertions[0].rule, "status=200");
+        assert_eq!(output.assertions[0].failed, 3);
+        assert_eq!(output.assertions[1].failed, 0);
+
+        let arg = <Arg as clap::Parser>::try_parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "http://example.com",
+        ])
+        .unwrap();
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Assertions failed:"));
+        assert!(output.contains("\"status=200\":       3"));
+    }
+
+    #[test]
+    fn test_output_with_protocols() {
+        let stats = Statistics::new();
+        stats
+            .protocols
+            .try_lock()
+            .unwrap()
+            .extend([("HTTP/2.0".to_string(), 8), ("HTTP/1.1".to_string(), 2)]);
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert_eq!(output.protocols["HTTP/2.0"], 8);
+
+        let arg = <Arg as clap::Parser>::try_parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "http://example.com",
+        ])
+        .unwrap();
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Protocols:"));
+        assert!(output.contains("    HTTP/1.1 - 2, HTTP/2.0 - 8\n"));
+    }
+
+    #[test]
+    fn test_output_with_websocket() {
+        let stats = Statistics::new().with_websocket();
+        let websocket = stats.websocket.as_ref().unwrap();
+        websocket
+            .close_codes
+            .try_lock()
+            .unwrap()
+            .extend([(1006, 1), (1000, 4)]);
+        websocket.unmatched.fetch_add(3, Ordering::SeqCst);
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        let websocket = output.websocket.unwrap();
+        assert_eq!(websocket.close_codes[&1000], 4);
+        assert_eq!(websocket.unmatched, 3);
+
+        let arg = <Arg as clap::Parser>::try_parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "ws://example.com",
+        ])
+        .unwrap();
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Msgs/sec"));
+        assert!(output.contains("Connect"));
+        assert!(!output.contains("HTTP codes:"));
+        assert!(output.contains("    1000 - 4, 1006 - 1\n"));
+        assert!(output.contains("Unmatched messages: 3"));
+    }
+
+    #[test]
+    fn test_output_with_grpc() {
+        let stats = Statistics::new().with_grpc();
+        let grpc = stats.grpc.as_ref().unwrap();
+        grpc.codes[14].fetch_add(2, Ordering::SeqCst);
+        grpc.others.fetch_add(1, Ordering::SeqCst);
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        let grpc = output.grpc.unwrap();
+        assert_eq!(grpc.codes, HashMap::from([("UNAVAILABLE".into(), 2)]));
+        assert_eq!(grpc.total(), 3);
+
+        let arg = <Arg as clap::Parser>::try_parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "http://example.com",
+        ])
+        .unwrap();
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(!output.contains("HTTP codes:"));
+        assert!(output.contains("gRPC codes:"));
+        assert!(
+            output.contains("    OK - 0, UNAVAILABLE - 2\n    others - 1\n")
+        );
+    }
+
+    #[test]
+    fn test_write_thresholds() {
+        let mut output = String::new();
+        write_thresholds(&mut output, &[]).unwrap();
+        assert!(output.is_empty());
+
+        let thresholds = vec![
+            ThresholdOutput {
+                threshold: "p99<250ms".to_string(),
+                actual: Some("12.00ms".to_string()),
+                passed: true,
+            },
+            ThresholdOutput {
+                threshold: "error_rate<1%".to_string(),
+                actual: None,
+                passed: false,
+            },
+        ];
+        write_thresholds(&mut output, &thresholds).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1].trim(), "Thresholds:");
+        assert!(lines[2].contains("passed  p99<250ms"));
+        assert!(lines[2].ends_with("12.00ms"));
+        assert!(lines[3].contains("FAILED  error_rate<1%"));
+        assert!(lines[3].ends_with("unknown"));
+    }
+
+    #[test]
+    fn test_output_with_stages() {
+        let stats = Statistics::new();
+        stats
+            .stages
+            .blocking_lock()
+            .resize_with(2, || GroupStatistics::new(3));
+        let mut arg = Arg {
+            url: Some("http://example.com".to_string()),
+            requests: None,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: true,
+            percentiles: vec![0.5],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        arg.stages.push(crate::arg::Stage {
+            duration: Duration::from_secs(30),
+            rate: 100,
+            connections: None,
+        });
+
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Stages:"));
+        assert!(output.contains("30s:100rps"));
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert_eq!(output.stages.len(), 2);
+        assert_eq!(output.stages[1].stage, 1);
+    }
+
+    #[test]
+    fn test_output_with_requests() {
+        let stats = Statistics::new()
+            .with_requests(vec!["list items".to_string(), "order".to_string()]);
+        let arg = <Arg as clap::Parser>::try_parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "-l",
+            "http://example.com",
+        ])
+        .unwrap();
+
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Requests:"));
+        assert!(output.contains("list items"));
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert_eq!(output.requests.len(), 2);
+        assert_eq!(output.requests[1].name, "order");
+
+        // the statistics are flattened into the request
+        let json = serde_json::to_value(&output.requests[0]).unwrap();
+        assert_eq!(json["name"], "list items");
+        assert_eq!(json["total"], 0);
+    }
+
+    #[test]
+    fn test_sync_text_output_with_corrected_latencies() {
+        let stats = Statistics::new();
+        stats.corrected_total.fetch_add(1, Ordering::SeqCst);
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            requests: Some(10),
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: true,
+            percentiles: vec![0.5],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            duration: None,
+            rate: Some(10),
+            open_model: true,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        stats
+            .corrected_latencies
+            .blocking_lock()
+            .push((0.5, Duration::from_millis(1)));
+
+        let output = sync_text_output(&stats, &arg).unwrap();
+        assert!(output.contains("Corrected"));
+        assert!(output.contains("Corrected Latency Distribution"));
+
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert_eq!(output.corrected.unwrap().latencies.len(), 1);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0.0), "0.00B");
+        assert_eq!(format_bytes(512.0), "512.00B");
+        assert_eq!(format_bytes(1536.0), "1.50KB");
+        assert_eq!(format_bytes(3.0 * 1024.0 * 1024.0), "3.00MB");
+        assert_eq!(format_bytes(1024f64.powi(5)), "1024.00TB");
+    }
+
+    #[test]
+    fn test_format_percent() {
+        assert_eq!(format_percent(0.5), "50%");
+        assert_eq!(format_percent(0.75), "75%");
+        assert_eq!(format_percent(0.999), "99.9%");
+        assert_eq!(format_percent(0.9999), "99.99%");
+        assert_eq!(format_percent(0.99999), "99.999%");
+    }
+
+    #[test]
+    fn test_write_latency_distribution() {
+        let mut output = String::new();
+        write_latency_distribution(
+            &mut output,
+            "Latency Distribution",
+            &[
+                (0.5, Duration::from_millis(2)),
+                (0.999, Duration::from_millis(9)),
+            ],
+            Duration::from_millis(1),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        let rows: Vec<&str> = output.lines().map(|x| x.trim()).collect();
+        assert_eq!(rows[0], "Latency Distribution");
+        assert!(rows[1].starts_with("min") && rows[1].ends_with("1.00ms"));
+        assert!(rows[2].starts_with("50%") && rows[2].ends_with("2.00ms"));
+        assert!(rows[3].starts_with("99.9%") && rows[3].ends_with("9.00ms"));
+        assert!(rows[4].starts_with("max") && rows[4].ends_with("10.00ms"));
     }
 
     #[test]
@@ -1,51 +1,313 @@
 use std::collections::HashMap;
 
 use anyhow::anyhow;
-use async_process::{Command, Stdio};
-use bytes::Bytes;
-use reqwest::{Body, Client, Request, RequestBuilder, multipart};
+use bytes::{Bytes, BytesMut};
+use reqwest::{
+    Body, Client, Request, RequestBuilder, Response,
+    header::{HeaderMap, HeaderValue},
+    multipart,
+};
 use tokio::{self, fs as tfs};
-use tokio_util::codec::{BytesCodec, FramedRead};
 
 use crate::Arg;
+use crate::generator::{Generator, JsonCommand};
+use crate::scenario::ScenarioRequest;
+use crate::template::{Context, Templates};
 
-pub(crate) async fn build_request(
+/// the content type of the json bodies
+const JSON_CONTENT_TYPE: &str = "application/json; charset=UTF-8";
+
+/// [PreparedRequest] keeps what is read once for the request given on the
+/// command line, the files and the output of `--json-command` are shared by
+/// all requests, and the whole request is built once and cloned for each
+/// request when it has no placeholders
+///
+/// The request is built by one of the clients and sent by all of them, which
+/// holds as long as they are built from the same arguments, a built request
+/// carries nothing of its client, the default headers and the connection
+/// settings are applied when it's sent
+#[derive(Debug, Default)]
+pub(crate) struct PreparedRequest {
+    /// the content of `--text-file` or `--json-file`, or the output of
+    /// `--json-command`
+    body: Option<Bytes>,
+
+    /// the contents of `--mp-file`, in the same order
+    mp_files: Vec<Bytes>,
+
+    /// the whole request, if nothing is rendered for each request
+    request: Option<Request>,
+
+    /// the generator of `--json-generator`, which gives a new body for each
+    /// request
+    generator: Option<Generator>,
+}
+
+impl PreparedRequest {
+    /// read the files and run the command once, nothing is kept with
+    /// `--rebuild-body`, they are read again for each request
+    pub(crate) async fn new(
+        arg: &Arg,
+        templates: &Templates,
+        client: &Client,
+    ) -> anyhow::Result<Self> {
+        if arg.rebuild_body {
+            return Ok(Self::default());
+        }
+        if arg.json_generator
+            && let Some(command) = &arg.json_command
+        {
+            let command =
+                JsonCommand::parse(command, arg.json_command_timeout)?;
+            return Ok(Self {
+                generator: Some(command.spawn()?),
+                ..Default::default()
+            });
+        }
+        let mut prepared = Self::load(arg).await?;
+        if templates.is_literal() {
+            let request = build_request(
+                arg,
+                templates,
+                &Context::default(),
+                client,
+                &prepared,
+            )
+            .await?;
+            // a streaming body, like multipart, can't be cloned
+            prepared.request = request.try_clone().is_some().then_some(request);
+        }
+        Ok(prepared)
+    }
+
+    /// read the bodies given by the arguments
+    async fn load(arg: &Arg) -> anyhow::Result<Self> {
+        let body = match (&arg.text_file, &arg.json_file, &arg.json_command) {
+            (Some(path), _, _) | (None, Some(path), _) => {
+                Some(Bytes::from(tfs::read(path).await?))
+            },
+            (None, None, Some(command)) => Some(
+                JsonCommand::parse(command, arg.json_command_timeout)?
+                    .run()
+                    .await?,
+            ),
+            (None, None, None) => None,
+        };
+        let mut mp_files = vec![];
+        for (_, path) in &arg.mp_file {
+            mp_files.push(Bytes::from(tfs::read(path).await?));
+        }
+        Ok(Self {
+            body,
+            mp_files,
+            ..Default::default()
+        })
+    }
+
+    /// build the request, or clone the one built once, the failure to generate
+    /// the body is a [GeneratorError]
+    pub(crate) async fn build(
+        &self,
+        arg: &Arg,
+        templates: &Templates,
+        context: &Context<'_>,
+        client: &Client,
+    ) -> anyhow::Result<Request> {
+        let cached = self.request.as_ref().and_then(Request::try_clone);
+        let request = match (cached, &self.generator) {
+            (Some(request), _) => request,
+            (None, Some(generator)) => {
+                let prepared = Self {
+                    body: Some(generator.next().await?),
+                    ..Default::default()
+                };
+                build_request(arg, templates, context, client, &prepared)
+                    .await?
+            },
+            (None, None) if arg.rebuild_body => {
+                let prepared = Self::load(arg).await?;
+                build_request(arg, templates, context, client, &prepared)
+                    .await?
+            },
+            (None, None) => {
+                build_request(arg, templates, context, client, self).await?
+            },
+        };
+        Ok(request)
+    }
+}
+
+async fn build_request(
     arg: &Arg,
+    templates: &Templates,
+    context: &Context<'_>,
     client: &Client,
+    prepared: &PreparedRequest,
 ) -> anyhow::Result<Request> {
-    let mut builder = client.request(
-        arg.method.to_reqwest_method(),
-        arg.url.as_ref().unwrap().clone(),
-    );
+    let url = templates.url.as_ref().ok_or(anyhow!("missing url"))?;
+    let mut builder =
+        client.request(arg.method.to_reqwest_method(), url.render(context));
 
     // the following four types are mutually exclusive
     // only one will take effect
-    builder = set_request_text_body(arg, builder).await?;
-    builder = set_request_form_body(arg, builder).await?;
-    builder = set_request_json_body(arg, builder).await?;
-    builder = set_request_multipart_body(arg, builder).await?;
-
-    match builder.build() {
-        Ok(client) => Ok(client),
-        Err(e) => Err(Box::new(e).into()),
+    builder = set_request_text_body(arg, templates, context, prepared, builder)
+        .await?;
+    builder = set_request_form_body(templates, context, builder).await?;
+    builder = set_request_json_body(arg, templates, context, prepared, builder)
+        .await?;
+    builder =
+        set_request_multipart_body(arg, templates, context, prepared, builder)
+            .await?;
+
+    let mut request = match builder.build() {
+        Ok(request) => request,
+        Err(e) => return Err(Box::new(e).into()),
+    };
+    // the templated headers take precedence over the ones set by the body
+    set_headers(&mut request, templates, context)?;
+    Ok(request)
+}
+
+/// build the named request of the scenario, its headers take precedence over
+/// the ones set by the body
+pub(crate) fn build_scenario_request(
+    request: &ScenarioRequest,
+    templates: &Templates,
+    context: &Context<'_>,
+    client: &Client,
+) -> anyhow::Result<Request> {
+    let url = templates.url.as_ref().ok_or(anyhow!("missing url"))?;
+    let mut builder =
+        client.request(request.method.to_reqwest_method(), url.render(context));
+    if let Some(body) = &templates.text_body {
+        builder = builder
+            .body(body.render(context))
+            .header("Content-Type", "text/plain; charset=UTF-8");
     }
+    if let Some(json) = &templates.json_body {
+        builder = builder
+            .body(json.render(context))
+            .header("Content-Type", JSON_CONTENT_TYPE);
+    }
+    if request.form.is_some() {
+        let form: Vec<(&str, String)> = templates
+            .form
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.render(context)))
+            .collect();
+        builder = builder.form(&form);
+    }
+
+    let mut built = builder.build()?;
+    set_headers(&mut built, templates, context)?;
+    Ok(built)
+}
+
+/// render the headers of the templates into the request, replacing the ones
+/// of the same names, a header given several times keeps all its values
+fn set_headers(
+    request: &mut Request,
+    templates: &Templates,
+    context: &Context<'_>,
+) -> anyhow::Result<()> {
+    let headers = request.headers_mut();
+    for (name, _) in &templates.headers {
+        headers.remove(name);
+    }
+    for (name, value) in &templates.headers {
+        headers.append(
+            name.clone(),
+            HeaderValue::from_str(&value.render(context))?,
+        );
+    }
+    Ok(())
+}
+
+/// the size of the headers when they are serialized in HTTP/1.1
+fn headers_size(headers: &HeaderMap) -> u64 {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().len() + value.len() + 4) as u64)
+        .sum::<u64>()
+        + 2
+}
+
+/// approximate number of bytes of the request, including request line,
+/// headers and body, the body is only counted when it's in memory, the
+/// default headers of the client are not included
+pub(crate) fn request_size(request: &Request) -> u64 {
+    let request_line = format!(
+        "{} {} {:?}\r\n",
+        request.method(),
+        request.url().path(),
+        request.version()
+    );
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map_or(0, |body| body.len());
+    request_line.len() as u64 + headers_size(request.headers()) + body as u64
+}
+
+/// approximate number of bytes of the status line and headers of the response
+pub(crate) fn response_head_size(response: &Response) -> u64 {
+    let status_line =
+        format!("{:?} {}\r\n", response.version(), response.status());
+    status_line.len() as u64 + headers_size(response.headers())
+}
+
+/// read the whole response body, return the approximate number of bytes of the
+/// response, including status line, headers and body, and the body itself if
+/// `keep_body` is true, which forces the body to be read
+pub(crate) async fn drain_response(
+    response: &mut Response,
+    read_body: bool,
+    keep_body: bool,
+) -> reqwest::Result<(u64, Option<Bytes>)> {
+    let mut size = response_head_size(response);
+    let mut body = keep_body.then(BytesMut::new);
+    if read_body || keep_body {
+        while let Some(chunk) = response.chunk().await? {
+            size += chunk.len() as u64;
+            if let Some(body) = body.as_mut() {
+                body.extend_from_slice(&chunk);
+            }
+        }
+    }
+    Ok((size, body.map(BytesMut::freeze)))
+}
+
+/// whether `Content-Type` is given by `-H`
+fn has_content_type(arg: &Arg) -> bool {
+    arg.headers
+        .iter()
+        .filter_map(|header| header.split_once(':'))
+        .any(|(name, _)| name.trim().eq_ignore_ascii_case("content-type"))
 }
 
 async fn set_request_text_body(
     arg: &Arg,
+    templates: &Templates,
+    context: &Context<'_>,
+    prepared: &PreparedRequest,
     mut builder: RequestBuilder,
 ) -> anyhow::Result<RequestBuilder> {
-    if let Some(text_body) = &arg.text_body {
-        builder = builder
-            .body(Bytes::from(text_body.clone()))
-            .header("Content-Type", "text/plain; charset=UTF-8");
+    if let Some(text_body) = &templates.text_body {
+        builder = builder.body(Bytes::from(text_body.render(context)));
     }
 
-    if let Some(text_file) = &arg.text_file {
-        let file = tfs::File::open(text_file).await?;
-        builder = builder
-            .body(file)
-            .header("Content-Type", "text/plain; charset=UTF-8");
+    if arg.text_file.is_some()
+        && let Some(body) = &prepared.body
+    {
+        builder = builder.body(body.clone());
+    }
+
+    // text can be any format, such as xml or csv, so the given content type
+    // is kept
+    if (arg.text_body.is_some() || arg.text_file.is_some())
+        && !has_content_type(arg)
+    {
+        builder = builder.header("Content-Type", "text/plain; charset=UTF-8");
     }
 
     Ok(builder)
@@ -53,61 +315,38 @@ async fn set_request_text_body(
 
 async fn set_request_json_body(
     arg: &Arg,
+    templates: &Templates,
+    context: &Context<'_>,
+    prepared: &PreparedRequest,
     mut builder: RequestBuilder,
 ) -> anyhow::Result<RequestBuilder> {
-    if let Some(json_body) = &arg.json_body {
+    if let Some(json_body) = &templates.json_body {
         builder = builder
-            .body(Bytes::from(json_body.clone()))
-            .header("Content-Type", "application/json; charset=UTF-8");
+            .body(Bytes::from(json_body.render(context)))
+            .header("Content-Type", JSON_CONTENT_TYPE);
     }
 
-    if let Some(json_file) = &arg.json_file {
-        let file = tfs::File::open(json_file).await?;
-        builder = builder
-            .body(file)
-            .header("Content-Type", "application/json; charset=UTF-8");
-    }
-
-    if let Some(json_command) = &arg.json_command {
-        let inputs: Vec<&str> = json_command.split(" ").collect();
-        let inputs: Vec<&&str> =
-            inputs.iter().filter(|&&x| !x.is_empty()).collect();
-
-        if inputs.is_empty() {
-            anyhow::bail!(anyhow!("invalid json command"))
-        }
-
-        let mut cmd = Command::new(inputs[0]);
-        cmd.stdin(Stdio::null());
-        for arg in &inputs[1..] {
-            cmd.arg(arg);
-        }
-        let output = cmd.output().await?;
-        if !output.status.success() {
-            anyhow::bail!(anyhow!(format!(
-                "external command execute failed, exit code: {}",
-                output.status
-            )))
-        }
+    // the file and the output of the command
+    if (arg.json_file.is_some() || arg.json_command.is_some())
+        && let Some(body) = &prepared.body
+    {
         builder = builder
-            .body(output.stdout)
-            .header("Content-Type", "application/json; charset=UTF-8");
+            .body(body.clone())
+            .header("Content-Type", JSON_CONTENT_TYPE);
     }
 
     Ok(builder)
 }
 
 async fn set_request_form_body(
-    arg: &Arg,
+    templates: &Templates,
+    context: &Context<'_>,
     mut builder: RequestBuilder,
 ) -> anyhow::Result<RequestBuilder> {
-    if !arg.form.is_empty() {
+    if !templates.form.is_empty() {
         let mut params = HashMap::new();
-        for kv in &arg.form {
-            let parts = kv.trim().split_once(':');
-            if let Some(v) = parts {
-                params.insert(v.0, v.1);
-            }
+        for (k, v) in &templates.form {
+            params.insert(k.as_str(), v.render(context));
         }
         builder = builder.form(&params);
     }
@@ -117,25 +356,21 @@ async fn set_request_form_body(
 
 async fn set_request_multipart_body(
     arg: &Arg,
+    templates: &Templates,
+    context: &Context<'_>,
+    prepared: &PreparedRequest,
     mut builder: RequestBuilder,
 ) -> anyhow::Result<RequestBuilder> {
-    if !arg.mp.is_empty() || !arg.mp_file.is_empty() {
+    if !templates.mp.is_empty() || !arg.mp_file.is_empty() {
         let mut form = multipart::Form::new();
-        for kv in &arg.mp {
-            let parts = kv.trim().split_once(':');
-            if let Some(parts) = parts {
-                let k = parts.0.to_string().clone().to_owned();
-                let v = parts.1.to_string().clone().to_owned();
-                form = form.text(k, v);
-            }
+        for (k, v) in &templates.mp {
+            form = form.text(k.clone(), v.render(context));
         }
 
         // for uploading file
-        for parts in &arg.mp_file {
+        for (parts, content) in arg.mp_file.iter().zip(&prepared.mp_files) {
             let (filename, filepath) = parts;
-            let file = tfs::File::open(&filepath).await?;
-            let stream = FramedRead::new(file, BytesCodec::new());
-            let file_body = Body::wrap_stream(stream);
+            let file_body = Body::from(content.clone());
 
             // get file mime information
             let mime = mime_guess::from_path(filepath);
@@ -163,41 +398,99 @@ async fn set_request_multipart_body(
 mod tests {
     use std::time::Duration;
 
+    use clap::Parser;
+
     use super::*;
-    use crate::arg::{Method, OutputFormat};
+    use crate::arg::{
+        DataMode, Method, OutputFormat, ReplayMode, SignatureEncoding,
+    };
+    use crate::test_util::write_temp;
+
+    const URL: &str = "http://a.com/";
 
     #[tokio::test]
     async fn test_set_request_text_body_with_body() {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            text_body: Some("test body".to_string()),
+            text_file: None,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_text_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                text_body: Some("test body".to_string()),
-                text_file: None,
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                form: vec![],
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
@@ -209,33 +502,84 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            text_body: None,
+            text_file: None,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_text_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                text_body: None,
-                text_file: None,
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                form: vec![],
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
@@ -247,33 +591,84 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            json_body: Some("{\"key\":\"value\"}".to_string()),
+            json_file: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_json_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                json_body: Some("{\"key\":\"value\"}".to_string()),
-                json_file: None,
-                json_command: None,
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                form: vec![],
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
@@ -285,33 +680,84 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            json_body: None,
+            json_file: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_json_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                json_body: None,
-                json_file: None,
-                json_command: None,
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                form: vec![],
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
@@ -323,39 +769,82 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
-        let result = set_request_form_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                form: vec![
-                    "key1:value1".to_string(),
-                    "key2:value2".to_string(),
-                ],
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
-            builder,
-        )
-        .await;
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            form: vec!["key1:value1".to_string(), "key2:value2".to_string()],
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let result =
+            set_request_form_body(&templates, &Context::default(), builder)
+                .await;
         assert!(result.is_ok());
     }
 
@@ -364,36 +853,82 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
-        let result = set_request_form_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                form: vec![],
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                mp: vec![],
-                mp_file: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
-            builder,
-        )
-        .await;
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let result =
+            set_request_form_body(&templates, &Context::default(), builder)
+                .await;
         assert!(result.is_ok());
     }
 
@@ -402,33 +937,84 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            mp: vec!["key1:value1".to_string()],
+            mp_file: vec![],
+            rebuild_body: false,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_multipart_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                mp: vec!["key1:value1".to_string()],
-                mp_file: vec![],
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                form: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
@@ -440,36 +1026,270 @@ mod tests {
         let client = Client::new();
         let builder =
             client.request(reqwest::Method::GET, "http://example.com");
+        let arg = Arg {
+            url: Some("http://example.com".to_string()),
+            mp: vec![],
+            mp_file: vec![],
+            rebuild_body: false,
+            connections: 1,
+            timeout: Duration::from_secs(30),
+            latencies: false,
+            percentiles: vec![],
+            precision: 3,
+            method: Method::Get,
+            disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
+            headers: vec![],
+            requests: Some(10),
+            duration: None,
+            rate: None,
+            open_model: false,
+            stages: vec![],
+            cert: None,
+            key: None,
+            insecure: false,
+            text_file: None,
+            text_body: None,
+            json_file: None,
+            json_body: None,
+            json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
+            form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
+            output_format: OutputFormat::Text,
+            completions: None,
+        };
+        let templates = Templates::compile(&arg, &[]).unwrap();
         let result = set_request_multipart_body(
-            &Arg {
-                url: Some("http://example.com".to_string()),
-                mp: vec![],
-                mp_file: vec![],
-                connections: 1,
-                timeout: Duration::from_secs(30),
-                latencies: false,
-                percentiles: vec![],
-                method: Method::Get,
-                disable_keep_alive: false,
-                headers: vec![],
-                requests: Some(10),
-                duration: None,
-                rate: None,
-                cert: None,
-                key: None,
-                insecure: false,
-                text_file: None,
-                text_body: None,
-                json_file: None,
-                json_body: None,
-                json_command: None,
-                form: vec![],
-                output_format: OutputFormat::Text,
-                completions: None,
-            },
+            &arg,
+            &templates,
+            &Context::default(),
+            &PreparedRequest::default(),
             builder,
         )
         .await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_prepared_request() {
+        let path = write_temp("rsb_test_prepared.txt", "v1");
+        let path = path.to_str().unwrap();
+        let client = Client::new();
+        let body = |request: &Request| {
+            request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(Vec::from)
+        };
+
+        // the file is read once, and the request is cloned
+        let arg = Arg::parse_from(["rsb", "-n", "1", "--text-file", path, URL]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let prepared = PreparedRequest::new(&arg, &templates, &client)
+            .await
+            .unwrap();
+        assert!(prepared.request.is_some());
+        std::fs::write(path, "v2").unwrap();
+        let request = prepared
+            .build(&arg, &templates, &Context::default(), &client)
+            .await
+            .unwrap();
+        assert_eq!(body(&request).unwrap(), b"v1");
+        assert_eq!(
+            request.headers()["content-type"],
+            "text/plain; charset=UTF-8"
+        );
+
+        // the placeholders are rendered for each request
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--text-file",
+            path,
+            "http://a.com/{{seq}}",
+        ]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let prepared = PreparedRequest::new(&arg, &templates, &client)
+            .await
+            .unwrap();
+        assert!(prepared.request.is_none());
+        let context = Context {
+            seq: 7,
+            ..Default::default()
+        };
+        let request = prepared
+            .build(&arg, &templates, &context, &client)
+            .await
+            .unwrap();
+        assert_eq!(request.url().as_str(), "http://a.com/7");
+        assert_eq!(body(&request).unwrap(), b"v2");
+
+        // the file is read for each request
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--text-file",
+            path,
+            "--rebuild-body",
+            URL,
+        ]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let prepared = PreparedRequest::new(&arg, &templates, &client)
+            .await
+            .unwrap();
+        assert!(prepared.request.is_none());
+        std::fs::write(path, "v3").unwrap();
+        let request = prepared
+            .build(&arg, &templates, &Context::default(), &client)
+            .await
+            .unwrap();
+        assert_eq!(body(&request).unwrap(), b"v3");
+    }
+
+    #[test]
+    fn test_request_size() {
+        let request = Client::new()
+            .post("http://example.com/items")
+            .header("x-id", "1")
+            .body("hello")
+            .build()
+            .unwrap();
+        // "POST /items HTTP/1.1\r\n" + "x-id: 1\r\n" + "\r\n" + "hello"
+        assert_eq!(request_size(&request), 22 + 9 + 2 + 5);
+    }
+
+    #[tokio::test]
+    async fn test_build_request_templated_content_type() {
+        let client = Client::new();
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "-H",
+            "Content-Type: application/vnd.{{seq}}+json",
+            "-H",
+            "x-id: {{seq}}",
+            "-H",
+            "x-id: {{worker_id}}",
+            "--json-body",
+            "{}",
+            URL,
+        ]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let prepared = PreparedRequest::new(&arg, &templates, &client)
+            .await
+            .unwrap();
+        let context = Context {
+            worker_id: 1,
+            seq: 7,
+            values: &[],
+        };
+        let request = prepared
+            .build(&arg, &templates, &context, &client)
+            .await
+            .unwrap();
+        let content_type = request.headers().get_all("content-type");
+        assert_eq!(content_type.iter().count(), 1);
+        assert_eq!(request.headers()["content-type"], "application/vnd.7+json");
+        let ids: Vec<_> = request.headers().get_all("x-id").iter().collect();
+        assert_eq!(ids, ["7", "1"]);
+    }
+
+    #[test]
+    fn test_build_scenario_request() {
+        let request = ScenarioRequest {
+            name: "order".to_string(),
+            weight: 1,
+            method: Method::Post,
+            url: "http://example.com/orders".to_string(),
+            headers: [(
+                "content-type".to_string(),
+                "application/vnd.order+json".to_string(),
+            )]
+            .into(),
+            body: None,
+            json: Some(serde_json::json!({"item": 1})),
+            form: None,
+            extract: Default::default(),
+        };
+        let templates =
+            Templates::compile_request(&request, Some(&[])).unwrap();
+        let built = build_scenario_request(
+            &request,
+            &templates,
+            &Context::default(),
+            &Client::new(),
+        )
+        .unwrap();
+        assert_eq!(built.method(), reqwest::Method::POST);
+        assert_eq!(built.url().as_str(), "http://example.com/orders");
+        let content_type = built.headers().get_all("content-type");
+        assert_eq!(content_type.iter().count(), 1);
+        assert_eq!(
+            built.headers()["content-type"],
+            "application/vnd.order+json"
+        );
+        assert_eq!(
+            built.body().and_then(|body| body.as_bytes()),
+            Some(&b"{\"item\":1}"[..])
+        );
+
+        // the same content type as the json bodies of the command line
+        let request = ScenarioRequest {
+            headers: Default::default(),
+            ..request
+        };
+        let templates =
+            Templates::compile_request(&request, Some(&[])).unwrap();
+        let built = build_scenario_request(
+            &request,
+            &templates,
+            &Context::default(),
+            &Client::new(),
+        )
+        .unwrap();
+        assert_eq!(built.headers()["content-type"], JSON_CONTENT_TYPE);
+    }
 }
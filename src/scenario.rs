@@ -0,0 +1,718 @@
+//! mod scenario defines a mix of named requests with weights, which is loaded
+//! from a TOML, YAML or JSON file given by `--scenario`, e.g.
+//!
+//! ```toml
+//! [[requests]]
+//! name = "list items"
+//! weight = 70
+//! url = "/items"
+//!
+//! [[requests]]
+//! name = "create order"
+//! weight = 30
+//! method = "POST"
+//! url = "http://127.0.0.1:8080/orders"
+//! headers = { x-request-id = "1" }
+//! json = { item = 1, count = 2 }
+//! ```
+//!
+//! Relative urls are resolved against the url given on the command line, and
+//! one of the requests is picked at random by weight for each iteration. The
+//! url, headers and body can contain the placeholders of [crate::template].
+//!
+//! A file can also define `[[steps]]` instead of `[[requests]]`, which are
+//! sent in order as one iteration of a virtual user, such as login and then
+//! the calls using its token, the values extracted from the responses of the
+//! steps are variables of the later steps, see [crate::extract].
+//!
+//! The requests imported from a HAR file by `--har` are also replayed as a
+//! scenario, see [crate::har].
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use reqwest::Url;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Deserialize;
+
+use crate::arg::{Method, ReplayMode};
+use crate::extract::{Extract, Payload};
+use crate::template::{Templates, is_template};
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// [ScenarioRequest] is one named request of the scenario
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ScenarioRequest {
+    /// the name of the request, the statistics are grouped by it
+    pub(crate) name: String,
+
+    /// the relative frequency of the request, 1 by default
+    #[serde(default = "default_weight")]
+    pub(crate) weight: u32,
+
+    /// GET by default
+    #[serde(default)]
+    pub(crate) method: Method,
+
+    /// absolute url, or a url relative to the one on the command line
+    #[serde(default)]
+    pub(crate) url: String,
+
+    /// request headers, in addition to the ones given by `-H`
+    #[serde(default)]
+    pub(crate) headers: BTreeMap<String, String>,
+
+    /// request body for ContentType: text/plain
+    #[serde(default)]
+    pub(crate) body: Option<String>,
+
+    /// request body for ContentType: application/json
+    #[serde(default)]
+    pub(crate) json: Option<serde_json::Value>,
+
+    /// request parameters for ContentType: application/x-www-form-urlencoded
+    #[serde(default)]
+    pub(crate) form: Option<BTreeMap<String, String>>,
+
+    /// the variables taken from the response, only supported by steps
+    #[serde(default)]
+    pub(crate) extract: BTreeMap<String, Extract>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    #[serde(default)]
+    requests: Vec<ScenarioRequest>,
+
+    #[serde(default)]
+    steps: Vec<ScenarioRequest>,
+}
+
+/// [Scenario] is a weighted mix, or a sequence of named requests
+#[derive(Debug)]
+pub(crate) struct Scenario {
+    pub(crate) requests: Vec<ScenarioRequest>,
+
+    /// the compiled templates of each request
+    pub(crate) templates: Vec<Templates>,
+
+    mode: ReplayMode,
+    weights: WeightedIndex<u32>,
+
+    /// the distinct names of the requests, in the order of definition
+    names: Vec<String>,
+
+    /// the index of the name of each request in `names`
+    groups: Vec<usize>,
+
+    /// the interval before each request in the original timing
+    delays: Vec<Duration>,
+
+    /// the names of the variables extracted from the responses
+    extracted: Vec<String>,
+
+    /// the index in `extracted` and the source of the variables extracted
+    /// from the response of each request
+    extracts: Vec<Vec<(usize, Extract)>>,
+}
+
+impl Scenario {
+    /// load the scenario from the file, whose format is decided by the
+    /// extension, relative urls are resolved against `base_url`, `variables`
+    /// are the columns of `--data-file`
+    pub(crate) fn load(
+        path: &Path,
+        base_url: Option<&str>,
+        variables: &[String],
+    ) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("failed to read scenario {path:?}: {e}")
+        })?;
+        let file: ScenarioFile =
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => toml::from_str(&content)?,
+                Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+                Some("json") => serde_json::from_str(&content)?,
+                _ => anyhow::bail!(
+                    "unsupported scenario {path:?}, the extension must be one \
+                    of toml, yaml, yml and json"
+                ),
+            };
+        match (file.requests.is_empty(), file.steps.is_empty()) {
+            (true, false) => Self::steps(file.steps, base_url, variables),
+            (false, true) => Self::new(file.requests, base_url, variables),
+            (true, true) => anyhow::bail!("the scenario has no requests"),
+            (false, false) => anyhow::bail!(
+                "the scenario can't define both requests and steps"
+            ),
+        }
+    }
+
+    /// a weighted mix of the requests
+    fn new(
+        requests: Vec<ScenarioRequest>,
+        base_url: Option<&str>,
+        variables: &[String],
+    ) -> anyhow::Result<Self> {
+        if let Some(request) =
+            requests.iter().find(|request| !request.extract.is_empty())
+        {
+            anyhow::bail!(
+                "invalid request `{}`, extract is only supported by steps",
+                request.name
+            );
+        }
+        Self::build(requests, base_url, variables, ReplayMode::Weighted)
+    }
+
+    /// a sequence of steps sent in order as one iteration
+    fn steps(
+        steps: Vec<ScenarioRequest>,
+        base_url: Option<&str>,
+        variables: &[String],
+    ) -> anyhow::Result<Self> {
+        Self::build(steps, base_url, variables, ReplayMode::Ordered)
+    }
+
+    fn build(
+        mut requests: Vec<ScenarioRequest>,
+        base_url: Option<&str>,
+        variables: &[String],
+        mode: ReplayMode,
+    ) -> anyhow::Result<Self> {
+        let base_url = base_url.map(Url::parse).transpose()?;
+        let mut names = HashSet::new();
+        for request in requests.iter_mut() {
+            if !names.insert(request.name.clone()) {
+                anyhow::bail!(
+                    "the name `{}` is used by more than one request",
+                    request.name
+                );
+            }
+            request.url = resolve_url(base_url.as_ref(), &request.url)
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "invalid url `{}` of request `{}`: {e}, relative urls \
+                        require a url on the command line",
+                        request.url,
+                        request.name
+                    )
+                })?;
+        }
+        Self::replay(requests, vec![], mode, Some(variables))
+    }
+
+    /// build the scenario from requests with absolute urls, the requests
+    /// with the same name share the statistics, `delays` are the intervals
+    /// before each request, which are only kept with [ReplayMode::Timed],
+    /// the requests are templates with `variables` and the extracted ones, or
+    /// kept as is if it's None
+    pub(crate) fn replay(
+        requests: Vec<ScenarioRequest>,
+        delays: Vec<Duration>,
+        mode: ReplayMode,
+        variables: Option<&[String]>,
+    ) -> anyhow::Result<Self> {
+        if requests.is_empty() {
+            anyhow::bail!("the scenario has no requests");
+        }
+
+        // the extracted variables follow the given ones
+        let mut extracted: Vec<String> = vec![];
+        let mut extracts = vec![];
+        for request in &requests {
+            let mut sources = vec![];
+            for (name, extract) in &request.extract {
+                if variables.unwrap_or_default().contains(name) {
+                    anyhow::bail!(
+                        "the variable `{name}` extracted by `{}` is also a \
+                        column of the data file",
+                        request.name
+                    );
+                }
+                let index = match extracted.iter().position(|x| x == name) {
+                    Some(index) => index,
+                    None => {
+                        extracted.push(name.clone());
+                        extracted.len() - 1
+                    },
+                };
+                sources.push((index, extract.clone()));
+            }
+            extracts.push(sources);
+        }
+        let variables = variables
+            .map(|variables| [variables, extracted.as_slice()].concat());
+
+        let mut names: Vec<String> = vec![];
+        let mut groups = vec![];
+        let mut templates = vec![];
+        for request in &requests {
+            for (name, value) in &request.headers {
+                HeaderName::from_bytes(name.as_bytes())?;
+                if variables.is_none() || !is_template(value) {
+                    HeaderValue::from_str(value)?;
+                }
+            }
+            templates.push(
+                Templates::compile_request(request, variables.as_deref())
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "invalid request `{}`: {e}",
+                            request.name
+                        )
+                    })?,
+            );
+            match names.iter().position(|name| name == &request.name) {
+                Some(group) => groups.push(group),
+                None => {
+                    groups.push(names.len());
+                    names.push(request.name.clone());
+                },
+            }
+        }
+
+        let weights =
+            WeightedIndex::new(requests.iter().map(|request| request.weight))
+                .map_err(|e| anyhow::anyhow!("invalid weights: {e}"))?;
+        Ok(Self {
+            requests,
+            templates,
+            mode,
+            weights,
+            names,
+            groups,
+            delays,
+            extracted,
+            extracts,
+        })
+    }
+
+    /// the names of the variables extracted from the responses, which
+    /// follow the columns of the data file in the values of the templates
+    pub(crate) fn extracted(&self) -> &[String] {
+        &self.extracted
+    }
+
+    /// whether an iteration starts at the cursor, every request is an
+    /// iteration unless the requests are sent in order
+    pub(crate) fn is_iteration_start(&self, cursor: usize) -> bool {
+        self.mode == ReplayMode::Weighted
+            || cursor.is_multiple_of(self.requests.len())
+    }
+
+    /// whether any values are extracted from the response of the request
+    pub(crate) fn has_extract(&self, index: usize) -> bool {
+        !self.extracts[index].is_empty()
+    }
+
+    /// whether the body of the response is needed to extract values
+    pub(crate) fn needs_body(&self, index: usize) -> bool {
+        self.extracts[index]
+            .iter()
+            .any(|(_, extract)| extract.needs_body())
+    }
+
+    /// extract the variables from the response of the request into `values`,
+    /// returns the error of the first one which is not found
+    pub(crate) fn extract(
+        &self,
+        index: usize,
+        payload: &Payload<'_>,
+        values: &mut [String],
+    ) -> Result<(), String> {
+        for (variable, extract) in &self.extracts[index] {
+            match extract.apply(payload) {
+                Some(value) => values[*variable] = value,
+                None => {
+                    return Err(format!(
+                        "failed to extract `{}` from the response of `{}`",
+                        self.extracted[*variable], self.requests[index].name
+                    ));
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// the distinct names of the requests, in the order of definition
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// the index of the name of the request in [Scenario::names]
+    pub(crate) fn group(&self, index: usize) -> usize {
+        self.groups[index]
+    }
+
+    /// pick the index of the next request, at random by weight, or the next
+    /// one in order, `cursor` is the position of the connection in the
+    /// sequence
+    pub(crate) fn pick(&self, cursor: &mut usize) -> usize {
+        match self.mode {
+            ReplayMode::Weighted => {
+                self.weights.sample(&mut rand::thread_rng())
+            },
+            ReplayMode::Ordered | ReplayMode::Timed => {
+                let index = *cursor % self.requests.len();
+                *cursor = index + 1;
+                index
+            },
+        }
+    }
+
+    /// how long to wait before sending the next request at the cursor to keep
+    /// the original timing, zero unless it's replayed with
+    /// [ReplayMode::Timed]
+    pub(crate) fn delay(&self, cursor: usize) -> Duration {
+        match self.mode {
+            ReplayMode::Timed => self
+                .delays
+                .get(cursor % self.requests.len())
+                .copied()
+                .unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// resolve the url against the base url, the placeholders are kept as is
+/// instead of being percent-encoded
+fn resolve_url(base_url: Option<&Url>, url: &str) -> anyhow::Result<String> {
+    let mut placeholders = vec![];
+    let mut protected = String::new();
+    let mut rest = url;
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start..].find("}}")
+    {
+        protected.push_str(&rest[..start]);
+        protected.push_str(&format!("rsb-placeholder-{}", placeholders.len()));
+        placeholders.push(&rest[start..start + end + 2]);
+        rest = &rest[start + end + 2..];
+    }
+    protected.push_str(rest);
+
+    let mut resolved = match base_url {
+        Some(base_url) => base_url.join(&protected)?,
+        None => Url::parse(&protected)?,
+    }
+    .to_string();
+    // replace the last ones first, so that `-1` isn't replaced by `-10`
+    for (index, placeholder) in placeholders.iter().enumerate().rev() {
+        resolved =
+            resolved.replace(&format!("rsb-placeholder-{index}"), placeholder);
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::template::Context;
+    use crate::test_util::write_temp;
+
+    fn request(name: &str, weight: u32, url: &str) -> ScenarioRequest {
+        ScenarioRequest {
+            name: name.to_string(),
+            weight,
+            method: Method::Get,
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+            body: None,
+            json: None,
+            form: None,
+            extract: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_scenario_load_toml() {
+        let path = write_temp(
+            "rsb_test_scenario.toml",
+            r#"
+            [[requests]]
+            name = "list"
+            weight = 7
+            url = "/items?page=1"
+
+            [[requests]]
+            name = "order"
+            method = "post"
+            url = "http://localhost:9000/orders"
+            headers = { x-id = "1" }
+            json = { item = 1 }
+            "#,
+        );
+        let scenario =
+            Scenario::load(&path, Some("http://example.com/api/"), &[])
+                .unwrap();
+        assert_eq!(scenario.names(), vec!["list", "order"]);
+
+        let list = &scenario.requests[0];
+        assert_eq!(list.weight, 7);
+        assert_eq!(list.method, Method::Get);
+        assert_eq!(list.url, "http://example.com/items?page=1");
+
+        let order = &scenario.requests[1];
+        assert_eq!(order.weight, 1);
+        assert_eq!(order.method, Method::Post);
+        assert_eq!(order.url, "http://localhost:9000/orders");
+        assert_eq!(order.headers["x-id"], "1");
+        assert_eq!(order.json, Some(serde_json::json!({"item": 1})));
+    }
+
+    #[test]
+    fn test_scenario_load_yaml_and_json() {
+        let path = write_temp(
+            "rsb_test_scenario.yaml",
+            "requests:\n  - name: a\n    url: items\n    form:\n      k: v\n",
+        );
+        let scenario =
+            Scenario::load(&path, Some("http://example.com/api/"), &[])
+                .unwrap();
+        assert_eq!(scenario.requests[0].url, "http://example.com/api/items");
+        assert_eq!(scenario.requests[0].form.as_ref().unwrap()["k"], "v");
+
+        let path = write_temp(
+            "rsb_test_scenario.json",
+            r#"{"requests": [{"name": "a", "url": "http://a.com", "body": "x"}]}"#,
+        );
+        let scenario = Scenario::load(&path, None, &[]).unwrap();
+        assert_eq!(scenario.requests[0].body.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn test_scenario_load_invalid() {
+        let path = write_temp("rsb_test_scenario.txt", "");
+        assert!(Scenario::load(&path, None, &[]).is_err());
+
+        let path = write_temp(
+            "rsb_test_scenario_unknown.json",
+            r#"{"requests": [{"name": "a", "unknown": 1}]}"#,
+        );
+        assert!(Scenario::load(&path, Some("http://a.com"), &[]).is_err());
+
+        let missing = env::temp_dir().join("rsb_test_scenario_missing.json");
+        assert!(Scenario::load(&missing, None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_scenario_new_invalid() {
+        assert!(Scenario::new(vec![], Some("http://a.com"), &[]).is_err());
+        assert!(Scenario::new(vec![request("a", 1, "/a")], None, &[]).is_err());
+        assert!(
+            Scenario::new(
+                vec![request("a", 1, "/a"), request("a", 1, "/b")],
+                Some("http://a.com"),
+                &[]
+            )
+            .is_err()
+        );
+        assert!(
+            Scenario::new(
+                vec![request("a", 0, "/a")],
+                Some("http://a.com"),
+                &[]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_scenario_pick_by_weight() {
+        let scenario = Scenario::new(
+            vec![
+                request("a", 7, "/a"),
+                request("b", 3, "/b"),
+                request("c", 0, "/c"),
+            ],
+            Some("http://a.com"),
+            &[],
+        )
+        .unwrap();
+        let mut counts = [0; 3];
+        for _ in 0..10000 {
+            counts[scenario.pick(&mut 0)] += 1;
+        }
+        assert_eq!(counts[2], 0);
+        assert!(counts[0] > 6500 && counts[0] < 7500, "{counts:?}");
+    }
+
+    #[test]
+    fn test_scenario_replay() {
+        let requests = vec![
+            request("GET /items/{id}", 1, "http://a.com/items/1"),
+            request("GET /", 1, "http://a.com/"),
+            request("GET /items/{id}", 1, "http://a.com/items/2"),
+        ];
+        let delays = vec![
+            Duration::ZERO,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ];
+        let scenario = Scenario::replay(
+            requests.clone(),
+            delays.clone(),
+            ReplayMode::Timed,
+            None,
+        )
+        .unwrap();
+        assert_eq!(scenario.names(), vec!["GET /items/{id}", "GET /"]);
+        assert_eq!(scenario.group(2), 0);
+
+        let mut cursor = 0;
+        let indexes: Vec<usize> =
+            (0..4).map(|_| scenario.pick(&mut cursor)).collect();
+        assert_eq!(indexes, [0, 1, 2, 0]);
+        assert_eq!(scenario.delay(2), Duration::from_millis(20));
+        assert_eq!(scenario.delay(4), Duration::from_millis(10));
+
+        let scenario =
+            Scenario::replay(requests, delays, ReplayMode::Ordered, None)
+                .unwrap();
+        assert_eq!(scenario.delay(2), Duration::ZERO);
+        assert!(
+            Scenario::replay(vec![], vec![], ReplayMode::Ordered, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_scenario_templates() {
+        let mut order =
+            request("order", 1, "users/{{user_id}}/orders?id={{seq}}");
+        order
+            .headers
+            .insert("x-id".to_string(), "{{uuid}}".to_string());
+        order.json = Some(serde_json::json!({"user": "{{user_id}}"}));
+        let variables = vec!["user_id".to_string()];
+        let scenario =
+            Scenario::new(vec![order], Some("http://a.com/api/"), &variables)
+                .unwrap();
+        assert_eq!(
+            scenario.requests[0].url,
+            "http://a.com/api/users/{{user_id}}/orders?id={{seq}}"
+        );
+
+        let values = vec!["7".to_string()];
+        let context = Context {
+            worker_id: 0,
+            seq: 3,
+            values: &values,
+        };
+        let templates = &scenario.templates[0];
+        assert_eq!(
+            templates.url.as_ref().unwrap().render(&context),
+            "http://a.com/api/users/7/orders?id=3"
+        );
+        assert_eq!(
+            templates.json_body.as_ref().unwrap().render(&context),
+            "{\"user\":\"7\"}"
+        );
+        assert_eq!(templates.headers[0].1.render(&context).len(), 36);
+
+        let unknown = request("a", 1, "/{{unknown}}");
+        assert!(
+            Scenario::new(vec![unknown], Some("http://a.com"), &[]).is_err()
+        );
+
+        // the requests of har are kept as is
+        let literal = request("a", 1, "http://a.com/{{unknown}}");
+        let scenario =
+            Scenario::replay(vec![literal], vec![], ReplayMode::Ordered, None)
+                .unwrap();
+        assert_eq!(
+            scenario.templates[0].url.as_ref().unwrap().render(&context),
+            "http://a.com/{{unknown}}"
+        );
+    }
+
+    #[test]
+    fn test_scenario_load_steps() {
+        let path = write_temp(
+            "rsb_test_scenario_steps.yaml",
+            r#"
+            steps:
+              - name: login
+                method: POST
+                url: /login
+                json: { user: "{{user}}" }
+                extract:
+                  token: { json: "$.token" }
+                  sid: { header: x-sid }
+              - name: profile
+                url: /me
+                headers: { authorization: "Bearer {{token}}" }
+            "#,
+        );
+        let variables = vec!["user".to_string()];
+        let scenario =
+            Scenario::load(&path, Some("http://a.com"), &variables).unwrap();
+        assert_eq!(scenario.names(), vec!["login", "profile"]);
+        assert_eq!(scenario.extracted(), ["sid", "token"]);
+        assert!(scenario.has_extract(0));
+        assert!(!scenario.needs_body(1));
+        assert!(scenario.is_iteration_start(0));
+        assert!(!scenario.is_iteration_start(1));
+        assert!(scenario.is_iteration_start(2));
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-sid", HeaderValue::from_static("s1"));
+        let mut extracted = vec![String::new(); 2];
+        let payload = Payload::new(&headers, br#"{"token": "t1"}"#);
+        scenario.extract(0, &payload, &mut extracted).unwrap();
+        assert_eq!(extracted, ["s1", "t1"]);
+        let payload = Payload::new(&headers, b"{}");
+        assert!(scenario.extract(0, &payload, &mut extracted).is_err());
+
+        let values = ["alice", "s1", "t1"].map(String::from);
+        let context = Context {
+            values: &values,
+            ..Context::default()
+        };
+        assert_eq!(
+            scenario.templates[1].headers[0].1.render(&context),
+            "Bearer t1"
+        );
+    }
+
+    #[test]
+    fn test_scenario_load_steps_invalid() {
+        for (name, content) in [
+            (
+                "rsb_test_scenario_both.toml",
+                "[[requests]]\nname = \"a\"\n[[steps]]\nname = \"b\"\n",
+            ),
+            (
+                "rsb_test_scenario_extract.toml",
+                "[[requests]]\nname = \"a\"\nextract.x = { header = \"x\" }\n",
+            ),
+            (
+                "rsb_test_scenario_shadow.toml",
+                "[[steps]]\nname = \"a\"\nextract.user = { header = \"x\" }\n",
+            ),
+            (
+                "rsb_test_scenario_source.toml",
+                "[[steps]]\nname = \"a\"\nextract.x = { cookie = \"x\" }\n",
+            ),
+        ] {
+            let path = write_temp(name, content);
+            let variables = vec!["user".to_string()];
+            assert!(
+                Scenario::load(&path, Some("http://a.com"), &variables)
+                    .is_err(),
+                "{name}"
+            );
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! mod shell splits a command line into words like a POSIX shell, without any
+//! expansion, the supported syntaxes are:
+//!
+//! - `'...'`, everything is taken literally
+//! - `"..."`, only `\"`, `\\`, `\$` and `` \` `` are escaped
+//! - `$'...'`, ANSI-C quoting used by "Copy as cURL", like `\n` and `\x41`
+//! - `\` outside quotes escapes the next character, and joins lines
+
+/// split the command line into words
+pub(crate) fn split(input: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    // whether a word is started, so that `''` is an empty word
+    let mut started = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            },
+            '\'' => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("unclosed `'` in `{input}`"),
+                    }
+                }
+            },
+            '"' => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {},
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            },
+                            None => anyhow::bail!("unclosed `\"` in `{input}`"),
+                        },
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("unclosed `\"` in `{input}`"),
+                    }
+                }
+            },
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => word.push(unescape(&mut chars, input)?),
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("unclosed `$'` in `{input}`"),
+                    }
+                }
+            },
+            '\\' => match chars.next() {
+                // line continuation
+                Some('\n') => {},
+                Some(c) => {
+                    started = true;
+                    word.push(c);
+                },
+                None => anyhow::bail!("trailing `\\` in `{input}`"),
+            },
+            c => {
+                started = true;
+                word.push(c);
+            },
+        }
+    }
+    if started {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// the character escaped in `$'...'`, the leading `\` is consumed
+fn unescape(
+    chars: &mut impl Iterator<Item = char>,
+    input: &str,
+) -> anyhow::Result<char> {
+    let c = match chars.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('x') => {
+            let hex: String = chars.take(2).collect();
+            let code = u8::from_str_radix(&hex, 16).map_err(|_| {
+                anyhow::anyhow!("invalid escape `\\x{hex}` in `{input}`")
+            })?;
+            char::from(code)
+        },
+        Some('u') => {
+            let hex: String = chars.take(4).collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(anyhow::anyhow!(
+                    "invalid escape `\\u{hex}` in `{input}`"
+                ))?
+        },
+        Some(c) => c,
+        None => anyhow::bail!("unclosed `$'` in `{input}`"),
+    };
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("").unwrap(), Vec::<String>::new());
+        assert_eq!(split("  a  b\tc ").unwrap(), ["a", "b", "c"]);
+        assert_eq!(split("a 'b c' \"d e\"").unwrap(), ["a", "b c", "d e"]);
+        assert_eq!(split("a'b'\"c\"d").unwrap(), ["abcd"]);
+        assert_eq!(split("'' x").unwrap(), ["", "x"]);
+        assert_eq!(split(r#""a \"b\" \n""#).unwrap(), [r#"a "b" \n"#]);
+        assert_eq!(split(r"a\ b \'c").unwrap(), ["a b", "'c"]);
+        assert_eq!(split("a \\\n  b").unwrap(), ["a", "b"]);
+        assert_eq!(
+            split(r#"$'{\"a\":\n\x41é\'}'"#).unwrap(),
+            ["{\"a\":\nAé'}"]
+        );
+    }
+
+    #[test]
+    fn test_split_invalid() {
+        assert!(split("'a").is_err());
+        assert!(split("\"a").is_err());
+        assert!(split("$'a").is_err());
+        assert!(split("a\\").is_err());
+        assert!(split(r"$'\xzz'").is_err());
+    }
+}
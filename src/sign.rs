@@ -0,0 +1,407 @@
+//! mod sign signs each request once all of its headers are set, including the
+//! token of oauth2 and the cookies of the jar, so that gateways which
+//! verify the signature of the method, path, headers and body accept them,
+//! the signature is computed for each request, so that its timestamp stays
+//! valid during long tests.
+//!
+//! The supported signers are AWS Signature Version 4, enabled by
+//! `--aws-sigv4 <REGION:SERVICE>` with the credentials of the `AWS_*`
+//! environment variables, and a generic HMAC-SHA256 signer enabled by
+//! `--hmac-secret`, which signs
+//!
+//! ```text
+//! <METHOD>\n<PATH?QUERY>\n<TIMESTAMP>\n<name:value\n of signed headers><SHA256 of body>
+//! ```
+
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use reqwest::Request;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName};
+use sha2::{Digest, Sha256};
+
+use crate::Arg;
+use crate::arg::SignatureEncoding;
+
+/// [Signer] adds the signature headers to the built request
+pub(crate) trait Signer: Send + Sync {
+    fn sign(&self, request: &mut Request) -> anyhow::Result<()>;
+}
+
+/// build the signer given by the arguments, `defaults` are the default
+/// headers of the client, which aren't visible in the built requests
+pub(crate) fn build_signer(
+    arg: &Arg,
+    defaults: HeaderMap,
+) -> anyhow::Result<Option<Box<dyn Signer>>> {
+    if let Some(scope) = &arg.aws_sigv4 {
+        return Ok(Some(Box::new(SigV4::from_env(scope)?)));
+    }
+    if let Some(secret) = &arg.hmac_secret {
+        let mut signed_headers = vec![];
+        for name in &arg.hmac_signed_headers {
+            signed_headers
+                .push(HeaderName::from_bytes(name.trim().as_bytes())?);
+        }
+        return Ok(Some(Box::new(HmacSigner {
+            secret: secret.clone(),
+            header: HeaderName::from_bytes(arg.hmac_header.as_bytes())?,
+            timestamp_header: HeaderName::from_bytes(
+                arg.hmac_timestamp_header.as_bytes(),
+            )?,
+            signed_headers,
+            encoding: arg.hmac_encoding,
+            defaults,
+        })));
+    }
+    Ok(None)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("any key size is valid");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn body_sha256(request: &Request) -> anyhow::Result<String> {
+    let body = match request.body() {
+        Some(body) => body.as_bytes().ok_or(anyhow::anyhow!(
+            "the body of the request can't be signed as it's streamed"
+        ))?,
+        None => &[],
+    };
+    Ok(hex::encode(Sha256::digest(body)))
+}
+
+/// [HmacSigner] signs the request with HMAC-SHA256 of a shared secret
+struct HmacSigner {
+    secret: String,
+    /// the header of the signature
+    header: HeaderName,
+    /// the header of the unix timestamp in seconds
+    timestamp_header: HeaderName,
+    signed_headers: Vec<HeaderName>,
+    encoding: SignatureEncoding,
+    defaults: HeaderMap,
+}
+
+impl HmacSigner {
+    fn sign_at(
+        &self,
+        request: &mut Request,
+        now: SystemTime,
+    ) -> anyhow::Result<()> {
+        let timestamp = now.duration_since(UNIX_EPOCH)?.as_secs().to_string();
+        let url = request.url();
+        let mut payload = format!(
+            "{}\n{}{}\n{timestamp}\n",
+            request.method(),
+            url.path(),
+            url.query()
+                .map(|query| format!("?{query}"))
+                .unwrap_or_default()
+        );
+        for name in &self.signed_headers {
+            let value = request
+                .headers()
+                .get(name)
+                .or(self.defaults.get(name))
+                .map(|value| value.to_str())
+                .transpose()?
+                .unwrap_or_default();
+            payload.push_str(&format!("{name}:{}\n", value.trim()));
+        }
+        payload.push_str(&body_sha256(request)?);
+
+        let signature = hmac_sha256(self.secret.as_bytes(), payload.as_bytes());
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex::encode(signature),
+            SignatureEncoding::Base64 => STANDARD.encode(signature),
+        };
+        let headers = request.headers_mut();
+        headers.insert(self.timestamp_header.clone(), timestamp.parse()?);
+        headers.insert(self.header.clone(), signature.parse()?);
+        Ok(())
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign(&self, request: &mut Request) -> anyhow::Result<()> {
+        self.sign_at(request, SystemTime::now())
+    }
+}
+
+/// [SigV4] signs the request by AWS Signature Version 4
+struct SigV4 {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+    service: String,
+}
+
+impl SigV4 {
+    fn from_env(scope: &str) -> anyhow::Result<Self> {
+        let Some((region, service)) = scope.split_once(':') else {
+            anyhow::bail!(
+                "invalid --aws-sigv4 `{scope}`, expected <REGION:SERVICE>"
+            );
+        };
+        let var = |name| {
+            env::var(name).map_err(|_| {
+                anyhow::anyhow!("--aws-sigv4 requires the {name} variable")
+            })
+        };
+        Ok(Self {
+            access_key: var("AWS_ACCESS_KEY_ID")?,
+            secret_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            region: region.to_string(),
+            service: service.to_string(),
+        })
+    }
+
+    fn sign_at(
+        &self,
+        request: &mut Request,
+        now: SystemTime,
+    ) -> anyhow::Result<()> {
+        let amz_date = amz_date(now)?;
+        let date = &amz_date[..8];
+        let payload_hash = body_sha256(request)?;
+
+        // the host header is added when the request is sent
+        let url = request.url();
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => anyhow::bail!("missing host in url {url}"),
+        };
+        let mut headers =
+            vec![("host", host), ("x-amz-date", amz_date.clone())];
+        if let Some(content_type) = request.headers().get(CONTENT_TYPE) {
+            headers.push(("content-type", content_type.to_str()?.to_string()));
+        }
+        // s3 requires the hash of the payload as a header
+        if self.service == "s3" {
+            headers.push(("x-amz-content-sha256", payload_hash.clone()));
+        }
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort();
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+            request.method(),
+            self.canonical_path(url.path()),
+            canonical_query(url),
+        );
+        let scope =
+            format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request))
+        );
+        let mut key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in [date, &self.region, &self.service, "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature =
+            hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, \
+            SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        );
+        for (name, value) in headers {
+            if name != "host" && name != "content-type" {
+                request.headers_mut().insert(name, value.parse()?);
+            }
+        }
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, authorization.parse()?);
+        Ok(())
+    }
+
+    /// the path is encoded once more except for s3
+    fn canonical_path(&self, path: &str) -> String {
+        match self.service.as_str() {
+            "s3" => path.to_string(),
+            _ => path
+                .split('/')
+                .map(uri_encode)
+                .collect::<Vec<_>>()
+                .join("/"),
+        }
+    }
+}
+
+impl Signer for SigV4 {
+    fn sign(&self, request: &mut Request) -> anyhow::Result<()> {
+        self.sign_at(request, SystemTime::now())
+    }
+}
+
+/// percent-encode everything except the unreserved characters
+fn uri_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// the query pairs encoded and sorted by name and value
+fn canonical_query(url: &reqwest::Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| (uri_encode(&name), uri_encode(&value)))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// the time in the ISO 8601 basic format, e.g. `20150830T123600Z`
+fn amz_date(time: SystemTime) -> anyhow::Result<String> {
+    let secs = time.duration_since(UNIX_EPOCH)?.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // the civil date of the days since 1970-01-01
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    Ok(format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Client;
+
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_amz_date() {
+        assert_eq!(amz_date(at(0)).unwrap(), "19700101T000000Z");
+        assert_eq!(amz_date(at(1440938160)).unwrap(), "20150830T123600Z");
+        assert_eq!(amz_date(at(1709210096)).unwrap(), "20240229T123456Z");
+    }
+
+    #[test]
+    fn test_sigv4_sign() {
+        // the example of the AWS documentation
+        let signer = SigV4 {
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            region: "us-east-1".to_string(),
+            service: "iam".to_string(),
+        };
+        let mut request = Client::new()
+            .get("https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers")
+            .header(
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )
+            .build()
+            .unwrap();
+        signer.sign_at(&mut request, at(1440938160)).unwrap();
+        assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+        assert_eq!(
+            request.headers()[AUTHORIZATION],
+            "AWS4-HMAC-SHA256 \
+            Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+            SignedHeaders=content-type;host;x-amz-date, \
+            Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn test_hmac_sign() {
+        let mut defaults = HeaderMap::new();
+        defaults.insert("x-api-key", "k1".parse().unwrap());
+        let signer = HmacSigner {
+            secret: "secret".to_string(),
+            header: HeaderName::from_static("x-signature"),
+            timestamp_header: HeaderName::from_static("x-timestamp"),
+            signed_headers: vec![
+                HeaderName::from_static("content-type"),
+                HeaderName::from_static("x-api-key"),
+            ],
+            encoding: SignatureEncoding::Hex,
+            defaults,
+        };
+        let build = || {
+            Client::new()
+                .post("http://a.com/orders?id=1")
+                .header("content-type", "application/json")
+                .body("{}")
+                .build()
+                .unwrap()
+        };
+        let mut request = build();
+        signer.sign_at(&mut request, at(1700000000)).unwrap();
+        assert_eq!(request.headers()["x-timestamp"], "1700000000");
+        let payload = format!(
+            "POST\n/orders?id=1\n1700000000\ncontent-type:application/json\n\
+            x-api-key:k1\n{}",
+            hex::encode(Sha256::digest(b"{}"))
+        );
+        assert_eq!(
+            request.headers()["x-signature"],
+            hex::encode(hmac_sha256(b"secret", payload.as_bytes()))
+        );
+
+        // a new signature for another timestamp
+        let mut later = build();
+        signer.sign_at(&mut later, at(1700000001)).unwrap();
+        assert_ne!(
+            request.headers()["x-signature"],
+            later.headers()["x-signature"]
+        );
+    }
+}
@@ -5,10 +5,32 @@ use std::error::Error;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::*};
 use std::time::{Duration, Instant};
 
-use num::integer::Roots;
+use bytes::Bytes;
+use hdrhistogram::Histogram;
 use reqwest::{Response, StatusCode};
 use tokio::{sync as tsync, time as ttime};
 
+use crate::assertion::{Assertion, Subject};
+use crate::client::HandshakeError;
+use crate::grpc::CODES;
+use crate::stream::Stream;
+
+/// the default number of significant value digits kept by the histograms
+pub(crate) const DEFAULT_PRECISION: u8 = 3;
+
+/// the highest latency trackable by the histograms, unit: microseconds, larger
+/// values are saturated to it
+const MAX_TRACKABLE_MICROS: u64 = 3600 * 1_000_000;
+
+/// create a fixed-memory histogram to record latencies in microseconds
+pub(crate) fn new_histogram(precision: u8) -> Histogram<u64> {
+    Histogram::new_with_bounds(1, MAX_TRACKABLE_MICROS, precision).unwrap()
+}
+
+fn record(histogram: &mut Histogram<u64>, used_time: Duration) {
+    histogram.saturating_record(used_time.as_micros() as u64);
+}
+
 #[derive(Debug)]
 pub(crate) struct Statistics {
     /// status code [100, 200)
@@ -32,6 +54,17 @@ pub(crate) struct Statistics {
     /// errors category
     pub(crate) errors: tsync::Mutex<HashMap<String, u64>>,
 
+    /// failures to establish the connections of HTTP/3, they're counted apart
+    /// from the other errors
+    pub(crate) handshake_errors: tsync::Mutex<HashMap<String, u64>>,
+
+    /// the negotiated HTTP versions of the responses, e.g. `HTTP/2.0`
+    pub(crate) protocols: tsync::Mutex<HashMap<String, u64>>,
+
+    /// failures of `--json-command` to generate the bodies, no request is
+    /// sent for them
+    pub(crate) generator_errors: tsync::Mutex<HashMap<String, u64>>,
+
     /// start time
     started_at: tsync::Mutex<Instant>,
 
@@ -41,6 +74,31 @@ pub(crate) struct Statistics {
     /// total send and receive response requests although meets error
     total: AtomicU64,
 
+    /// number of requests which failed, each of them is counted once however
+    /// it failed, by an error, an assertion, an extraction or the generator
+    pub(crate) total_failed: AtomicU64,
+
+    /// rules which every response should satisfy
+    pub(crate) assertions: Vec<Assertion>,
+
+    /// number of responses which failed each assertion
+    pub(crate) assertion_failures: Vec<AtomicU64>,
+
+    /// number of responses which failed any assertion
+    pub(crate) total_assertion_failed: AtomicU64,
+
+    /// number of bytes sent in requests
+    pub(crate) req_bytes: AtomicU64,
+
+    /// number of bytes received in responses
+    pub(crate) rsp_bytes: AtomicU64,
+
+    /// number of bytes received per second during the whole test
+    pub(crate) transfer_per_second: tsync::Mutex<f64>,
+
+    /// minimum per second, the incomplete last second excluded
+    pub(crate) min_req_per_second: tsync::Mutex<f64>,
+
     /// maximum per second
     pub(crate) max_req_per_second: tsync::Mutex<f64>,
 
@@ -60,14 +118,41 @@ pub(crate) struct Statistics {
     /// average time spent on request
     pub(crate) avg_req_used_time: tsync::Mutex<Duration>,
 
+    /// minimum time spent by the request
+    pub(crate) min_req_used_time: tsync::Mutex<Duration>,
+
     /// maximum time spent by the request
     pub(crate) max_req_used_time: tsync::Mutex<Duration>,
 
     /// stdev per request, link: https://en.wikipedia.org/wiki/Standard_deviation
     pub(crate) stdev_req_used_time: tsync::Mutex<Duration>,
 
-    /// used internally to record the time spent on each request
-    used_time: tsync::Mutex<Vec<Duration>>,
+    /// number of significant value digits kept by the histograms
+    precision: u8,
+
+    /// histogram of the time spent on each request, unit: microseconds
+    pub(crate) used_time: tsync::Mutex<Histogram<u64>>,
+
+    /// histogram of the time spent on each request measured from its intended
+    /// start time, only recorded in open model
+    pub(crate) corrected_used_time: tsync::Mutex<Histogram<u64>>,
+
+    /// number of requests whose latency was corrected for coordinated omission
+    pub(crate) corrected_total: AtomicU64,
+
+    /// average time spent on request, measured from its intended start time
+    pub(crate) avg_corrected_used_time: tsync::Mutex<Duration>,
+
+    /// minimum time spent by the request, measured from its intended start
+    /// time
+    pub(crate) min_corrected_used_time: tsync::Mutex<Duration>,
+
+    /// maximum time spent by the request, measured from its intended start
+    /// time
+    pub(crate) max_corrected_used_time: tsync::Mutex<Duration>,
+
+    /// stdev per request, measured from its intended start time
+    pub(crate) stdev_corrected_used_time: tsync::Mutex<Duration>,
 
     /// indicates whether to stop, used to notify the internal timer to exit
     is_stopped: AtomicBool,
@@ -79,12 +164,94 @@ pub(crate) struct Statistics {
     pub(crate) throughput: tsync::Mutex<f64>,
 
     /// latencies for different percentiles
-    pub(crate) latencies: tsync::Mutex<Vec<(f32, Duration)>>,
+    pub(crate) latencies: tsync::Mutex<Vec<(f64, Duration)>>,
+
+    /// latencies for different percentiles, measured from the intended start
+    /// time of each request
+    pub(crate) corrected_latencies: tsync::Mutex<Vec<(f64, Duration)>>,
+
+    /// statistics of each stage of the load profile, only exists with stages
+    pub(crate) stages: tsync::Mutex<Vec<GroupStatistics>>,
+
+    /// statistics of each named request of the scenario, only exists with
+    /// scenario
+    pub(crate) requests: tsync::Mutex<Vec<(String, GroupStatistics)>>,
+
+    /// statistics of the sockets, only exists with WebSocket urls
+    pub(crate) websocket: Option<WebSocketStatistics>,
+
+    /// statistics of the status codes, only exists with `--grpc-method`
+    pub(crate) grpc: Option<GrpcStatistics>,
+
+    /// statistics of the events of the responses, only exists with `--stream`
+    pub(crate) stream: Option<StreamStatistics>,
+}
+
+/// [WebSocketStatistics] counts the sockets of the WebSocket urls, the
+/// messages are counted as the requests
+#[derive(Debug)]
+pub(crate) struct WebSocketStatistics {
+    /// the time spent opening each socket, including the handshake
+    pub(crate) connects: tsync::Mutex<GroupStatistics>,
+
+    /// the codes of the close frames of the server, 1006 if a socket is
+    /// closed without one
+    pub(crate) close_codes: tsync::Mutex<HashMap<u16, u64>>,
+
+    /// number of messages which received their replies
+    pub(crate) replies: AtomicU64,
+
+    /// number of messages received which aren't the reply of any message
+    pub(crate) unmatched: AtomicU64,
+}
+
+/// [GrpcStatistics] counts the status codes of the gRPC calls, which take
+/// the place of the HTTP codes
+#[derive(Debug)]
+pub(crate) struct GrpcStatistics {
+    /// number of calls of each code, indexed by the code
+    pub(crate) codes: [AtomicU64; CODES.len()],
+
+    /// number of calls whose code is unknown to gRPC
+    pub(crate) others: AtomicU64,
+}
+
+/// [StreamStatistics] counts the events of the responses read as streams,
+/// each time is recorded as a group of its own, the streams which failed
+/// aren't counted
+#[derive(Debug)]
+pub(crate) struct StreamStatistics {
+    /// the time to the headers of each response
+    pub(crate) ttfb: tsync::Mutex<GroupStatistics>,
+
+    /// the time to the first event of each response which has any
+    pub(crate) first_event: tsync::Mutex<GroupStatistics>,
+
+    /// the time between two consecutive events of a response
+    pub(crate) gaps: tsync::Mutex<GroupStatistics>,
+
+    /// the time from each request to the end of its stream
+    pub(crate) durations: tsync::Mutex<GroupStatistics>,
+
+    /// histogram of the number of events of each response
+    pub(crate) events: tsync::Mutex<Histogram<u64>>,
+
+    /// number of events of all responses
+    pub(crate) total_events: AtomicU64,
+
+    /// the number of events for different percentiles
+    pub(crate) event_counts: tsync::Mutex<Vec<(f64, u64)>>,
 }
 
 impl Statistics {
     /// construct empty Statistics
     pub(crate) fn new() -> Statistics {
+        Statistics::with_precision(DEFAULT_PRECISION)
+    }
+
+    /// construct empty Statistics, whose histograms keep the given number of
+    /// significant value digits
+    pub(crate) fn with_precision(precision: u8) -> Statistics {
         Self {
             rsp1xx: AtomicU64::new(0),
             rsp2xx: AtomicU64::new(0),
@@ -93,11 +260,22 @@ impl Statistics {
             rsp5xx: AtomicU64::new(0),
             rsp_others: AtomicU64::new(0),
             errors: tsync::Mutex::new(HashMap::new()),
+            handshake_errors: tsync::Mutex::new(HashMap::new()),
+            protocols: tsync::Mutex::new(HashMap::new()),
+            generator_errors: tsync::Mutex::new(HashMap::new()),
             started_at: tsync::Mutex::new(Instant::now()),
             total: AtomicU64::new(0),
+            total_failed: AtomicU64::new(0),
             total_success: AtomicU64::new(0),
+            assertions: vec![],
+            assertion_failures: vec![],
+            total_assertion_failed: AtomicU64::new(0),
+            req_bytes: AtomicU64::new(0),
+            rsp_bytes: AtomicU64::new(0),
+            transfer_per_second: tsync::Mutex::new(0.0),
             req_per_second: tsync::Mutex::new(Vec::new()),
             avg_req_per_second: tsync::Mutex::new(0.0),
+            min_req_per_second: tsync::Mutex::new(0.0),
             max_req_per_second: tsync::Mutex::new(0.0),
             stdev_per_second: tsync::Mutex::new(0.0),
             is_stopped: AtomicBool::new(false),
@@ -105,13 +283,84 @@ impl Statistics {
             stopped_at: tsync::Mutex::new(None),
             latencies: tsync::Mutex::new(Vec::new()),
             throughput: tsync::Mutex::new(0.0),
-            used_time: tsync::Mutex::new(Vec::new()),
+            precision,
+            used_time: tsync::Mutex::new(new_histogram(precision)),
             avg_req_used_time: tsync::Mutex::new(Duration::from_secs(0)),
+            min_req_used_time: tsync::Mutex::new(Duration::from_secs(0)),
             max_req_used_time: tsync::Mutex::new(Duration::from_secs(0)),
             stdev_req_used_time: tsync::Mutex::new(Duration::from_secs(0)),
+            corrected_used_time: tsync::Mutex::new(new_histogram(precision)),
+            corrected_total: AtomicU64::new(0),
+            avg_corrected_used_time: tsync::Mutex::new(Duration::from_secs(0)),
+            min_corrected_used_time: tsync::Mutex::new(Duration::from_secs(0)),
+            max_corrected_used_time: tsync::Mutex::new(Duration::from_secs(0)),
+            stdev_corrected_used_time: tsync::Mutex::new(Duration::from_secs(
+                0,
+            )),
+            corrected_latencies: tsync::Mutex::new(Vec::new()),
+            stages: tsync::Mutex::new(Vec::new()),
+            requests: tsync::Mutex::new(Vec::new()),
+            websocket: None,
+            grpc: None,
+            stream: None,
         }
     }
 
+    /// count the sockets of the WebSocket urls
+    pub(crate) fn with_websocket(mut self) -> Statistics {
+        self.websocket = Some(WebSocketStatistics {
+            connects: tsync::Mutex::new(GroupStatistics::new(self.precision)),
+            close_codes: tsync::Mutex::new(HashMap::new()),
+            replies: AtomicU64::new(0),
+            unmatched: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// count the status codes of the gRPC calls
+    pub(crate) fn with_grpc(mut self) -> Statistics {
+        self.grpc = Some(GrpcStatistics {
+            codes: std::array::from_fn(|_| AtomicU64::new(0)),
+            others: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// count the events of the responses read as streams
+    pub(crate) fn with_stream(mut self) -> Statistics {
+        let group = || tsync::Mutex::new(GroupStatistics::new(self.precision));
+        self.stream = Some(StreamStatistics {
+            ttfb: group(),
+            first_event: group(),
+            gaps: group(),
+            durations: group(),
+            events: tsync::Mutex::new(new_histogram(self.precision)),
+            event_counts: tsync::Mutex::new(Vec::new()),
+            total_events: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// count the requests of the scenario by their names
+    pub(crate) fn with_requests(mut self, names: Vec<String>) -> Statistics {
+        *self.requests.get_mut() = names
+            .into_iter()
+            .map(|name| (name, GroupStatistics::new(self.precision)))
+            .collect();
+        self
+    }
+
+    /// check every response against the assertions
+    pub(crate) fn with_assertions(
+        mut self,
+        assertions: Vec<Assertion>,
+    ) -> Statistics {
+        self.assertion_failures =
+            assertions.iter().map(|_| AtomicU64::new(0)).collect();
+        self.assertions = assertions;
+        self
+    }
+
     /// return current send and rcv requests
     pub(crate) fn get_total(&self) -> u64 {
         self.total.load(Acquire)
@@ -181,7 +430,29 @@ impl Statistics {
         }
     }
 
-    async fn handle_resp_error(&self, err: reqwest::Error) {
+    fn statistics_grpc_code(&self, code: u32) {
+        let Some(grpc) = &self.grpc else {
+            return;
+        };
+        match grpc.codes.get(code as usize) {
+            Some(count) => count.fetch_add(1, SeqCst),
+            None => grpc.others.fetch_add(1, SeqCst),
+        };
+    }
+
+    async fn handle_resp_error(&self, err: anyhow::Error) {
+        let err = match err.downcast::<reqwest::Error>() {
+            Ok(err) => err,
+            // the handshake errors of HTTP/3 are counted apart
+            Err(err) => {
+                let errors = match err.is::<HandshakeError>() {
+                    true => &self.handshake_errors,
+                    false => &self.errors,
+                };
+                *errors.lock().await.entry(err.to_string()).or_insert(0) += 1;
+                return;
+            },
+        };
         let err_msg = format!("{}", err.source().as_ref().unwrap());
         {
             let mut errors = self.errors.lock().await;
@@ -195,28 +466,208 @@ impl Statistics {
         }
     }
 
+    /// count the failure to generate the body of a request, which is counted
+    /// as a request that isn't sent
+    pub(crate) async fn handle_generator_error(&self, error: String) {
+        self.total.fetch_add(1, SeqCst);
+        self.total_failed.fetch_add(1, SeqCst);
+        *self.generator_errors.lock().await.entry(error).or_insert(0) += 1;
+    }
+
+    /// count the time spent opening a socket, the failed ones are counted but
+    /// their time isn't
+    pub(crate) async fn handle_connect(
+        &self,
+        started_at: Instant,
+        connected_at: Instant,
+        is_success: bool,
+    ) {
+        if let Some(websocket) = &self.websocket {
+            let mut connects = websocket.connects.lock().await;
+            connects.record(started_at, connected_at, is_success);
+        }
+    }
+
+    /// count the close code of a socket
+    pub(crate) async fn handle_close(&self, code: u16) {
+        if let Some(websocket) = &self.websocket {
+            *websocket.close_codes.lock().await.entry(code).or_insert(0) += 1;
+        }
+    }
+
+    /// count the messages received which aren't the reply
+    pub(crate) fn handle_unmatched(&self, unmatched: u64) {
+        if let Some(websocket) = &self.websocket {
+            websocket.unmatched.fetch_add(unmatched, SeqCst);
+        }
+    }
+
+    /// count the timing of the events of a stream
+    async fn handle_stream(&self, req_at: Instant, stream: Stream) {
+        let Some(statistics) = &self.stream else {
+            return;
+        };
+        statistics
+            .ttfb
+            .lock()
+            .await
+            .record(req_at, stream.headers_at, true);
+        if let Some(first) = stream.events.first() {
+            statistics
+                .first_event
+                .lock()
+                .await
+                .record(req_at, *first, true);
+        }
+        let mut gaps = statistics.gaps.lock().await;
+        for pair in stream.events.windows(2) {
+            gaps.record(pair[0], pair[1], true);
+        }
+        statistics
+            .durations
+            .lock()
+            .await
+            .record(req_at, stream.ended_at, true);
+        let events = stream.events.len() as u64;
+        statistics.events.lock().await.saturating_record(events);
+        statistics.total_events.fetch_add(events, SeqCst);
+    }
+
     /// receive message and make statistics
     pub(crate) async fn handle_message(&self, message: Message) {
         let Message {
             rsp_at,
             req_at,
+            intended_at,
+            stage,
+            request,
+            req_bytes,
+            rsp_bytes,
+            body,
+            error,
             response,
+            stream,
         } = message;
 
         self.total.fetch_add(1, SeqCst);
-
-        if response.is_err() {
-            let err = response.err().unwrap();
-            self.handle_resp_error(err).await;
-            return;
+        self.req_bytes.fetch_add(req_bytes, SeqCst);
+        self.rsp_bytes.fetch_add(rsp_bytes, SeqCst);
+        // the response whose values can't be extracted is a failed request
+        let extracted = error.is_none();
+        if let Some(error) = error {
+            *self.errors.lock().await.entry(error).or_insert(0) += 1;
         }
 
-        let response = response.unwrap();
-        self.statistics_rsp_code(response.status());
-        self.total_success.fetch_add(1, SeqCst);
+        let received = match response {
+            Ok(received) => received,
+            Err(err) => {
+                self.total_failed.fetch_add(1, SeqCst);
+                self.record_groups(stage, request, req_at, rsp_at, false)
+                    .await;
+                self.handle_resp_error(err).await;
+                return;
+            },
+        };
+
+        // the replies of the WebSocket messages and the gRPC calls have no
+        // response to check
+        let passed = match received {
+            Received::Response(response) => {
+                self.statistics_rsp_code(response.status());
+                if let Some(stream) = stream {
+                    self.handle_stream(req_at, stream).await;
+                }
+                *self
+                    .protocols
+                    .lock()
+                    .await
+                    .entry(format!("{:?}", response.version()))
+                    .or_insert(0) += 1;
+                let subject = Subject::new(
+                    &response,
+                    body.as_deref().unwrap_or_default(),
+                    rsp_at - req_at,
+                );
+                self.check_assertions(&subject)
+            },
+            Received::Reply => {
+                if let Some(websocket) = &self.websocket {
+                    websocket.replies.fetch_add(1, SeqCst);
+                }
+                true
+            },
+            Received::Grpc(code) => {
+                self.statistics_grpc_code(code);
+                true
+            },
+        };
+        if !passed {
+            self.total_assertion_failed.fetch_add(1, SeqCst);
+        }
+        let success = passed && extracted;
+        if success {
+            self.total_success.fetch_add(1, SeqCst);
+        } else {
+            self.total_failed.fetch_add(1, SeqCst);
+        }
+        self.record_groups(stage, request, req_at, rsp_at, success)
+            .await;
         self.current_cumulative.fetch_add(1, SeqCst);
-        let mut used_time = self.used_time.lock().await;
-        used_time.push(rsp_at - req_at);
+        {
+            let mut used_time = self.used_time.lock().await;
+            record(&mut used_time, rsp_at - req_at);
+        }
+        if let Some(intended_at) = intended_at {
+            self.corrected_total.fetch_add(1, SeqCst);
+            let mut corrected_used_time = self.corrected_used_time.lock().await;
+            record(
+                &mut corrected_used_time,
+                rsp_at.saturating_duration_since(intended_at),
+            );
+        }
+    }
+
+    /// record the request in its stage and its request of the scenario, the
+    /// response failing an assertion or an extraction isn't a success
+    async fn record_groups(
+        &self,
+        stage: Option<usize>,
+        request: Option<usize>,
+        req_at: Instant,
+        rsp_at: Instant,
+        success: bool,
+    ) {
+        if let Some(stage) = stage {
+            let mut stages = self.stages.lock().await;
+            if stages.len() <= stage {
+                stages.resize_with(stage + 1, || {
+                    GroupStatistics::new(self.precision)
+                });
+            }
+            stages[stage].record(req_at, rsp_at, success);
+        }
+
+        if let Some(request) = request
+            && let Some((_, group)) =
+                self.requests.lock().await.get_mut(request)
+        {
+            group.record(req_at, rsp_at, success);
+        }
+    }
+
+    /// check the response against all assertions, returns false if it fails any
+    /// of them, every failed assertion is counted
+    fn check_assertions(&self, subject: &Subject) -> bool {
+        let mut passed = true;
+        for (assertion, failures) in
+            self.assertions.iter().zip(&self.assertion_failures)
+        {
+            if !assertion.check(subject) {
+                failures.fetch_add(1, SeqCst);
+                passed = false;
+            }
+        }
+        passed
     }
 
     /// notify stop timer
@@ -234,6 +685,21 @@ impl Statistics {
         }
     }
 
+    async fn calculate_min_per_second(&self) {
+        let req_per_second = self.req_per_second.lock().await;
+        let mut origin = &*req_per_second as &[u64];
+
+        // the data at the last second is likely to be incomplete
+        if origin.len() > 2 {
+            origin = &origin[..origin.len() - 1];
+        }
+
+        if let Some(min) = origin.iter().min() {
+            let mut min_per_second = self.min_req_per_second.lock().await;
+            *min_per_second = *min as f64;
+        }
+    }
+
     async fn calculate_avg_per_second(&self) {
         let req_per_second = self.req_per_second.lock().await;
         if (*req_per_second).is_empty() {
@@ -281,38 +747,33 @@ impl Statistics {
     }
 
     async fn calculate_elapsed_time(&self) {
-        let mut used_time = self.used_time.lock().await;
-        if (*used_time).is_empty() {
-            return;
+        let used_time = self.used_time.lock().await;
+        if !used_time.is_empty() {
+            let (avg, min, max, stdev) = elapsed_time_of(&used_time);
+            *self.avg_req_used_time.lock().await = avg;
+            *self.min_req_used_time.lock().await = min;
+            *self.max_req_used_time.lock().await = max;
+            *self.stdev_req_used_time.lock().await = stdev;
         }
-        used_time.sort();
-
-        // avg_req_elapsed_time
-        let mut avg_req_used_time = self.avg_req_used_time.lock().await;
-        let total: Duration = used_time.iter().sum();
-        let count = used_time.len();
-        *avg_req_used_time = total / count as u32;
 
-        // max_req_elapsed_time
-        let mut max_req_used_time = self.max_req_used_time.lock().await;
-        if let Some(max) = used_time.iter().max() {
-            *max_req_used_time = *max;
+        let corrected_used_time = self.corrected_used_time.lock().await;
+        if !corrected_used_time.is_empty() {
+            let (avg, min, max, stdev) = elapsed_time_of(&corrected_used_time);
+            *self.avg_corrected_used_time.lock().await = avg;
+            *self.min_corrected_used_time.lock().await = min;
+            *self.max_corrected_used_time.lock().await = max;
+            *self.stdev_corrected_used_time.lock().await = stdev;
         }
+    }
 
-        // stdev_req_elapsed_time
-        let sum = (*used_time).iter().sum::<Duration>();
-        let mean = (sum as Duration / count as u32).as_nanos();
-        let variance: u128 = (*used_time)
-            .iter()
-            .map(|x| {
-                let diff: i128 = (*x).as_nanos() as i128 - mean as i128;
-                (diff * diff) as u128
-            })
-            .sum::<u128>()
-            / count as u128;
-        let stdev = variance.sqrt();
-        let mut stdev_req_used_time = self.stdev_req_used_time.lock().await;
-        *stdev_req_used_time = Duration::from_nanos(stdev as u64);
+    async fn calculate_transfer_per_second(&self) {
+        let started_at = *self.started_at.lock().await;
+        let stopped_at = self.stopped_at.lock().await.unwrap_or(Instant::now());
+        let sec = (stopped_at - started_at).as_secs_f64();
+        if sec > 0f64 {
+            let mut transfer_per_second = self.transfer_per_second.lock().await;
+            *transfer_per_second = self.rsp_bytes.load(Acquire) as f64 / sec;
+        }
     }
 
     async fn calculate_throughput(&self, connections: u16) {
@@ -324,78 +785,324 @@ impl Statistics {
         }
     }
 
-    async fn calculate_latencies(&self, percentiles: Vec<f32>) {
-        let mut used_time = self.used_time.lock().await;
-        if used_time.is_empty() {
-            return;
-        }
-        if !used_time.is_sorted() {
-            used_time.sort();
+    async fn calculate_latencies(&self, percentiles: Vec<f64>) {
+        let used_time = self.used_time.lock().await;
+        if !used_time.is_empty() {
+            let mut latencies = self.latencies.lock().await;
+            latencies.extend(latencies_of(&used_time, &percentiles));
         }
 
-        let mut latencies = self.latencies.lock().await;
-        let count = used_time.len();
-        for percent in percentiles {
-            let percent_len = (count as f32 * percent) as usize;
-            if percent_len > count || percent_len == 0 {
-                continue;
-            }
-            let percent_elapsed_time: &[Duration] =
-                &(*used_time)[..percent_len];
-            let sum = percent_elapsed_time.iter().sum::<Duration>();
-            latencies.push((percent, sum / percent_len as u32));
+        let corrected_used_time = self.corrected_used_time.lock().await;
+        if !corrected_used_time.is_empty() {
+            let mut latencies = self.corrected_latencies.lock().await;
+            latencies.extend(latencies_of(&corrected_used_time, &percentiles));
         }
     }
 
-    async fn clear_temporary_data(&self) {
-        let mut used_time = self.used_time.lock().await;
-        used_time.clear();
-        used_time.shrink_to(0);
+    async fn calculate_groups(&self, percentiles: &[f64]) {
+        for stage in self.stages.lock().await.iter_mut() {
+            stage.summary(percentiles);
+        }
+        for (_, request) in self.requests.lock().await.iter_mut() {
+            request.summary(percentiles);
+        }
+        if let Some(websocket) = &self.websocket {
+            websocket.connects.lock().await.summary(percentiles);
+        }
+        if let Some(stream) = &self.stream {
+            for group in [
+                &stream.ttfb,
+                &stream.first_event,
+                &stream.gaps,
+                &stream.durations,
+            ] {
+                group.lock().await.summary(percentiles);
+            }
+            let events = stream.events.lock().await;
+            if !events.is_empty() {
+                *stream.event_counts.lock().await = percentiles
+                    .iter()
+                    .map(|percent| {
+                        (*percent, events.value_at_quantile(*percent))
+                    })
+                    .collect();
+            }
+        }
     }
 
     /// need to manually call this method for statistical summary
     pub(crate) async fn summary(
         &self,
         connections: u16,
-        percentiles: Vec<f32>,
+        percentiles: Vec<f64>,
     ) {
+        self.calculate_min_per_second().await;
         self.calculate_max_per_second().await;
         self.calculate_avg_per_second().await;
         self.calculate_elapsed_time().await;
         self.calculate_stdev_per_second().await;
         self.calculate_throughput(connections).await;
+        self.calculate_transfer_per_second().await;
+        self.calculate_groups(&percentiles).await;
         self.calculate_latencies(percentiles).await;
-        self.clear_temporary_data().await;
     }
 }
 
+/// [GroupStatistics] counts a group of requests, such as the requests sent
+/// during one stage of the load profile, or the requests of one named request
+/// of the scenario
+#[derive(Debug)]
+pub(crate) struct GroupStatistics {
+    /// total send and receive response requests although meets error
+    pub(crate) total: u64,
+
+    /// total_success send and receive response requests
+    pub(crate) total_success: u64,
+
+    /// the time at which the first request of the group was sent
+    first_req_at: Option<Instant>,
+
+    /// the time at which the last response of the group was received
+    last_rsp_at: Option<Instant>,
+
+    /// histogram of the time spent on each request, unit: microseconds
+    used_time: Histogram<u64>,
+
+    /// average number of successful requests per second of the group
+    pub(crate) avg_req_per_second: f64,
+
+    /// average time spent on request
+    pub(crate) avg_req_used_time: Duration,
+
+    /// stdev per request
+    pub(crate) stdev_req_used_time: Duration,
+
+    /// minimum time spent by the request
+    pub(crate) min_req_used_time: Duration,
+
+    /// maximum time spent by the request
+    pub(crate) max_req_used_time: Duration,
+
+    /// latencies for different percentiles
+    pub(crate) latencies: Vec<(f64, Duration)>,
+}
+
+impl GroupStatistics {
+    pub(crate) fn new(precision: u8) -> Self {
+        Self {
+            total: 0,
+            total_success: 0,
+            first_req_at: None,
+            last_rsp_at: None,
+            used_time: new_histogram(precision),
+            avg_req_per_second: 0.0,
+            avg_req_used_time: Duration::ZERO,
+            stdev_req_used_time: Duration::ZERO,
+            min_req_used_time: Duration::ZERO,
+            max_req_used_time: Duration::ZERO,
+            latencies: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, req_at: Instant, rsp_at: Instant, is_success: bool) {
+        self.total += 1;
+        self.first_req_at = Some(
+            self.first_req_at
+                .map_or(req_at, |first| std::cmp::min(first, req_at)),
+        );
+        self.last_rsp_at = Some(
+            self.last_rsp_at
+                .map_or(rsp_at, |last| std::cmp::max(last, rsp_at)),
+        );
+        if is_success {
+            self.total_success += 1;
+            record(&mut self.used_time, rsp_at - req_at);
+        }
+    }
+
+    fn summary(&mut self, percentiles: &[f64]) {
+        if let (Some(first), Some(last)) = (self.first_req_at, self.last_rsp_at)
+        {
+            let sec = (last - first).as_secs_f64();
+            if sec > 0f64 {
+                self.avg_req_per_second = self.total_success as f64 / sec;
+            }
+        }
+
+        if !self.used_time.is_empty() {
+            let (avg, min, max, stdev) = elapsed_time_of(&self.used_time);
+            self.avg_req_used_time = avg;
+            self.min_req_used_time = min;
+            self.max_req_used_time = max;
+            self.stdev_req_used_time = stdev;
+            self.latencies = latencies_of(&self.used_time, percentiles);
+        }
+    }
+}
+
+/// calculate the average, minimum, maximum and standard deviation of the time
+/// spent
+fn elapsed_time_of(
+    used_time: &Histogram<u64>,
+) -> (Duration, Duration, Duration, Duration) {
+    let avg = Duration::from_secs_f64(used_time.mean() / 1_000_000f64);
+    let min = Duration::from_micros(used_time.min());
+    let max = Duration::from_micros(used_time.max());
+    let stdev = Duration::from_secs_f64(used_time.stdev() / 1_000_000f64);
+    (avg, min, max, stdev)
+}
+
+/// calculate the latencies of the time spent for different percentiles, each
+/// of which is the value at the rank of that percentile, that is, the given
+/// percentage of requests took no longer than it
+fn latencies_of(
+    used_time: &Histogram<u64>,
+    percentiles: &[f64],
+) -> Vec<(f64, Duration)> {
+    percentiles
+        .iter()
+        .map(|percent| {
+            let micros = used_time.value_at_quantile(*percent);
+            (*percent, Duration::from_micros(micros))
+        })
+        .collect()
+}
+
 impl Default for Statistics {
     fn default() -> Self {
         Statistics::new()
     }
 }
 
+/// [Received] is what a request received
+#[derive(Debug)]
+enum Received {
+    /// the response of a HTTP request
+    Response(Response),
+    /// the reply of a WebSocket message
+    Reply,
+    /// the status code of a gRPC call
+    Grpc(u32),
+}
+
 /// Message entity for [Statistics]
 #[derive(Debug)]
 pub(crate) struct Message {
     rsp_at: Instant,
     req_at: Instant,
-    response: Result<Response, reqwest::Error>,
+    intended_at: Option<Instant>,
+    stage: Option<usize>,
+    request: Option<usize>,
+    req_bytes: u64,
+    rsp_bytes: u64,
+    body: Option<Bytes>,
+    error: Option<String>,
+    response: anyhow::Result<Received>,
+    /// the timing of the events of the response with `--stream`
+    stream: Option<Stream>,
 }
 
 impl Message {
     /// construct message
-    pub(crate) fn new(
-        response: Result<Response, reqwest::Error>,
+    pub(crate) fn new<E: Into<anyhow::Error>>(
+        response: Result<Response, E>,
+        req_at: Instant,
+        rsp_at: Instant,
+    ) -> Message {
+        Self::with_response(
+            response.map(Received::Response).map_err(Into::into),
+            req_at,
+            rsp_at,
+        )
+    }
+
+    /// construct the message of a WebSocket message, which has no response
+    pub(crate) fn reply(
+        result: anyhow::Result<()>,
+        req_at: Instant,
+        rsp_at: Instant,
+    ) -> Message {
+        Self::with_response(result.map(|_| Received::Reply), req_at, rsp_at)
+    }
+
+    /// construct the message of a gRPC call, which has its status code only
+    pub(crate) fn grpc(
+        code: anyhow::Result<u32>,
+        req_at: Instant,
+        rsp_at: Instant,
+    ) -> Message {
+        Self::with_response(code.map(Received::Grpc), req_at, rsp_at)
+    }
+
+    fn with_response(
+        response: anyhow::Result<Received>,
         req_at: Instant,
         rsp_at: Instant,
     ) -> Message {
         Self {
             rsp_at,
             req_at,
+            intended_at: None,
+            stage: None,
+            request: None,
+            req_bytes: 0,
+            rsp_bytes: 0,
+            body: None,
+            error: None,
             response,
+            stream: None,
         }
     }
+
+    /// set the time at which the request was intended to be sent, used to
+    /// correct the latency for coordinated omission in open model
+    pub(crate) fn with_intended_at(mut self, intended_at: Instant) -> Message {
+        self.intended_at = Some(intended_at);
+        self
+    }
+
+    /// set the number of bytes sent in the request and received in the
+    /// response
+    pub(crate) fn with_bytes(
+        mut self,
+        req_bytes: u64,
+        rsp_bytes: u64,
+    ) -> Message {
+        self.req_bytes = req_bytes;
+        self.rsp_bytes = rsp_bytes;
+        self
+    }
+
+    /// set the response body, which is only kept when assertions need it
+    pub(crate) fn with_body(mut self, body: Bytes) -> Message {
+        self.body = Some(body);
+        self
+    }
+
+    /// set the timing of the events of the response read as a stream
+    pub(crate) fn with_stream(mut self, stream: Stream) -> Message {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// set the error found after the response is received, such as a value
+    /// failed to be extracted, which is counted in the errors
+    pub(crate) fn with_error(mut self, error: String) -> Message {
+        self.error = Some(error);
+        self
+    }
+
+    /// set the index of the named request of the scenario
+    pub(crate) fn with_request(mut self, request: usize) -> Message {
+        self.request = Some(request);
+        self
+    }
+
+    /// set the index of the stage which the request belongs to
+    pub(crate) fn with_stage(mut self, stage: usize) -> Message {
+        self.stage = Some(stage);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -509,7 +1216,7 @@ mod tests {
 
         let mut used_time = stats.used_time.lock().await;
         for i in 0..10 {
-            used_time.push(Duration::from_millis(i as u64));
+            record(&mut used_time, Duration::from_millis(i as u64));
         }
         drop(used_time);
 
@@ -574,7 +1281,7 @@ mod tests {
         // Add some test data
         let mut used_time = stats.used_time.lock().await;
         for i in 0..10 {
-            used_time.push(Duration::from_millis(i as u64 * 10));
+            record(&mut used_time, Duration::from_millis(i as u64 * 10));
         }
         drop(used_time);
 
@@ -611,7 +1318,7 @@ mod tests {
         // Add some test data
         let mut used_time = stats.used_time.lock().await;
         for i in 0..100 {
-            used_time.push(Duration::from_millis(i as u64));
+            record(&mut used_time, Duration::from_millis(i as u64));
         }
         drop(used_time);
 
@@ -622,6 +1329,309 @@ mod tests {
         assert_eq!(latencies.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_statistics_corrected_latencies() {
+        let stats = Statistics::new();
+
+        let mut used_time = stats.used_time.lock().await;
+        let mut corrected_used_time = stats.corrected_used_time.lock().await;
+        for i in 1..=100 {
+            record(&mut used_time, Duration::from_millis(i));
+            // each request waited 10ms for its turn
+            record(&mut corrected_used_time, Duration::from_millis(i + 10));
+        }
+        drop(used_time);
+        drop(corrected_used_time);
+
+        stats.calculate_elapsed_time().await;
+        stats.calculate_latencies(vec![0.5]).await;
+
+        let avg = *stats.avg_req_used_time.lock().await;
+        let corrected_avg = *stats.avg_corrected_used_time.lock().await;
+        assert_close(avg, Duration::from_micros(50_500));
+        assert_close(corrected_avg, Duration::from_micros(60_500));
+        let max = *stats.max_corrected_used_time.lock().await;
+        assert_close(max, Duration::from_millis(110));
+        assert_eq!(stats.corrected_latencies.lock().await.len(), 1);
+    }
+
+    /// the histograms of 3 significant digits keep the values within 0.1%,
+    /// only the values below 2048µs are exact
+    fn assert_close(actual: Duration, expected: Duration) {
+        assert!(
+            actual.abs_diff(expected) <= expected / 1000,
+            "{actual:?} isn't close to {expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_message_with_intended_at() {
+        let intended_at = Instant::now();
+        let message = Message {
+            rsp_at: Instant::now(),
+            req_at: Instant::now(),
+            intended_at: None,
+            stage: None,
+            request: None,
+            req_bytes: 0,
+            rsp_bytes: 0,
+            body: None,
+            error: None,
+            response: Err(reqwest::Client::new()
+                .get("invalid url")
+                .build()
+                .unwrap_err()
+                .into()),
+            stream: None,
+        };
+        let message = message.with_intended_at(intended_at);
+        assert_eq!(message.intended_at, Some(intended_at));
+    }
+
+    #[tokio::test]
+    async fn test_statistics_assertions() {
+        use crate::assertion::parse_assertion;
+
+        let stats = Statistics::new().with_assertions(vec![
+            parse_assertion("status=2xx").unwrap(),
+            parse_assertion("body~ok").unwrap(),
+        ]);
+        *stats.requests.lock().await =
+            vec![("a".to_string(), GroupStatistics::new(DEFAULT_PRECISION))];
+        for (status, body) in [(200, "ok"), (500, "ok"), (200, "no"), (503, "")]
+        {
+            let response =
+                http::Response::builder().status(status).body("").unwrap();
+            let message = Message::new(
+                anyhow::Ok(Response::from(response)),
+                Instant::now(),
+                Instant::now(),
+            )
+            .with_body(Bytes::from(body))
+            .with_stage(0)
+            .with_request(0);
+            stats.handle_message(message).await;
+        }
+
+        // the responses failing an assertion aren't successes of their groups
+        let stages = stats.stages.lock().await;
+        assert_eq!(stages[0].total, 4);
+        assert_eq!(stages[0].total_success, 1);
+        let requests = stats.requests.lock().await;
+        assert_eq!(requests[0].1.total, 4);
+        assert_eq!(requests[0].1.total_success, 1);
+
+        assert_eq!(stats.total_success.load(Acquire), 1);
+        assert_eq!(stats.total_assertion_failed.load(Acquire), 3);
+        assert_eq!(stats.assertion_failures[0].load(Acquire), 2);
+        assert_eq!(stats.assertion_failures[1].load(Acquire), 2);
+        assert_eq!(stats.rsp2xx.load(Acquire), 2);
+        assert_eq!(stats.rsp5xx.load(Acquire), 2);
+        assert!(stats.errors.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_statistics_message_error() {
+        let stats = Statistics::new();
+        for _ in 0..2 {
+            let response =
+                http::Response::builder().status(200).body("").unwrap();
+            let message = Message::new(
+                anyhow::Ok(Response::from(response)),
+                Instant::now(),
+                Instant::now(),
+            )
+            .with_error("failed to extract `token`".to_string())
+            .with_stage(0);
+            stats.handle_message(message).await;
+        }
+
+        assert_eq!(stats.rsp2xx.load(Acquire), 2);
+        assert_eq!(stats.errors.lock().await["failed to extract `token`"], 2);
+        assert_eq!(stats.total.load(Acquire), 2);
+        assert_eq!(stats.total_success.load(Acquire), 0);
+        assert_eq!(stats.total_failed.load(Acquire), 2);
+        let stages = stats.stages.lock().await;
+        assert_eq!(stages[0].total, 2);
+        assert_eq!(stages[0].total_success, 0);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_protocols() {
+        let stats = Statistics::new();
+        for version in [http::Version::HTTP_2, http::Version::HTTP_2] {
+            let response = http::Response::builder()
+                .version(version)
+                .status(200)
+                .body("")
+                .unwrap();
+            let message = Message::new(
+                anyhow::Ok(Response::from(response)),
+                Instant::now(),
+                Instant::now(),
+            );
+            stats.handle_message(message).await;
+        }
+        let response = reqwest::Client::new().get("invalid url").send().await;
+        let message = Message::new(response, Instant::now(), Instant::now());
+        stats.handle_message(message).await;
+
+        let protocols = stats.protocols.lock().await;
+        assert_eq!(protocols.len(), 1);
+        assert_eq!(protocols["HTTP/2.0"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_handshake_error() {
+        let stats = Statistics::new();
+        for _ in 0..2 {
+            let response: anyhow::Result<Response> =
+                Err(HandshakeError("handshake timed out".to_string()).into());
+            let message =
+                Message::new(response, Instant::now(), Instant::now());
+            stats.handle_message(message).await;
+        }
+        let response: anyhow::Result<Response> =
+            Err(anyhow::anyhow!("operation timed out"));
+        stats
+            .handle_message(Message::new(
+                response,
+                Instant::now(),
+                Instant::now(),
+            ))
+            .await;
+
+        assert_eq!(stats.total.load(Acquire), 3);
+        assert_eq!(
+            stats.handshake_errors.lock().await["handshake timed out"],
+            2
+        );
+        assert_eq!(stats.errors.lock().await["operation timed out"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_generator_error() {
+        let stats = Statistics::new();
+        for _ in 0..3 {
+            stats
+                .handle_generator_error("json command timed out".to_string())
+                .await;
+        }
+        assert_eq!(
+            stats.generator_errors.lock().await["json command timed out"],
+            3
+        );
+        assert_eq!(stats.total.load(Acquire), 3);
+        assert_eq!(stats.total_success.load(Acquire), 0);
+        assert!(stats.errors.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_statistics_transfer() {
+        let stats = Statistics::new();
+        let response = reqwest::Client::new().get("invalid url").send().await;
+        let message = Message::new(response, Instant::now(), Instant::now())
+            .with_bytes(100, 2048);
+        stats.handle_message(message).await;
+        assert_eq!(stats.req_bytes.load(Acquire), 100);
+        assert_eq!(stats.rsp_bytes.load(Acquire), 2048);
+
+        *stats.started_at.lock().await =
+            Instant::now() - Duration::from_secs(2);
+        stats.stop_timer().await;
+        stats.calculate_transfer_per_second().await;
+        let transfer_per_second = *stats.transfer_per_second.lock().await;
+        assert!((transfer_per_second - 1024.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_group_statistics_summary() {
+        let mut stage = GroupStatistics::new(DEFAULT_PRECISION);
+        let started_at = Instant::now();
+        for i in 1..=10 {
+            let req_at = started_at + Duration::from_millis(i * 100);
+            let rsp_at = req_at + Duration::from_millis(i);
+            stage.record(req_at, rsp_at, i != 10);
+        }
+        stage.summary(&[0.5]);
+
+        assert_eq!(stage.total, 10);
+        assert_eq!(stage.total_success, 9);
+        assert_close(stage.min_req_used_time, Duration::from_millis(1));
+        assert_close(stage.max_req_used_time, Duration::from_millis(9));
+        assert_close(stage.avg_req_used_time, Duration::from_millis(5));
+        assert_eq!(stage.latencies.len(), 1);
+        assert!(stage.avg_req_per_second > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_handle_message_with_stage() {
+        let stats = Statistics::new();
+        let response = reqwest::Client::new().get("invalid url").send().await;
+        let message = Message::new(response, Instant::now(), Instant::now())
+            .with_stage(1);
+        stats.handle_message(message).await;
+
+        let stages = stats.stages.lock().await;
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].total, 0);
+        assert_eq!(stages[1].total, 1);
+        assert_eq!(stages[1].total_success, 0);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_handle_message_with_request() {
+        let stats = Statistics::new();
+        *stats.requests.lock().await = vec![
+            ("a".to_string(), GroupStatistics::new(DEFAULT_PRECISION)),
+            ("b".to_string(), GroupStatistics::new(DEFAULT_PRECISION)),
+        ];
+        for request in [1, 1, 0, 2] {
+            let response =
+                reqwest::Client::new().get("invalid url").send().await;
+            let message =
+                Message::new(response, Instant::now(), Instant::now())
+                    .with_request(request);
+            stats.handle_message(message).await;
+        }
+
+        let requests = stats.requests.lock().await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].1.total, 1);
+        assert_eq!(requests[1].1.total, 2);
+        assert_eq!(stats.get_total(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_statistics_latencies_are_rank_based() {
+        let stats = Statistics::with_precision(5);
+        let mut used_time = stats.used_time.lock().await;
+        for i in 1..=10_000 {
+            record(&mut used_time, Duration::from_micros(i));
+        }
+        drop(used_time);
+
+        stats.calculate_elapsed_time().await;
+        stats
+            .calculate_latencies(vec![0.5, 0.99, 0.999, 0.9999])
+            .await;
+
+        let latencies = stats.latencies.lock().await;
+        assert_eq!(
+            *latencies,
+            vec![
+                (0.5, Duration::from_micros(5_000)),
+                (0.99, Duration::from_micros(9_900)),
+                (0.999, Duration::from_micros(9_990)),
+                (0.9999, Duration::from_micros(9_999)),
+            ]
+        );
+        let min = *stats.min_req_used_time.lock().await;
+        let max = *stats.max_req_used_time.lock().await;
+        assert_eq!(min, Duration::from_micros(1));
+        assert_eq!(max, Duration::from_micros(10_000));
+    }
+
     #[tokio::test]
     async fn test_statistics_timer_per_second() {
         use std::sync::Arc;
@@ -663,20 +1673,22 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_statistics_clear_temporary_data() {
-        let stats = Statistics::new();
-
-        // Add some data
+    async fn test_statistics_histogram_has_fixed_memory() {
+        let stats = Statistics::with_precision(2);
         let mut used_time = stats.used_time.lock().await;
-        for i in 0..10 {
-            used_time.push(Duration::from_millis(i as u64));
+        let size = used_time.distinct_values();
+        for i in 0..100_000 {
+            record(&mut used_time, Duration::from_micros(i));
         }
-        drop(used_time);
-
-        stats.clear_temporary_data().await;
-
-        let used_time = stats.used_time.lock().await;
-        assert!(used_time.is_empty());
+        // values beyond the trackable range are saturated
+        record(&mut used_time, Duration::from_secs(7200));
+
+        assert_eq!(used_time.len(), 100_001);
+        assert_eq!(used_time.distinct_values(), size);
+        assert_eq!(
+            used_time.max(),
+            used_time.highest_equivalent(MAX_TRACKABLE_MICROS)
+        );
     }
 
     #[tokio::test]
@@ -713,6 +1725,21 @@ mod tests {
         assert_eq!(max, 90.0);
     }
 
+    #[tokio::test]
+    async fn test_statistics_calculate_min_per_second() {
+        let stats = Statistics::new();
+
+        // the last second is excluded
+        let mut req_per_second = stats.req_per_second.lock().await;
+        req_per_second.extend([30, 20, 40, 5]);
+        drop(req_per_second);
+
+        stats.calculate_min_per_second().await;
+
+        let min = *stats.min_req_per_second.lock().await;
+        assert_eq!(min, 20.0);
+    }
+
     #[tokio::test]
     async fn test_statistics_calculate_stdev_per_second() {
         let stats = Statistics::new();
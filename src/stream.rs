@@ -0,0 +1,317 @@
+//! mod stream reads the responses as streams of events with `--stream`, the
+//! events are the Server-Sent Events of the `text/event-stream` responses,
+//! which end with a blank line, and the lines of the other responses, such as
+//! the chunks of a token stream in json lines.
+//!
+//! Each stream is read until it ends, or until `--stream-events` events are
+//! received, or `--stream-duration` has passed since its request was sent,
+//! the response is dropped then, so the connection isn't reused.
+
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use reqwest::Response;
+use reqwest::header::CONTENT_TYPE;
+use tokio::time as ttime;
+
+use crate::Arg;
+use crate::request::response_head_size;
+
+/// [StreamLimits] is when to stop reading each stream
+#[derive(Debug, Clone)]
+pub(crate) struct StreamLimits {
+    events: Option<u64>,
+    duration: Option<Duration>,
+}
+
+impl StreamLimits {
+    /// the limits of `--stream`, None without it
+    pub(crate) fn new(arg: &Arg) -> anyhow::Result<Option<Self>> {
+        if !arg.stream {
+            return Ok(None);
+        }
+        // the streams are timed out by the client otherwise
+        if arg
+            .stream_duration
+            .is_some_and(|duration| duration >= arg.timeout)
+        {
+            anyhow::bail!("--stream-duration must be shorter than --timeout");
+        }
+        Ok(Some(Self {
+            events: arg.stream_events,
+            duration: arg.stream_duration,
+        }))
+    }
+}
+
+/// [Stream] is the timing of the events of a response
+#[derive(Debug, Clone)]
+pub(crate) struct Stream {
+    /// the time at which the headers of the response were received
+    pub(crate) headers_at: Instant,
+    /// the time at which each event was received
+    pub(crate) events: Vec<Instant>,
+    /// the time at which the stream ended, or stopped being read
+    pub(crate) ended_at: Instant,
+}
+
+/// [Events] splits the body into events, only the shape of the current line
+/// is kept
+#[derive(Debug)]
+struct Events {
+    /// whether the events are Server-Sent Events
+    sse: bool,
+    /// whether the current line has any byte, the carriage returns excluded
+    line: bool,
+    /// whether the current line is a comment of Server-Sent Events
+    comment: bool,
+    /// whether the current Server-Sent Event has any field
+    event: bool,
+}
+
+impl Events {
+    fn new(response: &Response) -> Self {
+        let sse = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value.trim_start().starts_with("text/event-stream")
+            });
+        Self {
+            sse,
+            line: false,
+            comment: false,
+            event: false,
+        }
+    }
+
+    /// feed a chunk of the body, returns the number of events it completes
+    fn feed(&mut self, chunk: &[u8]) -> u64 {
+        let mut completed = 0;
+        for byte in chunk {
+            match byte {
+                b'\n' => {
+                    completed += self.end_line() as u64;
+                },
+                b'\r' => {},
+                _ if !self.line => {
+                    self.line = true;
+                    self.comment = *byte == b':';
+                },
+                _ => {},
+            }
+        }
+        completed
+    }
+
+    /// end the current line, returns whether it completes an event
+    fn end_line(&mut self) -> bool {
+        let line = std::mem::take(&mut self.line);
+        if !self.sse {
+            return line;
+        }
+        // the comments, such as heartbeats, aren't fields
+        match line {
+            true => {
+                self.event |= !self.comment;
+                false
+            },
+            false => std::mem::take(&mut self.event),
+        }
+    }
+
+    /// end the body, the last line is an event unless it's an incomplete
+    /// Server-Sent Event, which is discarded
+    fn finish(&mut self) -> bool {
+        !self.sse && std::mem::take(&mut self.line)
+    }
+}
+
+/// read the response as a stream of events, returns the approximate number of
+/// bytes like [crate::request::drain_response], the body if `keep_body` is
+/// true, and the timing of the stream, the time limit is measured from
+/// `req_at`
+pub(crate) async fn read_stream(
+    response: &mut Response,
+    limits: &StreamLimits,
+    req_at: Instant,
+    keep_body: bool,
+) -> reqwest::Result<(u64, Option<Bytes>, Stream)> {
+    let mut stream = Stream {
+        headers_at: Instant::now(),
+        events: vec![],
+        ended_at: Instant::now(),
+    };
+    let mut size = response_head_size(response);
+    let mut body = keep_body.then(BytesMut::new);
+    let mut events = Events::new(response);
+    let deadline = limits.duration.map(|duration| req_at + duration);
+    let is_done = |stream: &Stream| {
+        limits
+            .events
+            .is_some_and(|limit| stream.events.len() as u64 >= limit)
+    };
+    while !is_done(&stream) {
+        let chunk = match deadline {
+            Some(deadline) => {
+                let deadline = ttime::Instant::from_std(deadline);
+                match ttime::timeout_at(deadline, response.chunk()).await {
+                    Ok(chunk) => chunk?,
+                    Err(_) => break,
+                }
+            },
+            None => response.chunk().await?,
+        };
+        let now = Instant::now();
+        let Some(chunk) = chunk else {
+            if events.finish() {
+                stream.events.push(now);
+            }
+            break;
+        };
+        size += chunk.len() as u64;
+        if let Some(body) = body.as_mut() {
+            body.extend_from_slice(&chunk);
+        }
+        // the events completed by one chunk are received at once
+        let completed = events.feed(&chunk);
+        let limit = limits.events.unwrap_or(u64::MAX);
+        let room = limit.saturating_sub(stream.events.len() as u64);
+        stream
+            .events
+            .extend(std::iter::repeat_n(now, completed.min(room) as usize));
+    }
+    stream.ended_at = Instant::now();
+    Ok((size, body.map(BytesMut::freeze), stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, mpsc};
+
+    use clap::Parser;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::Task;
+
+    fn events(sse: bool) -> Events {
+        Events {
+            sse,
+            line: false,
+            comment: false,
+            event: false,
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let mut sse = events(true);
+        assert_eq!(sse.feed(b": ping\n\nid: 1\ndata: a\n\ndata: b\r\n"), 1);
+        assert_eq!(sse.feed(b"\r"), 0);
+        assert_eq!(sse.feed(b"\ndata: c\n\n\n\nda"), 2);
+        // the incomplete event is discarded
+        assert!(!sse.finish());
+
+        let mut lines = events(false);
+        assert_eq!(lines.feed(b"{\"token\": 1}\n\r\n{\"tok"), 1);
+        assert_eq!(lines.feed(b"en\": 2}\r\n{\"token\": 3}"), 1);
+        assert!(lines.finish());
+        assert!(!lines.finish());
+    }
+
+    /// start a HTTP server in the background, which sends 3 Server-Sent
+    /// Events 10ms apart for each request, and closes the connection
+    fn start_server() -> SocketAddr {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream));
+                }
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    async fn serve(mut stream: TcpStream) {
+        let mut request = vec![0; 4096];
+        if stream.read(&mut request).await.is_err() {
+            return;
+        }
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+            connection: close\r\ntransfer-encoding: chunked\r\n\r\n";
+        if stream.write_all(head.as_bytes()).await.is_err() {
+            return;
+        }
+        for id in 0..3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let event = format!(": ping\n\nid: {id}\ndata: {{}}\n\n");
+            let chunk = format!("{:x}\r\n{event}\r\n", event.len());
+            if stream.write_all(chunk.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+        let _ = stream.write_all(b"0\r\n\r\n").await;
+    }
+
+    #[test]
+    fn test_stream_task() {
+        let addr = start_server();
+        let url = format!("http://{addr}/events");
+        let run = |args: &[&str]| {
+            let arg = Arg::parse_from(
+                ["rsb", "-n", "4", "-c", "2", "--stream"]
+                    .iter()
+                    .chain(args)
+                    .chain([&url.as_str()]),
+            );
+            let task = Arc::new(Task::new(arg, None).unwrap()).run().unwrap();
+            let text = task.clone().text_output().unwrap();
+            (task.json_output().unwrap(), text)
+        };
+
+        let (output, text) = run(&["-l"]);
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(output.rsp2xx, 4);
+        let stream = output.stream.unwrap();
+        assert_eq!(stream.ttfb.total, 4);
+        assert_eq!(stream.first_event.total, 4);
+        assert_eq!(stream.gaps.total, 8);
+        assert_eq!(stream.duration.total, 4);
+        assert_eq!(stream.events.total, 12);
+        assert_eq!(stream.events.max, 3);
+        assert_eq!(stream.events.percentiles.len(), 4);
+        for row in ["TTFB", "First event", "Event gap", "Events"] {
+            assert!(text.contains(&format!("\n  {row} ")), "{text}");
+        }
+        assert!(text.contains("Event Gap Distribution"));
+        assert!(text.contains("Events Distribution"));
+
+        // each stream stops after its first event
+        let (output, _) = run(&["--stream-events", "1"]);
+        let stream = output.stream.unwrap();
+        assert_eq!(stream.events.total, 4);
+        assert_eq!(stream.gaps.total, 0);
+
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--stream",
+            "--stream-duration",
+            "30s",
+            &url,
+        ]);
+        assert!(Task::new(arg, None).is_err());
+    }
+}
@@ -24,33 +24,78 @@
 //! writeln!(&mut io::stdout(), "{result}")?;
 //! ```
 
+use std::borrow::Cow;
 use std::cmp::min;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
 use colored::Colorize;
 use indicatif::ProgressBar;
 use num_cpus;
-use reqwest::Client;
+use reqwest::header::{AUTHORIZATION, COOKIE, HeaderMap, HeaderValue};
+use reqwest::{Client, Request, Response};
 use tokio::{
     self, runtime,
     sync::{self as tsync, mpsc},
 };
 
 use crate::Arg;
-use crate::client::build_client;
-use crate::dispatcher::DurationDispatcher;
+use crate::arg::Stage;
+use crate::assertion::Assertion;
+use crate::auth::OAuth2;
+use crate::client::{build_clients, check_version, default_headers};
+use crate::cookie::CookieJar;
+use crate::data::DataFile;
 use crate::dispatcher::{CountDispatcher, Dispatcher};
+use crate::dispatcher::{DurationDispatcher, StageDispatcher};
+use crate::extract::Payload;
+use crate::generator::GeneratorError;
+use crate::grpc::GrpcClient;
+use crate::har;
+#[cfg(feature = "http3")]
+use crate::http3::Http3Client;
 use crate::limiter::Limiter;
-use crate::output::{Output, sync_text_output};
-use crate::request::build_request;
+use crate::openapi;
+use crate::output::{Output, sync_text_output, write_thresholds};
+use crate::request::{
+    PreparedRequest, build_scenario_request, drain_response, request_size,
+};
+use crate::scenario::Scenario;
+use crate::sign::{Signer, build_signer};
 use crate::statistics::{Message, Statistics};
+use crate::stream::{StreamLimits, read_stream};
+use crate::template::{Context, Templates};
+use crate::websocket::{Socket, WebSocket};
 
 /// [Task] indicates a task to be performed
 pub struct Task {
     arg: Arg,
-    client: Client,
+    /// the clients shared by the workers, each of them keeps its own
+    /// connections, see [build_clients]
+    clients: Vec<Client>,
+    /// the client of `--http3`, which sends the requests built by the clients
+    #[cfg(feature = "http3")]
+    http3: Option<Http3Client>,
+    /// sends the messages of the `ws://` and `wss://` urls
+    websocket: Option<WebSocket>,
+    /// calls the method of `--grpc-method`
+    grpc: Option<GrpcClient>,
+    /// when to stop reading the responses as streams with `--stream`
+    stream: Option<StreamLimits>,
+    scenario: Option<Scenario>,
+    data: Option<DataFile>,
+    /// the cookies which every cookie jar starts with, None without
+    /// `--cookie-jar`
+    cookies: Option<CookieJar>,
+    /// the access token of `--oauth2-token-url`
+    oauth2: Option<Arc<OAuth2>>,
+    /// signs each request once all of its headers are set
+    signer: Option<Box<dyn Signer>>,
+    templates: Templates,
+    /// the request given on the command line, built once
+    prepared: OnceLock<PreparedRequest>,
+    seq: AtomicU64,
     statistics: Statistics,
     is_canceled: AtomicBool,
     progress_bar: Option<ProgressBar>,
@@ -61,30 +106,49 @@ pub struct Task {
 fn create_count_dispatcher(
     total: u64,
     rate: &Option<u16>,
+    open_model: bool,
 ) -> Box<dyn Dispatcher<Limiter = Limiter>> {
-    let count_dispatcher = CountDispatcher::new(total, rate);
+    let count_dispatcher = CountDispatcher::new(total, rate, open_model);
     Box::new(count_dispatcher)
 }
 
 fn create_duration_dispatcher(
     duration: Duration,
     rate: &Option<u16>,
+    open_model: bool,
 ) -> Box<dyn Dispatcher<Limiter = Limiter>> {
-    let duration_dispatcher = DurationDispatcher::new(duration, rate);
+    let duration_dispatcher =
+        DurationDispatcher::new(duration, rate, open_model);
     Box::new(duration_dispatcher)
 }
 
+fn create_stage_dispatcher(
+    stages: Vec<Stage>,
+    connections: u16,
+) -> Box<dyn Dispatcher<Limiter = Limiter>> {
+    let stage_dispatcher = StageDispatcher::new(stages, connections);
+    Box::new(stage_dispatcher)
+}
+
 fn create_dispatcher(
     arg: &Arg,
 ) -> Arc<tsync::RwLock<Box<dyn Dispatcher<Limiter = Limiter>>>> {
-    if let Some(requests) = arg.requests {
+    if !arg.stages.is_empty() {
+        Arc::new(tsync::RwLock::new(create_stage_dispatcher(
+            arg.stages.clone(),
+            arg.connections,
+        )))
+    } else if let Some(requests) = arg.requests {
         Arc::new(tsync::RwLock::new(create_count_dispatcher(
-            requests, &arg.rate,
+            requests,
+            &arg.rate,
+            arg.open_model,
         )))
     } else {
         Arc::new(tsync::RwLock::new(create_duration_dispatcher(
             arg.duration.unwrap(),
             &arg.rate,
+            arg.open_model,
         )))
     }
 }
@@ -99,18 +163,89 @@ impl Task {
     /// [progress_bar][`indicatif::ProgressBar`] - it is an optional value, when
     /// it exists, go back and update the progress
     pub fn new(
-        arg: Arg,
+        mut arg: Arg,
         progress_bar: Option<ProgressBar>,
     ) -> anyhow::Result<Self> {
-        let client = build_client(&arg)?;
+        arg.apply_from_curl()?;
+        let clients = build_clients(&arg)?;
+        #[cfg(feature = "http3")]
+        let http3 = arg.http3.then(|| Http3Client::new(&arg)).transpose()?;
+        #[cfg(not(feature = "http3"))]
+        if arg.http3 {
+            anyhow::bail!("`--http3` needs rsb built with the `http3` feature");
+        }
+        let websocket = arg
+            .is_websocket()
+            .then(|| WebSocket::new(&arg))
+            .transpose()?;
+        let grpc = arg
+            .grpc_method
+            .is_some()
+            .then(|| GrpcClient::new(&arg))
+            .transpose()?;
+        let stream = StreamLimits::new(&arg)?;
         let dispatcher = create_dispatcher(&arg);
+        let data = arg
+            .data_file
+            .as_ref()
+            .map(|path| DataFile::load(path, arg.data_mode, arg.data_once))
+            .transpose()?;
+        let variables = data.as_ref().map_or(&[][..], |data| data.columns());
+        let scenario = match (&arg.scenario, &arg.har) {
+            (Some(path), _) => {
+                Some(Scenario::load(path, arg.url.as_deref(), variables)?)
+            },
+            (None, Some(path)) => {
+                Some(har::load(path, arg.url.as_deref(), arg.har_mode)?)
+            },
+            (None, None) => arg
+                .openapi
+                .as_ref()
+                .map(|path| openapi::load(path, arg.url.as_deref(), variables))
+                .transpose()?,
+        };
+        let templates = Templates::compile(&arg, variables)?;
+        let cookies = match (arg.cookie_jar, &arg.cookie_file) {
+            (true, Some(path)) => Some(CookieJar::load(path)?),
+            (true, None) => Some(CookieJar::default()),
+            (false, _) => None,
+        };
+        let oauth2 = OAuth2::new(&arg)?.map(Arc::new);
+        let signer = build_signer(&arg, default_headers(&arg)?)?;
+        let mut statistics = Statistics::with_precision(arg.precision)
+            .with_assertions(arg.assertions.clone());
+        if let Some(scenario) = &scenario {
+            statistics = statistics.with_requests(scenario.names());
+        }
+        if websocket.is_some() {
+            statistics = statistics.with_websocket();
+        }
+        if grpc.is_some() {
+            statistics = statistics.with_grpc();
+        }
+        if stream.is_some() {
+            statistics = statistics.with_stream();
+        }
 
         Ok(Self {
+            statistics,
             arg,
-            client,
+            clients,
+            #[cfg(feature = "http3")]
+            http3,
+            websocket,
+            grpc,
+            stream,
+            scenario,
+            data,
+            cookies,
+            oauth2,
+            signer,
+            templates,
+            prepared: OnceLock::new(),
+            seq: AtomicU64::new(0),
             dispatcher,
             progress_bar,
-            statistics: Statistics::new(),
             is_canceled: AtomicBool::new(false),
             is_workers_done: AtomicBool::new(false),
         })
@@ -122,7 +257,7 @@ impl Task {
         }
         if self.arg.requests.is_some() {
             self.update_count_progress_bar().await;
-        } else if self.arg.duration.is_some() {
+        } else if self.arg.test_duration().is_some() {
             self.update_duration_progress_bar().await;
         }
     }
@@ -142,7 +277,7 @@ impl Task {
     }
 
     async fn update_duration_progress_bar(self: Arc<Self>) {
-        let total = self.arg.duration.unwrap().as_secs();
+        let total = self.arg.test_duration().unwrap().as_secs();
         let mut current = 0;
         loop {
             current += 1;
@@ -173,30 +308,484 @@ impl Task {
 
     async fn worker(
         self: Arc<Self>,
+        worker_id: usize,
         sender: mpsc::Sender<Message>,
     ) -> anyhow::Result<()> {
+        let client = &self.clients[worker_id % self.clients.len()];
+        let keep_body = self.arg.assertions.iter().any(Assertion::needs_body);
+        // the position of this connection in the sequence of scenario
+        let mut cursor = 0;
+        // the row of the data file, which is kept during one iteration
+        let mut row: &[String] = &[];
+        // the values extracted from the responses during one iteration
+        let mut extracted = vec![
+            String::new();
+            self.scenario.as_ref().map_or(0, |scenario| {
+                scenario.extracted().len()
+            })
+        ];
+        // the cookies of this connection
+        let mut jar = self.cookies.clone();
+        // the cookies given by `-H`, which are default headers of the client,
+        // so they are replaced by the header of the jar unless merged
+        let given_cookie = self
+            .arg
+            .headers
+            .iter()
+            .filter_map(|header| header.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("cookie"))
+            .map(|(_, value)| value.trim().to_string());
         loop {
-            if !self.dispatcher.read().await.try_apply_job().await {
-                break;
+            // the delay of a timed replay is waited before the job is taken,
+            // so that it isn't counted in the latency corrected from the
+            // intended time
+            if let Some(scenario) = &self.scenario {
+                let delay = scenario.delay(cursor);
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            let (intended_at, stage) = {
+                let dispatcher = self.dispatcher.read().await;
+                match dispatcher.try_apply_job().await {
+                    Some(intended_at) => {
+                        (intended_at, dispatcher.current_stage())
+                    },
+                    None => break,
+                }
+            };
+            if self
+                .scenario
+                .as_ref()
+                .is_none_or(|scenario| scenario.is_iteration_start(cursor))
+            {
+                // stop once all rows are used with `--data-once`
+                if let Some(data) = &self.data {
+                    match data.next(worker_id) {
+                        Some(next) => row = next,
+                        None => {
+                            self.dispatcher.read().await.complete_job();
+                            break;
+                        },
+                    }
+                }
+                extracted.iter_mut().for_each(String::clear);
+                if self.arg.cookie_reset {
+                    jar.clone_from(&self.cookies);
+                }
+            }
+            let values = match extracted.is_empty() {
+                true => Cow::Borrowed(row),
+                false => Cow::Owned([row, &extracted].concat()),
+            };
+
+            let context = Context {
+                worker_id,
+                seq: self.seq.fetch_add(1, Ordering::Relaxed),
+                values: &values,
+            };
+            // pick one of the named requests in scenario
+            let built = match &self.scenario {
+                Some(scenario) => {
+                    let index = scenario.pick(&mut cursor);
+                    build_scenario_request(
+                        &scenario.requests[index],
+                        &scenario.templates[index],
+                        &context,
+                        client,
+                    )
+                    .map(|request| (request, Some(index)))
+                },
+                None => {
+                    let prepared = self
+                        .prepared
+                        .get()
+                        .expect("prepared before the workers start");
+                    prepared
+                        .build(&self.arg, &self.templates, &context, client)
+                        .await
+                        .map(|request| (request, None))
+                },
+            };
+            let built = built.and_then(|(mut request, index)| {
+                self.finish_headers(
+                    &mut request,
+                    jar.as_ref(),
+                    given_cookie.as_deref(),
+                )?;
+                Ok((request, index))
+            });
+            let (request, index) = match built {
+                Ok(built) => built,
+                Err(e) => {
+                    self.dispatcher.read().await.complete_job();
+                    // no request is sent without a body
+                    match e.downcast::<GeneratorError>() {
+                        Ok(GeneratorError::Failed(reason)) => {
+                            self.statistics
+                                .handle_generator_error(reason)
+                                .await;
+                        },
+                        Ok(GeneratorError::Exhausted) => break,
+                        // the request which can't be built, such as with an
+                        // invalid rendered header, is a failed request
+                        Err(e) => {
+                            let now = Instant::now();
+                            let mut message = Message::new(Err(e), now, now);
+                            if let Some(stage) = stage {
+                                message = message.with_stage(stage);
+                            }
+                            sender.send(message).await?;
+                        },
+                    }
+                    continue;
+                },
+            };
+            let req_bytes = request_size(&request);
+
+            let req_at = Instant::now();
+            let mut response = self.execute(client, request).await;
+            // the body is read inside the timed section, so that the latency
+            // covers the whole response
+            let mut rsp_bytes = 0;
+            let mut body = None;
+            let mut stream = None;
+            let needs_body = keep_body
+                || index.is_some_and(|index| {
+                    self.scenario.as_ref().unwrap().needs_body(index)
+                });
+            if let Ok(rsp) = response.as_mut() {
+                let drained = match &self.stream {
+                    Some(limits) => {
+                        read_stream(rsp, limits, req_at, needs_body).await.map(
+                            |(size, bytes, timing)| {
+                                stream = Some(timing);
+                                (size, bytes)
+                            },
+                        )
+                    },
+                    None => {
+                        drain_response(rsp, !self.arg.skip_body, needs_body)
+                            .await
+                    },
+                };
+                match drained {
+                    Ok((size, bytes)) => (rsp_bytes, body) = (size, bytes),
+                    Err(e) => response = Err(e.into()),
+                }
+            }
+            let rsp_at = Instant::now();
+            self.dispatcher.read().await.complete_job();
+            if let Some(jar) = jar.as_mut()
+                && let Ok(rsp) = &response
+            {
+                jar.store(rsp.url(), rsp.headers());
+            }
+
+            // the rest of the iteration is skipped if the values can't be
+            // extracted, as the later steps depend on them
+            let mut error = None;
+            if let (Some(scenario), Some(index)) = (&self.scenario, index) {
+                let result = match &response {
+                    Ok(rsp) => {
+                        let payload = Payload::new(
+                            rsp.headers(),
+                            body.as_deref().unwrap_or_default(),
+                        );
+                        scenario
+                            .extract(index, &payload, &mut extracted)
+                            .map_err(Some)
+                    },
+                    // the error of the request is already counted
+                    Err(_) if scenario.has_extract(index) => Err(None),
+                    Err(_) => Ok(()),
+                };
+                if let Err(e) = result {
+                    error = e;
+                    cursor = 0;
+                }
+            }
+
+            let mut message = Message::new(response, req_at, rsp_at)
+                .with_bytes(req_bytes, rsp_bytes);
+            if keep_body && let Some(body) = body {
+                message = message.with_body(body);
+            }
+            if let Some(stream) = stream {
+                message = message.with_stream(stream);
             }
+            if let Some(error) = error {
+                message = message.with_error(error);
+            }
+            // in open model, the latency is also measured from the time at
+            // which the request should have been sent
+            if self.arg.open_model {
+                message = message.with_intended_at(intended_at);
+            }
+            if let Some(stage) = stage {
+                message = message.with_stage(stage);
+            }
+            if let (Some(scenario), Some(index)) = (&self.scenario, index) {
+                message = message.with_request(scenario.group(index));
+            }
+            sender.send(message).await?;
+        }
+        Ok(())
+    }
+
+    /// set the headers which are added to the built request, the token of
+    /// oauth2 and the cookies of the jar, the request is signed once all of
+    /// its headers are final
+    fn finish_headers(
+        &self,
+        request: &mut Request,
+        jar: Option<&CookieJar>,
+        given_cookie: Option<&str>,
+    ) -> anyhow::Result<()> {
+        // the token of oauth2 doesn't replace the one given by request
+        if let Some(oauth2) = &self.oauth2
+            && !request.headers().contains_key(AUTHORIZATION)
+            && let Some(authorization) = oauth2.header()
+        {
+            request.headers_mut().insert(AUTHORIZATION, authorization);
+        }
+        if let Some(jar) = jar
+            && let Some(cookie) = jar.header(request.url())
+        {
+            let given = match request.headers().get(COOKIE) {
+                Some(given) => Some(given.to_str()?),
+                None => given_cookie,
+            };
+            let cookie = match given {
+                Some(given) => format!("{given}; {cookie}"),
+                None => cookie,
+            };
+            request
+                .headers_mut()
+                .insert(COOKIE, HeaderValue::from_str(&cookie)?);
+        }
+        if let Some(signer) = &self.signer {
+            signer.sign(request)?;
+        }
+        Ok(())
+    }
+
+    /// the worker of the WebSocket urls, which keeps a socket, and sends a
+    /// message over it for each job, the socket is opened again once it's
+    /// closed by the server
+    async fn ws_worker(
+        self: Arc<Self>,
+        worker_id: usize,
+        sender: mpsc::Sender<Message>,
+    ) -> anyhow::Result<()> {
+        let websocket = self.websocket.as_ref().expect("WebSocket url");
+        let mut socket = None;
+        let mut row: &[String] = &[];
+        loop {
+            let (intended_at, stage) = {
+                let dispatcher = self.dispatcher.read().await;
+                match dispatcher.try_apply_job().await {
+                    Some(intended_at) => {
+                        (intended_at, dispatcher.current_stage())
+                    },
+                    None => break,
+                }
+            };
+            if let Some(data) = &self.data {
+                match data.next(worker_id) {
+                    Some(next) => row = next,
+                    None => {
+                        self.dispatcher.read().await.complete_job();
+                        break;
+                    },
+                }
+            }
+            let context = Context {
+                worker_id,
+                seq: self.seq.fetch_add(1, Ordering::Relaxed),
+                values: row,
+            };
+            let payload = websocket.message(&self.templates, &context);
+            let req_bytes = payload.len() as u64;
+
+            let mut message = 'message: {
+                // the socket is opened by the first job, and again once it's
+                // closed
+                let opened = match socket.as_mut() {
+                    Some(opened) => opened,
+                    None => {
+                        let connect_at = Instant::now();
+                        let result = self.ws_connect(websocket, &context).await;
+                        let connected_at = Instant::now();
+                        self.statistics
+                            .handle_connect(
+                                connect_at,
+                                connected_at,
+                                result.is_ok(),
+                            )
+                            .await;
+                        match result {
+                            Ok(opened) => socket.insert(opened),
+                            // the message isn't sent without the socket
+                            Err(e) => {
+                                self.dispatcher.read().await.complete_job();
+                                break 'message Message::reply(
+                                    Err(e),
+                                    connect_at,
+                                    connected_at,
+                                );
+                            },
+                        }
+                    },
+                };
+                let req_at = Instant::now();
+                let round_trip = websocket.round_trip(opened, payload).await;
+                let rsp_at = Instant::now();
+                self.dispatcher.read().await.complete_job();
+                self.statistics.handle_unmatched(round_trip.unmatched);
+                // the socket is dropped once a message fails
+                if round_trip.result.is_err() {
+                    socket = None;
+                    if let Some(code) = round_trip.closed {
+                        self.statistics.handle_close(code).await;
+                    }
+                }
+                Message::reply(round_trip.result, req_at, rsp_at)
+                    .with_bytes(req_bytes, round_trip.rsp_bytes)
+            };
+            if self.arg.open_model {
+                message = message.with_intended_at(intended_at);
+            }
+            if let Some(stage) = stage {
+                message = message.with_stage(stage);
+            }
+            sender.send(message).await?;
+        }
+        if let Some(opened) = socket {
+            let code = websocket.close(opened).await;
+            self.statistics.handle_close(code).await;
+        }
+        Ok(())
+    }
 
-            let request = build_request(&self.arg, &self.client).await?;
+    /// open the socket of a worker, the templated headers and the token of
+    /// oauth2 are set for each socket
+    async fn ws_connect(
+        &self,
+        websocket: &WebSocket,
+        context: &Context<'_>,
+    ) -> anyhow::Result<Socket> {
+        let url = self.templates.url.as_ref().expect("WebSocket url");
+        let headers = self.render_headers(context)?;
+        websocket.connect(&url.render(context), headers).await
+    }
+
+    /// the worker of `--grpc-method`, which calls the method for each job
+    async fn grpc_worker(
+        self: Arc<Self>,
+        worker_id: usize,
+        sender: mpsc::Sender<Message>,
+    ) -> anyhow::Result<()> {
+        let grpc = self.grpc.as_ref().expect("gRPC method");
+        let mut row: &[String] = &[];
+        loop {
+            let (intended_at, stage) = {
+                let dispatcher = self.dispatcher.read().await;
+                match dispatcher.try_apply_job().await {
+                    Some(intended_at) => {
+                        (intended_at, dispatcher.current_stage())
+                    },
+                    None => break,
+                }
+            };
+            if let Some(data) = &self.data {
+                match data.next(worker_id) {
+                    Some(next) => row = next,
+                    None => {
+                        self.dispatcher.read().await.complete_job();
+                        break;
+                    },
+                }
+            }
+            let context = Context {
+                worker_id,
+                seq: self.seq.fetch_add(1, Ordering::Relaxed),
+                values: row,
+            };
+            let prepared =
+                grpc.message(&self.templates, &context).and_then(|payload| {
+                    Ok((payload, self.render_headers(&context)?))
+                });
 
             let req_at = Instant::now();
-            let response = self.client.execute(request).await;
+            let (result, req_bytes, rsp_bytes) = match prepared {
+                Ok((payload, headers)) => {
+                    let req_bytes = payload.len() as u64;
+                    match grpc.call(worker_id, payload, &headers).await {
+                        Ok(call) => (Ok(call.code), req_bytes, call.rsp_bytes),
+                        Err(e) => (Err(e), req_bytes, 0),
+                    }
+                },
+                // the call isn't made without its message
+                Err(e) => (Err(e), 0, 0),
+            };
+            let rsp_at = Instant::now();
             self.dispatcher.read().await.complete_job();
-            let message = Message::new(response, req_at, Instant::now());
+
+            let mut message = Message::grpc(result, req_at, rsp_at)
+                .with_bytes(req_bytes, rsp_bytes);
+            if self.arg.open_model {
+                message = message.with_intended_at(intended_at);
+            }
+            if let Some(stage) = stage {
+                message = message.with_stage(stage);
+            }
             sender.send(message).await?;
         }
         Ok(())
     }
 
+    /// the templated headers and the token of oauth2, which are rendered for
+    /// the transports which aren't built by reqwest
+    fn render_headers(
+        &self,
+        context: &Context<'_>,
+    ) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.templates.headers {
+            headers.insert(
+                name.clone(),
+                HeaderValue::from_str(&value.render(context))?,
+            );
+        }
+        if let Some(oauth2) = &self.oauth2
+            && !headers.contains_key(AUTHORIZATION)
+            && let Some(authorization) = oauth2.header()
+        {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        Ok(headers)
+    }
+
+    /// send the request by the client, or over HTTP/3 with `--http3`
+    async fn execute(
+        &self,
+        client: &Client,
+        request: Request,
+    ) -> anyhow::Result<Response> {
+        #[cfg(feature = "http3")]
+        if let Some(http3) = &self.http3 {
+            return http3.execute(request).await;
+        }
+        check_version(&self.arg, client.execute(request).await?)
+    }
+
     /// get the text output after task execution
     ///
     /// ```text
-    /// Statistics         Avg          Stdev          Max
-    ///   Reqs/sec       15197.11       583.93       15817.00
-    ///   Latency         3.25ms        2.04ms       56.11ms
+    /// Statistics         Avg          Stdev          Min           Max
+    ///   Reqs/sec       15197.11       583.93       14210.00      15817.00
+    ///   Latency         3.25ms        2.04ms        412.00µs      56.11ms
     ///   Latency Distribution
     ///      50%      2.10ms
     ///      75%      2.49ms
@@ -208,13 +797,26 @@ impl Task {
     ///   Throughput:   15388.50/s
     /// ```
     pub fn text_output(self: Arc<Self>) -> anyhow::Result<String> {
-        sync_text_output(&self.statistics, &self.arg)
+        let mut output = sync_text_output(&self.statistics, &self.arg)?;
+        if !self.arg.thresholds.is_empty() {
+            let thresholds = self.json_output()?.thresholds;
+            write_thresholds(&mut output, &thresholds)?;
+        }
+        Ok(output)
     }
 
     /// returns a structure that can be serialized into json, and users can also
-    /// customize it
+    /// customize it, the criteria given by `--threshold` are checked against
+    /// it, see [Output::thresholds]
     pub fn json_output(self: Arc<Self>) -> anyhow::Result<Output> {
-        Output::sync_from_statistics(&self.statistics)
+        let mut output = Output::sync_from_statistics(&self.statistics)?;
+        output.thresholds = self
+            .arg
+            .thresholds
+            .iter()
+            .map(|threshold| threshold.check(&output))
+            .collect();
+        Ok(output)
     }
 
     async fn rcv_worker_message(
@@ -259,6 +861,27 @@ impl Task {
         rt.block_on(async {
             let (tx, rx) = mpsc::channel::<Message>(500);
 
+            // the token is fetched before any requests are sent
+            if let Some(oauth2) = &self.oauth2 {
+                oauth2.clone().start().await?;
+            }
+
+            // the files and the command of the body are read once
+            if self.scenario.is_none()
+                && self.websocket.is_none()
+                && self.grpc.is_none()
+            {
+                // the clients only differ by their connections, so the
+                // request built by one of them is sent by all
+                let prepared = PreparedRequest::new(
+                    &self.arg,
+                    &self.templates,
+                    &self.clients[0],
+                )
+                .await?;
+                let _ = self.prepared.set(prepared);
+            }
+
             // start workers by connection number
             let mut jobs = Vec::with_capacity(self.arg.connections as usize);
 
@@ -284,8 +907,15 @@ impl Task {
             });
 
             // start all worker and send request
-            for _ in 0..self.arg.connections {
-                jobs.push(tokio::spawn(self.clone().worker(tx.clone())));
+            for worker_id in 0..self.arg.connections as usize {
+                let task = self.clone();
+                jobs.push(if self.websocket.is_some() {
+                    tokio::spawn(task.ws_worker(worker_id, tx.clone()))
+                } else if self.grpc.is_some() {
+                    tokio::spawn(task.grpc_worker(worker_id, tx.clone()))
+                } else {
+                    tokio::spawn(task.worker(worker_id, tx.clone()))
+                });
             }
 
             // handle statistics
@@ -336,12 +966,17 @@ impl Task {
 mod tests {
     use std::time::Duration;
 
+    use clap::Parser;
+
     use super::*;
-    use crate::arg::{Method, OutputFormat};
+    use crate::arg::{
+        DataMode, Method, OutputFormat, ReplayMode, SignatureEncoding,
+    };
+    use crate::test_util::write_temp;
 
     #[test]
     fn test_create_count_dispatcher() {
-        let dispatcher = create_count_dispatcher(100, &Some(10));
+        let dispatcher = create_count_dispatcher(100, &Some(10), false);
         // Just test that it creates without panicking
         let _ = dispatcher;
     }
@@ -349,7 +984,19 @@ mod tests {
     #[test]
     fn test_create_duration_dispatcher() {
         let duration = Duration::from_secs(60);
-        let _dispatcher = create_duration_dispatcher(duration, &Some(10));
+        let _dispatcher =
+            create_duration_dispatcher(duration, &Some(10), false);
+        // Just test that it creates without panicking
+    }
+
+    #[test]
+    fn test_create_stage_dispatcher() {
+        let stages = vec![Stage {
+            duration: Duration::from_secs(60),
+            rate: 10,
+            connections: None,
+        }];
+        let _dispatcher = create_stage_dispatcher(stages, 10);
         // Just test that it creates without panicking
     }
 
@@ -363,10 +1010,16 @@ mod tests {
             timeout: Duration::from_secs(30),
             latencies: false,
             percentiles: vec![],
+            precision: 3,
             method: Method::Get,
             disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
             headers: vec![],
             rate: None,
+            open_model: false,
+            stages: vec![],
             cert: None,
             key: None,
             insecure: false,
@@ -375,9 +1028,49 @@ mod tests {
             json_file: None,
             json_body: None,
             json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
             form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
             mp: vec![],
             mp_file: vec![],
+            rebuild_body: false,
             output_format: OutputFormat::Text,
             completions: None,
         };
@@ -397,10 +1090,16 @@ mod tests {
             timeout: Duration::from_secs(30),
             latencies: false,
             percentiles: vec![],
+            precision: 3,
             method: Method::Get,
             disable_keep_alive: false,
+            skip_body: false,
+            assertions: vec![],
+            thresholds: vec![],
             headers: vec![],
             rate: None,
+            open_model: false,
+            stages: vec![],
             cert: None,
             key: None,
             insecure: false,
@@ -409,9 +1108,49 @@ mod tests {
             json_file: None,
             json_body: None,
             json_command: None,
+            http1_only: false,
+            http2: false,
+            http2_prior_knowledge: false,
+            http2_max_streams: None,
+            http3: false,
+            ws_match: None,
+            grpc_method: None,
+            proto: None,
+            import_paths: vec![],
+            protoset: None,
+            stream: false,
+            stream_events: None,
+            stream_duration: None,
+            json_generator: false,
+            json_command_timeout: Duration::from_secs(10),
             form: vec![],
+            scenario: None,
+            from_curl: None,
+            har: None,
+            har_mode: ReplayMode::Ordered,
+            openapi: None,
+            openapi_list: false,
+            cookie_jar: false,
+            cookie_file: None,
+            cookie_reset: false,
+            basic: None,
+            bearer: None,
+            oauth2_token_url: None,
+            oauth2_client_id: None,
+            oauth2_client_secret: None,
+            oauth2_scope: None,
+            aws_sigv4: None,
+            hmac_secret: None,
+            hmac_header: "X-Signature".to_string(),
+            hmac_timestamp_header: "X-Timestamp".to_string(),
+            hmac_signed_headers: vec![],
+            hmac_encoding: SignatureEncoding::Hex,
+            data_file: None,
+            data_mode: DataMode::Sequential,
+            data_once: false,
             mp: vec![],
             mp_file: vec![],
+            rebuild_body: false,
             output_format: OutputFormat::Text,
             completions: None,
         };
@@ -420,4 +1159,115 @@ mod tests {
         // Just test that it creates without panicking
         let _ = dispatcher;
     }
+
+    #[test]
+    fn test_generator_errors() {
+        // nothing listens on the port, the sent requests fail to connect
+        let run = |args: &[&str]| {
+            let arg = Arg::parse_from(
+                ["rsb", "-n", "3", "-c", "2", "-m", "POST"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["http://127.0.0.1:1/"]),
+            );
+            let task = Arc::new(Task::new(arg, None).unwrap()).run().unwrap();
+            (task.clone().json_output().unwrap(), task)
+        };
+
+        let (output, task) =
+            run(&["--json-command", "sh -c 'exit 1'", "--rebuild-body"]);
+        assert_eq!(output.generator_errors.values().sum::<u64>(), 3);
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(task.statistics.get_total(), 3);
+
+        // the run stops once the generator is exhausted
+        let (output, task) =
+            run(&["--json-command", "echo {}", "--json-generator"]);
+        assert!(output.generator_errors.is_empty());
+        assert_eq!(output.errors.values().sum::<u64>(), 1);
+        assert_eq!(task.statistics.get_total(), 1);
+    }
+
+    #[test]
+    fn test_finish_headers_signs_cookies() {
+        let path = write_temp(
+            "rsb_test_task_signed.txt",
+            "a.com\tFALSE\t/\tFALSE\t0\tsid\t1\n",
+        );
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--cookie-jar",
+            "--cookie-file",
+            path.to_str().unwrap(),
+            "--hmac-secret",
+            "secret",
+            "--hmac-signed-headers",
+            "cookie",
+            "http://a.com/",
+        ]);
+        let task = Task::new(arg, None).unwrap();
+        let signer = task.signer.as_deref().unwrap();
+        loop {
+            let mut request =
+                task.clients[0].get("http://a.com/").build().unwrap();
+            task.finish_headers(&mut request, task.cookies.as_ref(), None)
+                .unwrap();
+            assert_eq!(request.headers()[COOKIE], "sid=1");
+
+            // the signature covers the cookie, unless a second has passed
+            let mut expected = request.try_clone().unwrap();
+            signer.sign(&mut expected).unwrap();
+            if expected.headers()["x-timestamp"]
+                == request.headers()["x-timestamp"]
+            {
+                assert_eq!(
+                    expected.headers()["x-signature"],
+                    request.headers()["x-signature"]
+                );
+                expected.headers_mut().remove(COOKIE);
+                signer.sign(&mut expected).unwrap();
+                assert_ne!(
+                    expected.headers()["x-signature"],
+                    request.headers()["x-signature"]
+                );
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_once_with_stages() {
+        let path = write_temp("rsb_test_task_data_once.csv", "id\n1\n2\n");
+        // only one of the workers is active, nothing listens on the port
+        let arg = Arg::parse_from([
+            "rsb",
+            "-c",
+            "2",
+            "--stage",
+            "30s:1000:1",
+            "--data-once",
+            "--data-file",
+            path.to_str().unwrap(),
+            "http://127.0.0.1:1/{{id}}",
+        ]);
+        let started_at = Instant::now();
+        let task = Arc::new(Task::new(arg, None).unwrap()).run().unwrap();
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+        assert_eq!(task.statistics.get_total(), 2);
+
+        // the workers complete their last jobs, so the stage still has room
+        // for one
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dispatcher = task.dispatcher.read().await;
+            let job = tokio::time::timeout(
+                Duration::from_secs(1),
+                dispatcher.try_apply_job(),
+            )
+            .await;
+            assert!(job.unwrap().is_some());
+        });
+    }
 }
@@ -0,0 +1,438 @@
+//! mod template renders the placeholders in the url, headers and body of each
+//! request, the supported placeholders are:
+//!
+//! - `{{uuid}}`, a random UUID v4
+//! - `{{randInt 1 1000}}`, a random integer in the inclusive range
+//! - `{{seq}}`, the sequence number of the request, starts from 0
+//! - `{{now_ms}}`, the current unix timestamp in milliseconds
+//! - `{{worker_id}}`, the index of the connection sending the request
+//! - `{{name}}`, the value of the column `name` in the row of `--data-file`
+//!
+//! Only `{{` followed by a name, its arguments and `}}` is a placeholder, any
+//! other `{{` is kept as is, and `\{{name}}` is the literal `{{name}}`.
+//!
+//! Templates are compiled once before the task starts, so rendering them only
+//! costs a few string copies on the hot path.
+
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use regex::Regex;
+use reqwest::header::HeaderName;
+
+use crate::Arg;
+use crate::scenario::ScenarioRequest;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Uuid,
+    RandInt(i64, i64),
+    Seq,
+    NowMs,
+    WorkerId,
+    Var(usize),
+}
+
+impl Part {
+    fn parse(expr: &str, variables: &[String]) -> anyhow::Result<Self> {
+        let words: Vec<&str> = expr.split_whitespace().collect();
+        // variables shadow the builtin placeholders with the same name
+        if let [name] = words.as_slice()
+            && let Some(index) = variables.iter().position(|x| x == name)
+        {
+            return Ok(Part::Var(index));
+        }
+        let part = match words.as_slice() {
+            ["uuid"] => Part::Uuid,
+            ["seq"] => Part::Seq,
+            ["now_ms"] => Part::NowMs,
+            ["worker_id"] => Part::WorkerId,
+            ["randInt", min, max] => {
+                let (min, max) = (min.parse::<i64>()?, max.parse::<i64>()?);
+                if min > max {
+                    anyhow::bail!(
+                        "invalid placeholder `{{{{{expr}}}}}`, {min} is \
+                        greater than {max}"
+                    );
+                }
+                Part::RandInt(min, max)
+            },
+            _ => anyhow::bail!(
+                "unknown placeholder `{{{{{expr}}}}}`, supports: uuid, \
+                randInt <min> <max>, seq, now_ms, worker_id and the columns \
+                of --data-file"
+            ),
+        };
+        Ok(part)
+    }
+}
+
+/// [Context] is the state of the request being built
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Context<'a> {
+    /// the index of the connection sending the request
+    pub(crate) worker_id: usize,
+
+    /// the sequence number of the request
+    pub(crate) seq: u64,
+
+    /// the values of the variables, in the order of their names given to
+    /// [Template::compile]
+    pub(crate) values: &'a [String],
+}
+
+/// a placeholder, or an escaped one with the leading backslash
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\\?\{\{\s*([A-Za-z_][\w.-]*(?:\s+[^\s{}]+)*)\s*\}\}")
+        .expect("valid regex")
+});
+
+/// [Template] is a compiled string which may contain placeholders
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// compile the string, fails if any placeholder is unknown, `variables`
+    /// are the names of the values given by [Context]
+    pub(crate) fn compile(
+        input: &str,
+        variables: &[String],
+    ) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut last = 0;
+        for captures in PLACEHOLDER.captures_iter(input) {
+            let matched = captures.get(0).expect("whole match");
+            literal.push_str(&input[last..matched.start()]);
+            last = matched.end();
+            if let Some(escaped) = matched.as_str().strip_prefix('\\') {
+                literal.push_str(escaped);
+                continue;
+            }
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::parse(&captures[1], variables)?);
+        }
+        literal.push_str(&input[last..]);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// the template without any placeholder, the input is kept as is
+    pub(crate) fn literal(input: &str) -> Self {
+        Self {
+            parts: vec![Part::Literal(input.to_string())],
+        }
+    }
+
+    /// whether the template has no placeholder
+    pub(crate) fn is_literal(&self) -> bool {
+        self.parts
+            .iter()
+            .all(|part| matches!(part, Part::Literal(_)))
+    }
+
+    /// render the template with the state of the request
+    pub(crate) fn render(&self, context: &Context) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => output.push_str(literal),
+                Part::Uuid => {
+                    output.push_str(&uuid::Uuid::new_v4().to_string())
+                },
+                Part::RandInt(min, max) => output.push_str(
+                    &rand::thread_rng().gen_range(*min..=*max).to_string(),
+                ),
+                Part::Seq => output.push_str(&context.seq.to_string()),
+                Part::NowMs => output.push_str(
+                    &SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |now| now.as_millis())
+                        .to_string(),
+                ),
+                Part::WorkerId => {
+                    output.push_str(&context.worker_id.to_string())
+                },
+                Part::Var(index) => {
+                    if let Some(value) = context.values.get(*index) {
+                        output.push_str(value)
+                    }
+                },
+            }
+        }
+        output
+    }
+}
+
+/// whether the string contains any placeholder, including the escaped ones
+pub(crate) fn is_template(input: &str) -> bool {
+    PLACEHOLDER.is_match(input)
+}
+
+/// compile the values of the `k:v` pairs given on the command line
+fn compile_pairs(
+    pairs: &[String],
+    variables: &[String],
+) -> anyhow::Result<Vec<(String, Template)>> {
+    pairs
+        .iter()
+        .filter_map(|kv| kv.trim().split_once(':'))
+        .map(|(k, v)| Ok((k.to_string(), Template::compile(v, variables)?)))
+        .collect()
+}
+
+/// [Templates] are the parts of the request given on the command line, which
+/// are compiled once and rendered for each request
+#[derive(Debug, Default)]
+pub(crate) struct Templates {
+    pub(crate) url: Option<Template>,
+
+    /// only the headers containing placeholders, the others are set as the
+    /// default headers of the client
+    pub(crate) headers: Vec<(HeaderName, Template)>,
+
+    pub(crate) text_body: Option<Template>,
+
+    pub(crate) json_body: Option<Template>,
+
+    pub(crate) form: Vec<(String, Template)>,
+
+    pub(crate) mp: Vec<(String, Template)>,
+}
+
+impl Templates {
+    /// whether every part is the same for all requests
+    pub(crate) fn is_literal(&self) -> bool {
+        let literal = |template: &Option<Template>| {
+            template.as_ref().is_none_or(Template::is_literal)
+        };
+        literal(&self.url)
+            && self.headers.is_empty()
+            && literal(&self.text_body)
+            && literal(&self.json_body)
+            && self.form.iter().all(|(_, value)| value.is_literal())
+            && self.mp.iter().all(|(_, value)| value.is_literal())
+    }
+
+    /// compile the parts of the request, `variables` are the columns of
+    /// `--data-file`
+    pub(crate) fn compile(
+        arg: &Arg,
+        variables: &[String],
+    ) -> anyhow::Result<Self> {
+        let compile = |input: &str| Template::compile(input, variables);
+        let mut headers = vec![];
+        for header in &arg.headers {
+            if let Some((name, value)) = header.trim().split_once(':')
+                && is_template(value)
+            {
+                headers.push((
+                    HeaderName::from_bytes(name.trim().as_bytes())?,
+                    compile(value.trim())?,
+                ));
+            }
+        }
+
+        Ok(Self {
+            url: arg.url.as_deref().map(compile).transpose()?,
+            headers,
+            text_body: arg.text_body.as_deref().map(compile).transpose()?,
+            json_body: arg.json_body.as_deref().map(compile).transpose()?,
+            form: compile_pairs(&arg.form, variables)?,
+            mp: compile_pairs(&arg.mp, variables)?,
+        })
+    }
+
+    /// compile the parts of the named request of the scenario, they are kept
+    /// as is if `variables` is None
+    pub(crate) fn compile_request(
+        request: &ScenarioRequest,
+        variables: Option<&[String]>,
+    ) -> anyhow::Result<Self> {
+        let compile = |input: &str| match variables {
+            Some(variables) => Template::compile(input, variables),
+            None => Ok(Template::literal(input)),
+        };
+        let mut headers = vec![];
+        for (name, value) in &request.headers {
+            headers.push((
+                HeaderName::from_bytes(name.as_bytes())?,
+                compile(value)?,
+            ));
+        }
+        let mut form = vec![];
+        for (name, value) in request.form.iter().flatten() {
+            form.push((name.clone(), compile(value)?));
+        }
+
+        Ok(Self {
+            url: Some(compile(&request.url)?),
+            headers,
+            text_body: request.body.as_deref().map(compile).transpose()?,
+            json_body: request
+                .json
+                .as_ref()
+                .map(|json| compile(&json.to_string()))
+                .transpose()?,
+            form,
+            mp: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn render(input: &str, context: &Context) -> String {
+        Template::compile(input, &[]).unwrap().render(context)
+    }
+
+    fn compile(input: &str) -> anyhow::Result<Template> {
+        Template::compile(input, &[])
+    }
+
+    #[test]
+    fn test_template_compile() {
+        let template = compile("/items/{{ seq }}?r={{randInt 1 9}}").unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Literal("/items/".to_string()),
+                Part::Seq,
+                Part::Literal("?r=".to_string()),
+                Part::RandInt(1, 9),
+            ]
+        );
+        assert_eq!(compile("").unwrap().parts, vec![]);
+
+        // only the valid placeholders are templates
+        for literal in ["{{seq", "{{}}", "a {{ {\"b\": 1}}", "{{\"a\":1}}"] {
+            assert!(!is_template(literal), "{literal}");
+            assert_eq!(
+                compile(literal).unwrap().parts,
+                vec![Part::Literal(literal.to_string())]
+            );
+        }
+        let template = compile(r#"{"id":{{seq}}, "raw": "\{{seq}}"}"#).unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Literal("{\"id\":".to_string()),
+                Part::Seq,
+                Part::Literal(r#", "raw": "{{seq}}"}"#.to_string()),
+            ]
+        );
+        assert!(is_template(r"\{{seq}}"));
+
+        assert!(compile("{{unknown}}").is_err());
+        assert!(compile("{{randInt 1}}").is_err());
+        assert!(compile("{{randInt a 2}}").is_err());
+        assert!(compile("{{randInt 9 1}}").is_err());
+    }
+
+    #[test]
+    fn test_template_variables() {
+        let variables = vec!["user_id".to_string(), "seq".to_string()];
+        let template =
+            Template::compile("/users/{{user_id}}/{{seq}}", &variables)
+                .unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Literal("/users/".to_string()),
+                Part::Var(0),
+                Part::Literal("/".to_string()),
+                Part::Var(1),
+            ]
+        );
+
+        let values = vec!["1001".to_string(), "a".to_string()];
+        let context = Context {
+            worker_id: 0,
+            seq: 7,
+            values: &values,
+        };
+        assert_eq!(template.render(&context), "/users/1001/a");
+        assert!(Template::compile("{{name}}", &variables).is_err());
+    }
+
+    #[test]
+    fn test_template_render() {
+        let context = Context {
+            worker_id: 3,
+            seq: 42,
+            values: &[],
+        };
+        assert_eq!(render("plain", &context), "plain");
+        assert_eq!(render("{{seq}}-{{worker_id}}", &context), "42-3");
+        assert_eq!(render("{{randInt 7 7}}", &context), "7");
+
+        let value: i64 = render("{{randInt -5 5}}", &context).parse().unwrap();
+        assert!((-5..=5).contains(&value));
+
+        let uuid = render("{{uuid}}", &context);
+        assert_eq!(uuid.len(), 36);
+        assert_ne!(uuid, render("{{uuid}}", &context));
+
+        let now_ms: u128 = render("{{now_ms}}", &context).parse().unwrap();
+        assert!(now_ms > 1_600_000_000_000);
+    }
+
+    #[test]
+    fn test_templates_compile() {
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--text-body",
+            "{{worker_id}}",
+            "-H",
+            "x-static:1",
+            "x-id: id-{{seq}}",
+            "--",
+            "http://example.com/{{seq}}",
+        ]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        let context = Context {
+            worker_id: 1,
+            seq: 2,
+            values: &[],
+        };
+        assert_eq!(
+            templates.url.unwrap().render(&context),
+            "http://example.com/2"
+        );
+        assert_eq!(templates.headers.len(), 1);
+        assert_eq!(templates.headers[0].0, "x-id");
+        assert_eq!(templates.headers[0].1.render(&context), "id-2");
+        assert_eq!(templates.text_body.unwrap().render(&context), "1");
+        assert!(templates.json_body.is_none());
+
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--form",
+            "k:{{seq}}",
+            "--",
+            "http://example.com",
+        ]);
+        let templates = Templates::compile(&arg, &[]).unwrap();
+        assert_eq!(templates.form[0].0, "k");
+        assert_eq!(templates.form[0].1.render(&context), "2");
+
+        let arg = Arg::parse_from(["rsb", "-n", "1", "http://a.com/{{x}}"]);
+        assert!(Templates::compile(&arg, &[]).is_err());
+    }
+}
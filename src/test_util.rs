@@ -0,0 +1,13 @@
+//! mod test_util is shared by the tests of the other modules
+
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// write the file into the temporary directory, the name is unique to this
+/// process, so that concurrent runs of the tests don't overwrite each other's
+/// files
+pub(crate) fn write_temp(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("{}_{name}", std::process::id()));
+    fs::write(&path, content).unwrap();
+    path
+}
@@ -0,0 +1,336 @@
+//! mod threshold checks the final [Output] against the pass/fail criteria
+//! given by `--threshold`, e.g. `p99<250ms`, `error_rate<1%` and `rps>1000`,
+//! the supported metrics are:
+//!
+//! - `avg`, `min`, `max`, `stdev` and percentiles like `p99` or `p99.9`, the
+//!   latency of requests, compared with a time like `250ms`
+//! - `error_rate`, the rate of requests which met an error, failed any
+//!   assertion or extraction, or whose body failed to be generated, each
+//!   request is counted once, compared with a percentage like `1%` or a
+//!   fraction like `0.01`
+//! - `rps` and `throughput`, compared with a number
+
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::arg::{parse_latency, parse_percentiles};
+use crate::output::{Output, ThresholdOutput, decode_histogram};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Avg,
+    Min,
+    Max,
+    Stdev,
+    Percentile(f64),
+    ErrorRate,
+    Rps,
+    Throughput,
+}
+
+impl Metric {
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        let metric = match name {
+            "avg" => Metric::Avg,
+            "min" => Metric::Min,
+            "max" => Metric::Max,
+            "stdev" => Metric::Stdev,
+            "error_rate" => Metric::ErrorRate,
+            "rps" => Metric::Rps,
+            "throughput" => Metric::Throughput,
+            _ if name.starts_with('p') => {
+                Metric::Percentile(parse_percentiles(name)?)
+            },
+            _ => anyhow::bail!(
+                "unknown metric `{name}`, supports: avg, min, max, stdev, \
+                p99, error_rate, rps and throughput"
+            ),
+        };
+        Ok(metric)
+    }
+
+    fn is_latency(&self) -> bool {
+        matches!(
+            self,
+            Metric::Avg
+                | Metric::Min
+                | Metric::Max
+                | Metric::Stdev
+                | Metric::Percentile(_)
+        )
+    }
+
+    /// parse the expected value, latencies are converted to microseconds and
+    /// rates to fractions
+    fn parse_value(&self, value: &str) -> anyhow::Result<f64> {
+        let value = value.trim();
+        if self.is_latency() {
+            return Ok(parse_latency(value)?.as_micros() as f64);
+        }
+        match (self, value.strip_suffix('%')) {
+            (Metric::ErrorRate, Some(percent)) => {
+                Ok(percent.trim().parse::<f64>()? / 100f64)
+            },
+            _ => Ok(value.parse::<f64>()?),
+        }
+    }
+
+    /// the actual value of the metric in the output, None if it's unknown
+    fn actual(&self, output: &Output) -> Option<f64> {
+        let micros = |micros| Duration::from(micros).as_micros() as f64;
+        match self {
+            Metric::Avg => Some(micros(output.avg_req_used_time)),
+            Metric::Min => Some(micros(output.min_req_used_time)),
+            Metric::Max => Some(micros(output.max_req_used_time)),
+            Metric::Stdev => Some(micros(output.stdev_req_used_time)),
+            Metric::Percentile(percent) => output
+                .latencies
+                .iter()
+                .find(|latency| (latency.percent - percent).abs() < 1e-9)
+                .map(|latency| micros(latency.micros))
+                .or_else(|| {
+                    let histogram = decode_histogram(&output.histogram)?;
+                    (!histogram.is_empty())
+                        .then(|| histogram.value_at_quantile(*percent) as f64)
+                }),
+            Metric::ErrorRate => (output.total > 0)
+                .then(|| output.total_failed as f64 / output.total as f64),
+            Metric::Rps => Some(output.avg_req_per_second),
+            Metric::Throughput => Some(output.throughput),
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self {
+            _ if self.is_latency() => {
+                format!("{:.2?}", Duration::from_micros(value as u64))
+            },
+            Metric::ErrorRate => format!("{:.2}%", value * 100f64),
+            _ => format!("{value:.2}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn compare(&self, actual: f64, expected: f64) -> bool {
+        match self {
+            Operator::Less => actual < expected,
+            Operator::LessOrEqual => actual <= expected,
+            Operator::Greater => actual > expected,
+            Operator::GreaterOrEqual => actual >= expected,
+        }
+    }
+}
+
+/// [Threshold] is a pass/fail criterion of the final result
+#[derive(Debug, Clone)]
+pub(crate) struct Threshold {
+    expr: String,
+    metric: Metric,
+    operator: Operator,
+    value: f64,
+}
+
+impl Threshold {
+    /// check the output against the threshold
+    pub(crate) fn check(&self, output: &Output) -> ThresholdOutput {
+        let actual = self.metric.actual(output);
+        ThresholdOutput {
+            threshold: self.expr.clone(),
+            passed: actual.is_some_and(|actual| {
+                self.operator.compare(actual, self.value)
+            }),
+            actual: actual.map(|actual| self.metric.format(actual)),
+        }
+    }
+}
+
+impl Display for Threshold {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+/// parse the criterion given by `--threshold`
+pub(crate) fn parse_threshold(arg: &str) -> anyhow::Result<Threshold> {
+    let expr = arg.trim();
+    let pos = expr.find(['<', '>']).ok_or(anyhow::anyhow!(
+        "invalid threshold `{expr}`, examples: p99<250ms, error_rate<1%, \
+        rps>1000"
+    ))?;
+    let (name, rest) = expr.split_at(pos);
+    let (operator, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Operator::LessOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Operator::GreaterOrEqual, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Operator::Less, value)
+    } else {
+        (Operator::Greater, &rest[1..])
+    };
+    let metric = Metric::parse(name.trim())?;
+
+    Ok(Threshold {
+        expr: expr.to_string(),
+        metric,
+        operator,
+        value: metric.parse_value(value)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::output::{Latency, Micros};
+    use crate::statistics::{Message, Statistics};
+
+    fn output() -> Output {
+        let mut output =
+            Output::sync_from_statistics(&Statistics::new()).unwrap();
+        output.avg_req_used_time = Micros::from(Duration::from_millis(10));
+        output.max_req_used_time = Micros::from(Duration::from_millis(300));
+        output.latencies =
+            vec![Latency::new(0.99, Micros::from(Duration::from_millis(200)))];
+        output.rsp2xx = 98;
+        output.errors = HashMap::from([("timeout".to_string(), 2)]);
+        output.total_assertion_failed = 1;
+        output.total = 100;
+        output.total_failed = 3;
+        output.avg_req_per_second = 1200.0;
+        output
+    }
+
+    fn passed(expr: &str, output: &Output) -> bool {
+        parse_threshold(expr).unwrap().check(output).passed
+    }
+
+    #[test]
+    fn test_parse_threshold() {
+        let threshold = parse_threshold("p99.9 < 250ms").unwrap();
+        assert_eq!(threshold.metric, Metric::Percentile(0.999));
+        assert_eq!(threshold.operator, Operator::Less);
+        assert_eq!(threshold.value, 250_000.0);
+        assert_eq!(threshold.to_string(), "p99.9 < 250ms");
+
+        let threshold = parse_threshold("error_rate<=1%").unwrap();
+        assert_eq!(threshold.metric, Metric::ErrorRate);
+        assert_eq!(threshold.operator, Operator::LessOrEqual);
+        assert_eq!(threshold.value, 0.01);
+        assert_eq!(parse_threshold("error_rate<0.05").unwrap().value, 0.05);
+
+        let threshold = parse_threshold("rps>=1000").unwrap();
+        assert_eq!(threshold.metric, Metric::Rps);
+        assert_eq!(threshold.operator, Operator::GreaterOrEqual);
+        assert_eq!(threshold.value, 1000.0);
+
+        assert!(parse_threshold("p99").is_err());
+        assert!(parse_threshold("p100<1s").is_err());
+        assert!(parse_threshold("latency<1s").is_err());
+        assert!(parse_threshold("avg<fast").is_err());
+        assert!(parse_threshold("rps>1k").is_err());
+    }
+
+    #[test]
+    fn test_threshold_check() {
+        let output = output();
+        assert!(passed("p99<250ms", &output));
+        assert!(!passed("p99<200ms", &output));
+        assert!(passed("p99<=200ms", &output));
+        assert!(passed("avg<11ms", &output));
+        assert!(!passed("max<250ms", &output));
+        assert!(passed("error_rate<=3%", &output));
+        assert!(!passed("error_rate<3%", &output));
+        assert!(passed("rps>1000", &output));
+        assert!(!passed("rps>1200", &output));
+
+        let result = parse_threshold("p99<200ms").unwrap().check(&output);
+        assert_eq!(result.threshold, "p99<200ms");
+        assert_eq!(result.actual, Some("200.00ms".to_string()));
+        let result = parse_threshold("error_rate<1%").unwrap().check(&output);
+        assert_eq!(result.actual, Some("3.00%".to_string()));
+    }
+
+    #[test]
+    fn test_threshold_check_without_requests() {
+        let output = Output::sync_from_statistics(&Statistics::new()).unwrap();
+        let result = parse_threshold("error_rate<1%").unwrap().check(&output);
+        assert!(!result.passed);
+        assert_eq!(result.actual, None);
+        assert!(!passed("p99.99<1s", &output));
+    }
+
+    #[tokio::test]
+    async fn test_threshold_check_websocket() {
+        let stats = Statistics::new().with_websocket();
+        for i in 0..100 {
+            let result = match i < 98 {
+                true => Ok(()),
+                false => Err(anyhow::anyhow!("WebSocket closed by the server")),
+            };
+            let now = Instant::now();
+            stats.handle_message(Message::reply(result, now, now)).await;
+        }
+        let output = Output::from_statistics(&stats).await;
+        assert!(passed("error_rate<=2%", &output));
+        assert!(!passed("error_rate<2%", &output));
+    }
+
+    #[tokio::test]
+    async fn test_threshold_check_error_rate() {
+        let stats = Statistics::new();
+        let response = || {
+            let response = http::Response::builder().body("").unwrap();
+            anyhow::Ok(reqwest::Response::from(response))
+        };
+        let now = Instant::now();
+        for _ in 0..6 {
+            stats
+                .handle_message(Message::new(response(), now, now))
+                .await;
+        }
+        // the response is counted in the HTTP codes and the errors, but it's
+        // one failed request
+        let message = Message::new(response(), now, now)
+            .with_error("failed to extract `token`".to_string());
+        stats.handle_message(message).await;
+        let error = Err(anyhow::anyhow!("connection refused"));
+        stats
+            .handle_message(Message::new::<anyhow::Error>(error, now, now))
+            .await;
+        stats
+            .handle_generator_error("exit status: 1".to_string())
+            .await;
+
+        let output = Output::from_statistics(&stats).await;
+        assert_eq!(output.rsp2xx, 7);
+        assert_eq!(output.errors.values().sum::<u64>(), 2);
+        let result = parse_threshold("error_rate<1%").unwrap().check(&output);
+        assert_eq!(result.actual, Some("33.33%".to_string()));
+    }
+
+    #[test]
+    fn test_threshold_check_percentile_from_histogram() {
+        let stats = Statistics::new();
+        {
+            let mut used_time = stats.used_time.blocking_lock();
+            for micros in 1..=1000 {
+                used_time.record(micros).unwrap();
+            }
+        }
+        let output = Output::sync_from_statistics(&stats).unwrap();
+        assert!(output.latencies.is_empty());
+        assert!(passed("p99.9<=999us", &output));
+        assert!(!passed("p99.9<999us", &output));
+    }
+}
@@ -0,0 +1,465 @@
+//! mod websocket sends the messages over WebSocket for the `ws://` and
+//! `wss://` urls, each connection keeps its own socket, on which the messages
+//! are sent one by one, and each of them waits for its reply, so the latency
+//! is the round trip of the message.
+//!
+//! The reply of a message is the first text or binary message received after
+//! it's sent, with `--ws-match`, it's the one whose value at the json path
+//! equals the value of the message sent, and the others received meanwhile are
+//! counted as unmatched.
+
+use std::fs as sfs;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use native_tls::TlsConnector;
+use reqwest::header::{CONNECTION, HeaderMap};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+use tokio_tungstenite::{
+    Connector, MaybeTlsStream, WebSocketStream, connect_async_tls_with_config,
+};
+
+use crate::Arg;
+use crate::client::{build_tls_connector, default_headers};
+use crate::json_path::{JsonPath, value_to_string};
+use crate::template::{Context, Templates};
+
+/// the close code of a socket which is closed without a close frame
+const ABNORMAL_CLOSURE: u16 = 1006;
+
+/// the close code of a close frame without a code
+const NO_STATUS_RECEIVED: u16 = 1005;
+
+/// [WebSocket] opens the sockets of the workers and renders their messages
+pub(crate) struct WebSocket {
+    connector: TlsConnector,
+    /// the headers of the handshake given by `-H`, `--basic` and `--bearer`
+    headers: HeaderMap,
+    /// the content of `--text-file` or `--json-file`
+    file: Option<Bytes>,
+    matcher: Option<JsonPath>,
+    timeout: Duration,
+}
+
+impl WebSocket {
+    pub(crate) fn new(arg: &Arg) -> anyhow::Result<Self> {
+        let unsupported = [
+            ("--scenario", arg.scenario.is_some()),
+            ("--har", arg.har.is_some()),
+            ("--openapi", arg.openapi.is_some()),
+            ("--form", !arg.form.is_empty()),
+            ("--mp", !arg.mp.is_empty() || !arg.mp_file.is_empty()),
+            ("--json-command", arg.json_command.is_some()),
+            ("--http3", arg.http3),
+            ("--assert", !arg.assertions.is_empty()),
+            ("--cookie-jar", arg.cookie_jar),
+            ("--aws-sigv4", arg.aws_sigv4.is_some()),
+            ("--hmac-secret", arg.hmac_secret.is_some()),
+            ("--stream", arg.stream),
+        ];
+        if let Some((option, _)) = unsupported.iter().find(|(_, given)| *given)
+        {
+            anyhow::bail!("`{option}` isn't supported with WebSocket urls");
+        }
+        if arg.text_body.is_none()
+            && arg.json_body.is_none()
+            && arg.text_file.is_none()
+            && arg.json_file.is_none()
+        {
+            anyhow::bail!(
+                "WebSocket urls need a message given by --text-body, \
+                --json-body, --text-file or --json-file"
+            );
+        }
+
+        let file = match (&arg.text_file, &arg.json_file) {
+            (Some(path), _) | (None, Some(path)) => {
+                Some(Bytes::from(sfs::read(path)?))
+            },
+            (None, None) => None,
+        };
+        Ok(Self {
+            connector: build_tls_connector(arg, &[])?,
+            headers: default_headers(arg)?,
+            file,
+            matcher: arg.ws_match.clone(),
+            timeout: arg.timeout,
+        })
+    }
+
+    /// the message sent for each job, the files which aren't valid UTF-8 are
+    /// sent as binary messages
+    pub(crate) fn message(
+        &self,
+        templates: &Templates,
+        context: &Context,
+    ) -> Message {
+        let template = templates
+            .text_body
+            .as_ref()
+            .or(templates.json_body.as_ref());
+        if let Some(template) = template {
+            return Message::text(template.render(context));
+        }
+        let file = self.file.clone().unwrap_or_default();
+        match Utf8Bytes::try_from(file.clone()) {
+            Ok(text) => Message::Text(text),
+            Err(_) => Message::Binary(file),
+        }
+    }
+
+    /// open a socket, `headers` are rendered for the connection, and take
+    /// precedence over the default ones
+    pub(crate) async fn connect(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+    ) -> anyhow::Result<Socket> {
+        let mut request = url.into_client_request()?;
+        let mut merged = self.headers.clone();
+        merged.extend(headers);
+        for (name, value) in &merged {
+            // the headers of the upgrade are kept
+            let name = HeaderName::from_bytes(name.as_str().as_bytes())?;
+            if name == CONNECTION.as_str()
+                || request.headers().contains_key(&name)
+            {
+                continue;
+            }
+            request
+                .headers_mut()
+                .insert(name, HeaderValue::from_bytes(value.as_bytes())?);
+        }
+
+        let connector = Connector::NativeTls(self.connector.clone());
+        let connect =
+            connect_async_tls_with_config(request, None, true, Some(connector));
+        let (stream, _) = tokio::time::timeout(self.timeout, connect)
+            .await
+            .map_err(|_| {
+            anyhow::anyhow!(
+                "WebSocket handshake timed out after {:?}",
+                self.timeout
+            )
+        })??;
+        Ok(Socket { stream })
+    }
+
+    /// send the message and wait for its reply
+    pub(crate) async fn round_trip(
+        &self,
+        socket: &mut Socket,
+        message: Message,
+    ) -> RoundTrip {
+        let key = self
+            .matcher
+            .as_ref()
+            .and_then(|path| select(path, &message));
+        let mut round_trip = RoundTrip {
+            result: Ok(()),
+            rsp_bytes: 0,
+            unmatched: 0,
+            closed: None,
+        };
+        let exchange = socket.exchange(
+            message,
+            self.matcher.as_ref().zip(key.as_ref()),
+            &mut round_trip,
+        );
+        round_trip.result =
+            match tokio::time::timeout(self.timeout, exchange).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow::anyhow!(
+                    "WebSocket reply timed out after {:?}",
+                    self.timeout
+                )),
+            };
+        round_trip
+    }
+
+    /// close the socket, returns the code of the close frame of the server
+    pub(crate) async fn close(&self, socket: Socket) -> u16 {
+        tokio::time::timeout(self.timeout, socket.close())
+            .await
+            .unwrap_or(ABNORMAL_CLOSURE)
+    }
+}
+
+/// the value of the message at the json path, None if the message isn't json
+/// or the value doesn't exist
+fn select(path: &JsonPath, message: &Message) -> Option<String> {
+    let data = match message {
+        Message::Text(text) => text.as_bytes(),
+        Message::Binary(data) => data,
+        _ => return None,
+    };
+    let value: Value = serde_json::from_slice(data).ok()?;
+    path.select(&value).map(value_to_string)
+}
+
+/// [RoundTrip] is the result of sending a message
+#[derive(Debug)]
+pub(crate) struct RoundTrip {
+    /// the failure to send the message or to receive its reply
+    pub(crate) result: anyhow::Result<()>,
+    /// number of bytes received, including the unmatched messages
+    pub(crate) rsp_bytes: u64,
+    /// number of messages received which aren't the reply
+    pub(crate) unmatched: u64,
+    /// the close code once the socket is closed by the server or dropped
+    pub(crate) closed: Option<u16>,
+}
+
+/// [Socket] is the WebSocket connection of a worker
+pub(crate) struct Socket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Socket {
+    /// send the message, and read until its reply, which is the first data
+    /// message without `matcher`, or the one whose value at the path is the
+    /// given one
+    async fn exchange(
+        &mut self,
+        message: Message,
+        matcher: Option<(&JsonPath, &String)>,
+        round_trip: &mut RoundTrip,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self.stream.send(message).await {
+            round_trip.closed = Some(ABNORMAL_CLOSURE);
+            return Err(e.into());
+        }
+        loop {
+            let received = match self.stream.next().await {
+                Some(Ok(received)) => received,
+                Some(Err(e)) => {
+                    round_trip.closed = Some(ABNORMAL_CLOSURE);
+                    return Err(e.into());
+                },
+                None => {
+                    round_trip.closed = Some(ABNORMAL_CLOSURE);
+                    anyhow::bail!("WebSocket closed before the reply");
+                },
+            };
+            match &received {
+                Message::Text(_) | Message::Binary(_) => {},
+                Message::Close(frame) => {
+                    round_trip.closed = Some(close_code(frame.as_ref()));
+                    anyhow::bail!("WebSocket closed by the server");
+                },
+                // the pings are answered by the stream
+                _ => continue,
+            }
+            round_trip.rsp_bytes += received.len() as u64;
+            match matcher {
+                Some((path, key))
+                    if select(path, &received).as_ref() != Some(key) =>
+                {
+                    round_trip.unmatched += 1;
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// send a normal close frame, and wait for the one of the server
+    async fn close(mut self) -> u16 {
+        let frame = CloseFrame {
+            code: CloseCode::Normal,
+            reason: Utf8Bytes::default(),
+        };
+        if self.stream.close(Some(frame)).await.is_err() {
+            return ABNORMAL_CLOSURE;
+        }
+        while let Some(Ok(received)) = self.stream.next().await {
+            if let Message::Close(frame) = received {
+                return close_code(frame.as_ref());
+            }
+        }
+        ABNORMAL_CLOSURE
+    }
+}
+
+fn close_code(frame: Option<&CloseFrame>) -> u16 {
+    frame.map_or(NO_STATUS_RECEIVED, |frame| frame.code.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, mpsc};
+
+    use clap::Parser;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::Task;
+    use crate::template::Context;
+
+    /// start a WebSocket server in the background, which pushes a message
+    /// before echoing each one, and closes the socket with 1001 on `bye`
+    fn start_server() -> SocketAddr {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream));
+                }
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    async fn serve(stream: TcpStream) {
+        let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await
+        else {
+            return;
+        };
+        while let Some(Ok(received)) = socket.next().await {
+            let Message::Text(text) = received else {
+                continue;
+            };
+            if text.as_str() == "bye" {
+                let frame = CloseFrame {
+                    code: CloseCode::Away,
+                    reason: Utf8Bytes::default(),
+                };
+                let _ = socket.close(Some(frame)).await;
+                continue;
+            }
+            let push = Message::text(r#"{"id": "push"}"#);
+            if socket.send(push).await.is_err()
+                || socket.send(Message::Text(text)).await.is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    fn message(websocket: &WebSocket, arg: &Arg, seq: u64) -> Message {
+        let templates = Templates::compile(arg, &[]).unwrap();
+        let context = Context {
+            seq,
+            ..Default::default()
+        };
+        websocket.message(&templates, &context)
+    }
+
+    #[tokio::test]
+    async fn test_websocket_round_trip() {
+        let addr = start_server();
+        let url = format!("ws://{addr}/");
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--json-body",
+            r#"{"id": "{{seq}}"}"#,
+            "--ws-match",
+            "$.id",
+            &url,
+        ]);
+        let websocket = WebSocket::new(&arg).unwrap();
+        let mut socket =
+            websocket.connect(&url, HeaderMap::new()).await.unwrap();
+        for seq in 0..2 {
+            let sent = message(&websocket, &arg, seq);
+            assert_eq!(sent, Message::text(format!(r#"{{"id": "{seq}"}}"#)));
+            let round_trip = websocket.round_trip(&mut socket, sent).await;
+            assert!(round_trip.result.is_ok());
+            // the push is received before the reply
+            assert_eq!(round_trip.unmatched, 1);
+            assert_eq!(round_trip.rsp_bytes, 14 + 11);
+        }
+        assert_eq!(websocket.close(socket).await, 1000);
+
+        // the first message received is the reply without `--ws-match`
+        let arg =
+            Arg::parse_from(["rsb", "-n", "1", "--text-body", "bye", &url]);
+        let websocket = WebSocket::new(&arg).unwrap();
+        let mut socket =
+            websocket.connect(&url, HeaderMap::new()).await.unwrap();
+        let sent = message(&websocket, &arg, 0);
+        let round_trip = websocket.round_trip(&mut socket, sent).await;
+        assert!(round_trip.result.is_err());
+        assert_eq!(round_trip.closed, Some(1001));
+
+        // no message is given
+        let arg = Arg::parse_from(["rsb", "-n", "1", &url]);
+        assert!(WebSocket::new(&arg).is_err());
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "1",
+            "--text-body",
+            "a",
+            "--cookie-jar",
+            &url,
+        ]);
+        assert!(WebSocket::new(&arg).is_err());
+    }
+
+    #[test]
+    fn test_websocket_task() {
+        let addr = start_server();
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "20",
+            "-c",
+            "2",
+            "--json-body",
+            r#"{"id": "{{seq}}"}"#,
+            "--ws-match",
+            "$.id",
+            &format!("ws://{addr}/"),
+        ]);
+        let output = Arc::new(Task::new(arg, None).unwrap())
+            .run()
+            .unwrap()
+            .json_output()
+            .unwrap();
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(output.rsp2xx, 0);
+        let websocket = output.websocket.unwrap();
+        assert_eq!(websocket.connects.total_success, 2);
+        assert_eq!(websocket.replies, 20);
+        assert_eq!(websocket.close_codes[&1000], 2);
+        assert_eq!(websocket.unmatched, 20);
+
+        // nothing listens on the discard port
+        let arg = Arg::parse_from([
+            "rsb",
+            "-n",
+            "2",
+            "-c",
+            "1",
+            "--text-body",
+            "a",
+            "ws://127.0.0.1:9/",
+        ]);
+        let output = Arc::new(Task::new(arg, None).unwrap())
+            .run()
+            .unwrap()
+            .json_output()
+            .unwrap();
+        assert_eq!(output.errors.values().sum::<u64>(), 2);
+        let websocket = output.websocket.unwrap();
+        assert_eq!(websocket.connects.total, 2);
+        assert_eq!(websocket.connects.total_success, 0);
+        assert!(websocket.close_codes.is_empty());
+    }
+}